@@ -1,5 +1,956 @@
+//! Build script: linker arguments and board pin mapping.
+//!
+//! The board description is selected with the `ORNAMENT_BOARD` environment
+//! variable (e.g. `ORNAMENT_BOARD=rev-b`) or, if that is unset, with exactly
+//! one `board-*` Cargo feature. It is read from `boards/<name>.toml` and
+//! turned into `Peripherals::new` in `$OUT_DIR/board.rs`, which
+//! `src/hardware.rs` includes. The LED backend the board uses is exported as
+//! the `led_backend` cfg (`"flip_flop"`, `"shift_register"`, `"charlieplex"`
+//! or `"rgb"`) so only its driver is compiled. A `[sync]` section sets the
+//! `sync_link` cfg, both bare and with the link (`"wire"` or `"optical"`).
+//! A `[light_sensor]` section sets the `light_sensor` cfg and makes
+//! `AmbientSensor` its sensing LED.
+//!
+//! Descriptions are read with `toml` into typed sections, so unknown
+//! sections and keys, missing keys and malformed pins are reported with
+//! their line. [`check`] then verifies what the types cannot express.
+//!
+//! On LED string boards the light show cue sheet, `shows/demo.csv` or the
+//! CSV file named by the `ORNAMENT_SHOW` environment variable, is compiled
+//! into the binary light sequence format (see `src/show.rs`) in
+//! `$OUT_DIR/show.bin` by `src/cue_sheet.rs`. Each line is `time, strings`:
+//! the time as `[minutes:]seconds[.fraction]` and the strings lit from then
+//! on, by name or index separated by spaces, `all` or `-` for none. A final
+//! `time, end` line sets the show's length.
+//!
+//! On LED string boards a personal Morse message can be set with the
+//! `ORNAMENT_MESSAGE` environment variable (printable ASCII, up to 32
+//! characters) and its speed with `ORNAMENT_MESSAGE_WPM` (2 to 20 words
+//! per minute). It is written to `$OUT_DIR/message.rs` for
+//! `src/morse_message.rs`, which stores it in EEPROM at boot.
+//!
+//! The battery selection policy (see `src/power_policy.rs`) is chosen with
+//! the `ORNAMENT_POWER_POLICY` environment variable: `failover` (the
+//! default), `balance:<hours>`, `reserve` or `single`. It is exported as the
+//! `power_policy` cfg and as the `BatteryPolicy` alias in `board.rs`.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::path::PathBuf;
+
+use indexmap::IndexMap;
+use serde::Deserialize;
+
+#[path = "src/cue_sheet.rs"]
+mod cue_sheet;
+
+use cue_sheet::compile_show;
+
+/// Pins that are wired to fixed functions on every board revision.
+const RESERVED_PINS: [(&str, &str); 4] = [
+    ("PA13", "SWDIO"),
+    ("PA14", "SWCLK"),
+    ("PC14", "LSE OSC32_IN"),
+    ("PC15", "LSE OSC32_OUT"),
+];
+
+/// Maximum number of LED strings (one bit each in a `Frame`).
+const MAX_STRINGS: usize = 32;
+
+/// Maximum number of addressable RGB LEDs (`ws2812::MAX_LEDS`).
+const MAX_RGB_LEDS: usize = 16;
+
+/// Cue sheet compiled when `ORNAMENT_SHOW` is unset.
+const DEFAULT_SHOW: &str = "shows/demo.csv";
+
+/// Longest Morse message (`morse::MAX_MESSAGE_LEN`).
+const MAX_MESSAGE_LEN: usize = 32;
+
+/// Accepted Morse speeds in words per minute (`morse::MIN_WPM..=MAX_WPM`).
+const MESSAGE_WPM: std::ops::RangeInclusive<u8> = 2..=20;
+
+/// Morse speed used when `ORNAMENT_MESSAGE_WPM` is unset (`morse::DEFAULT_WPM`).
+const DEFAULT_MESSAGE_WPM: u8 = 6;
+
+/// Battery selection policies and the types implementing them.
+const POWER_POLICIES: [(&str, &str); 4] = [
+    ("failover", "Failover"),
+    ("balance", "Balance"),
+    ("reserve", "Reserve"),
+    ("single", "SingleCell"),
+];
+
+/// Policy used when `ORNAMENT_POWER_POLICY` is unset.
+const DEFAULT_POWER_POLICY: &str = "failover";
+
+/// Longest `balance` period that fits the policy's u32 milliseconds.
+const MAX_BALANCE_HOURS: u32 = u32::MAX / 3_600_000;
+
 fn main() {
     println!("cargo:rustc-link-arg-bins=--nmagic");
     println!("cargo:rustc-link-arg-bins=-Tlink.x");
     println!("cargo:rustc-link-arg-bins=-Tdefmt.x");
+
+    let board = select_board();
+    let path = PathBuf::from(format!("boards/{board}.toml"));
+    println!("cargo:rerun-if-env-changed=ORNAMENT_BOARD");
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let text = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("cannot read board description {}: {e}", path.display()));
+    let board: Board = toml::from_str(&text).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+    check(&board).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+
+    println!(
+        "cargo::rustc-check-cfg=cfg(led_backend, values(\"flip_flop\", \"shift_register\", \"charlieplex\", \"rgb\"))"
+    );
+    println!("cargo::rustc-cfg=led_backend=\"{}\"", board.backend());
+    println!("cargo::rustc-check-cfg=cfg(sync_link, values(none(), \"wire\", \"optical\"))");
+    if let Some(sync) = &board.sync {
+        println!("cargo::rustc-cfg=sync_link");
+        println!("cargo::rustc-cfg=sync_link=\"{}\"", sync.link.name());
+    }
+    println!("cargo::rustc-check-cfg=cfg(light_sensor)");
+    if board.light_sensor.is_some() {
+        println!("cargo::rustc-cfg=light_sensor");
+    }
+
+    let policy =
+        env::var("ORNAMENT_POWER_POLICY").unwrap_or_else(|_| DEFAULT_POWER_POLICY.to_string());
+    println!("cargo:rerun-if-env-changed=ORNAMENT_POWER_POLICY");
+    let policy = parse_policy(&policy).unwrap_or_else(|e| panic!("ORNAMENT_POWER_POLICY: {e}"));
+    let names = POWER_POLICIES.map(|(name, _)| format!("\"{name}\""));
+    println!(
+        "cargo::rustc-check-cfg=cfg(power_policy, values({}))",
+        names.join(", ")
+    );
+    println!("cargo::rustc-cfg=power_policy=\"{}\"", policy.name);
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join("board.rs"), generate(&board, &policy)).unwrap();
+
+    if board.backend() != "rgb" {
+        let show = env::var("ORNAMENT_SHOW").unwrap_or_else(|_| DEFAULT_SHOW.to_string());
+        println!("cargo:rerun-if-env-changed=ORNAMENT_SHOW");
+        println!("cargo:rerun-if-changed={show}");
+
+        let text = fs::read_to_string(&show)
+            .unwrap_or_else(|e| panic!("cannot read cue sheet {show}: {e}"));
+        let sequence =
+            compile_show(&text, &board.string_names()).unwrap_or_else(|e| panic!("{show}: {e}"));
+        fs::write(out_dir.join("show.bin"), sequence).unwrap();
+
+        let message = env::var("ORNAMENT_MESSAGE").ok();
+        let wpm = env::var("ORNAMENT_MESSAGE_WPM").ok();
+        println!("cargo:rerun-if-env-changed=ORNAMENT_MESSAGE");
+        println!("cargo:rerun-if-env-changed=ORNAMENT_MESSAGE_WPM");
+        let code = generate_message(message.as_deref(), wpm.as_deref())
+            .unwrap_or_else(|e| panic!("ORNAMENT_MESSAGE: {e}"));
+        fs::write(out_dir.join("message.rs"), code).unwrap();
+    }
+}
+
+/// Generates the Morse message configured at build time.
+///
+/// # Arguments
+///
+/// * `message` - Value of `ORNAMENT_MESSAGE`, if set
+/// * `wpm` - Value of `ORNAMENT_MESSAGE_WPM`, if set
+fn generate_message(message: Option<&str>, wpm: Option<&str>) -> Result<String, String> {
+    let mut out = String::from(
+        "/// Message set with `ORNAMENT_MESSAGE` when the firmware was built, and\n\
+         /// its speed in words per minute.\n",
+    );
+
+    let Some(message) = message else {
+        if wpm.is_some() {
+            return Err("ORNAMENT_MESSAGE_WPM is set without a message".to_string());
+        }
+        out.push_str("pub const CONFIGURED_MESSAGE: Option<(&[u8], u8)> = None;\n");
+        return Ok(out);
+    };
+
+    if message.is_empty() || message.len() > MAX_MESSAGE_LEN {
+        return Err(format!("must be 1 to {MAX_MESSAGE_LEN} characters"));
+    }
+    if !message.bytes().all(|c| c == b' ' || c.is_ascii_graphic()) {
+        return Err("must be printable ASCII".to_string());
+    }
+    let wpm = match wpm {
+        None => DEFAULT_MESSAGE_WPM,
+        Some(wpm) => wpm
+            .parse()
+            .ok()
+            .filter(|wpm| MESSAGE_WPM.contains(wpm))
+            .ok_or_else(|| {
+                format!(
+                    "ORNAMENT_MESSAGE_WPM must be {} to {}, not {wpm:?}",
+                    MESSAGE_WPM.start(),
+                    MESSAGE_WPM.end()
+                )
+            })?,
+    };
+
+    writeln!(
+        out,
+        "pub const CONFIGURED_MESSAGE: Option<(&[u8], u8)> = Some((b{message:?}, {wpm}));"
+    )
+    .unwrap();
+    Ok(out)
+}
+
+/// Board description, as read from `boards/<name>.toml`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Board {
+    /// Board name, reported in diagnostics
+    name: String,
+    /// Battery load switch enables
+    power: PowerPins,
+    /// Flip-flop driven LED strings, by name in display order
+    #[serde(default)]
+    string: IndexMap<String, FlipFlopPins>,
+    /// 74HC595 chain driving the strings instead
+    shift_register: Option<ShiftRegisterPins>,
+    /// Charlieplexed LEDs instead of strings
+    charlieplex: Option<CharlieplexPins>,
+    /// WS2812 chain instead of strings
+    rgb: Option<RgbPins>,
+    /// Push-button
+    button: Option<ButtonPin>,
+    /// Sync link to other ornaments
+    sync: Option<SyncPins>,
+    /// Dedicated light sensing LED
+    light_sensor: Option<LightSensorPins>,
+    /// Reserved UART pins
+    uart: Option<UartPins>,
+}
+
+/// `[power]`: active-low load switch enables.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PowerPins {
+    /// MAIN_POWER_N
+    main_n: Pin,
+    /// BACKUP_POWER_N
+    backup_n: Pin,
+}
+
+/// `[string.<name>]`: flip-flop inputs and feedback of one LED string.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FlipFlopPins {
+    /// Active-low preset
+    pre_n: Pin,
+    /// Active-low clear
+    clr_n: Pin,
+    /// D input
+    data: Pin,
+    /// Clock input
+    clk: Pin,
+    /// LSTR string node, the flip-flop's Q output (only ever read)
+    feedback: Pin,
+}
+
+/// `[shift_register]`: 74HC595 chain and the strings on its outputs.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ShiftRegisterPins {
+    /// Serial data into the first register
+    ser: Pin,
+    /// Shift clock
+    srclk: Pin,
+    /// Latch clock
+    rclk: Pin,
+    /// String names, from QA of the first register
+    strings: Vec<String>,
+}
+
+/// `[charlieplex]`: charlieplexed pins and the LEDs between them.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CharlieplexPins {
+    /// Charlieplexed pins
+    pins: Vec<Pin>,
+    /// LED names, in scan order
+    leds: Vec<String>,
+}
+
+/// `[rgb]`: WS2812 data and supply enable, and the LEDs on the chain.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RgbPins {
+    /// SPI1 MOSI to the first LED
+    data: Pin,
+    /// Active-low LED supply enable
+    power_n: Pin,
+    /// LED names, in chain order
+    leds: Vec<String>,
+}
+
+/// `[button]`: push-button to GND.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ButtonPin {
+    /// Button input, also its EXTI line
+    pin: Pin,
+}
+
+/// `[light_sensor]`: LED used as a light sensor, on a pin of its own.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LightSensorPins {
+    /// Sensing LED cathode (anode to GND)
+    led: Pin,
+}
+
+/// `[sync]`: sync link input (and output on a wire leader).
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SyncPins {
+    /// Sync input, also its EXTI line
+    pin: Pin,
+    /// Wire or optical link
+    link: SyncLink,
+}
+
+/// Kind of sync link, the `sync_link` cfg value.
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SyncLink {
+    /// Open-drain wire shared by all ornaments
+    Wire,
+    /// Leader's LEDs seen by a phototransistor
+    Optical,
+}
+
+impl SyncLink {
+    /// Returns the name used in board descriptions and cfgs.
+    fn name(self) -> &'static str {
+        match self {
+            SyncLink::Wire => "wire",
+            SyncLink::Optical => "optical",
+        }
+    }
+}
+
+/// `[uart]`: reserved UART pins.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UartPins {
+    /// Transmit pin
+    tx: Pin,
+    /// Receive pin
+    rx: Pin,
+}
+
+/// GPIO pin by its embassy-stm32 name, such as `PA15`.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+struct Pin(String);
+
+impl TryFrom<String> for Pin {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, String> {
+        match pin_number(&name) {
+            Some(_) => Ok(Self(name)),
+            None => Err(format!("`{name}` is not a GPIO pin")),
+        }
+    }
+}
+
+impl Pin {
+    /// Returns the pin number, which is also its EXTI line.
+    fn line(&self) -> u8 {
+        pin_number(&self.0).unwrap()
+    }
+}
+
+impl fmt::Display for Pin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Board {
+    /// Returns the LED backend: `"shift_register"`, `"charlieplex"` or
+    /// `"rgb"` if the board has that section, `"flip_flop"` otherwise.
+    fn backend(&self) -> &'static str {
+        if self.shift_register.is_some() {
+            "shift_register"
+        } else if self.charlieplex.is_some() {
+            "charlieplex"
+        } else if self.rgb.is_some() {
+            "rgb"
+        } else {
+            "flip_flop"
+        }
+    }
+
+    /// Returns the LED string names in string index order, from the
+    /// `[string.<name>]` sections, the shift register's `strings` list or
+    /// the charlieplex or RGB `leds` list.
+    fn string_names(&self) -> Vec<&str> {
+        let list = if let Some(shift_register) = &self.shift_register {
+            &shift_register.strings
+        } else if let Some(charlieplex) = &self.charlieplex {
+            &charlieplex.leds
+        } else if let Some(rgb) = &self.rgb {
+            &rgb.leds
+        } else {
+            return self.string.keys().map(String::as_str).collect();
+        };
+        list.iter().map(String::as_str).collect()
+    }
+
+    /// Returns every pin the board uses, with its role (`section.key`).
+    fn pins(&self) -> Vec<(String, &Pin)> {
+        let mut pins = vec![
+            ("power.main_n".to_string(), &self.power.main_n),
+            ("power.backup_n".to_string(), &self.power.backup_n),
+        ];
+        for (name, string) in &self.string {
+            for (key, pin) in [
+                ("pre_n", &string.pre_n),
+                ("clr_n", &string.clr_n),
+                ("data", &string.data),
+                ("clk", &string.clk),
+                ("feedback", &string.feedback),
+            ] {
+                pins.push((format!("string.{name}.{key}"), pin));
+            }
+        }
+        if let Some(shift_register) = &self.shift_register {
+            pins.extend([
+                ("shift_register.ser".to_string(), &shift_register.ser),
+                ("shift_register.srclk".to_string(), &shift_register.srclk),
+                ("shift_register.rclk".to_string(), &shift_register.rclk),
+            ]);
+        }
+        if let Some(charlieplex) = &self.charlieplex {
+            for pin in &charlieplex.pins {
+                pins.push(("charlieplex.pins".to_string(), pin));
+            }
+        }
+        if let Some(rgb) = &self.rgb {
+            pins.extend([
+                ("rgb.data".to_string(), &rgb.data),
+                ("rgb.power_n".to_string(), &rgb.power_n),
+            ]);
+        }
+        if let Some(button) = &self.button {
+            pins.push(("button.pin".to_string(), &button.pin));
+        }
+        if let Some(sync) = &self.sync {
+            pins.push(("sync.pin".to_string(), &sync.pin));
+        }
+        if let Some(light_sensor) = &self.light_sensor {
+            pins.push(("light_sensor.led".to_string(), &light_sensor.led));
+        }
+        if let Some(uart) = &self.uart {
+            pins.extend([
+                ("uart.tx".to_string(), &uart.tx),
+                ("uart.rx".to_string(), &uart.rx),
+            ]);
+        }
+        pins
+    }
+}
+
+/// Picks the board name from `ORNAMENT_BOARD` or the `board-*` features.
+fn select_board() -> String {
+    if let Ok(board) = env::var("ORNAMENT_BOARD") {
+        return board;
+    }
+
+    let boards: Vec<String> = env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_BOARD_")
+                .map(|name| name.to_lowercase().replace('_', "-"))
+        })
+        .collect();
+
+    match boards.as_slice() {
+        [board] => board.clone(),
+        [] => panic!("no board selected: enable a `board-*` feature or set ORNAMENT_BOARD"),
+        _ => panic!("multiple boards selected: {boards:?}"),
+    }
+}
+
+/// Checks what the description's types cannot: a single LED backend, the
+/// string count, the sync link, and that no pin is used twice.
+fn check(board: &Board) -> Result<(), String> {
+    let backends = usize::from(!board.string.is_empty())
+        + usize::from(board.shift_register.is_some())
+        + usize::from(board.charlieplex.is_some())
+        + usize::from(board.rgb.is_some());
+    if backends != 1 {
+        return Err(
+            "expected exactly one LED backend: [string.<name>] sections, \
+             [shift_register], [charlieplex] or [rgb]"
+                .to_string(),
+        );
+    }
+
+    let strings = board.string_names().len();
+    let max = if board.rgb.is_some() {
+        MAX_RGB_LEDS
+    } else if let Some(charlieplex) = &board.charlieplex {
+        let pins = charlieplex.pins.len();
+        if pins < 2 {
+            return Err(format!("{pins} charlieplex pins, expected at least 2"));
+        }
+        MAX_STRINGS.min(pins * (pins - 1))
+    } else {
+        MAX_STRINGS
+    };
+    if !(1..=max).contains(&strings) {
+        return Err(format!("{strings} LED strings, expected 1 to {max}"));
+    }
+
+    if let Some(sync) = &board.sync {
+        if board.rgb.is_some() {
+            return Err("[sync] is not supported with [rgb]".to_string());
+        }
+        if let Some(button) = &board.button
+            && button.pin.line() == sync.pin.line()
+        {
+            return Err(format!(
+                "[sync] and [button] pins share EXTI line {}",
+                sync.pin.line()
+            ));
+        }
+    }
+
+    let mut used: BTreeMap<&str, String> = RESERVED_PINS
+        .iter()
+        .map(|(pin, role)| (*pin, role.to_string()))
+        .collect();
+    for (role, pin) in board.pins() {
+        if let Some(other) = used.insert(&pin.0, role.clone()) {
+            return Err(format!("pin {pin} is used by both {other} and {role}"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Battery selection policy picked with `ORNAMENT_POWER_POLICY`.
+struct Policy {
+    /// Policy name, as in [`POWER_POLICIES`]
+    name: &'static str,
+    /// Path of the type implementing the policy
+    path: String,
+    /// Expression constructing the policy
+    constructor: String,
+}
+
+/// Parses a policy given as `<name>` or, for `balance`, `balance:<hours>`.
+fn parse_policy(value: &str) -> Result<Policy, String> {
+    let (name, hours) = match value.split_once(':') {
+        Some((name, hours)) => (name.trim(), Some(hours.trim())),
+        None => (value.trim(), None),
+    };
+    let &(name, type_name) = POWER_POLICIES
+        .iter()
+        .find(|(policy, _)| *policy == name)
+        .ok_or_else(|| {
+            let names = POWER_POLICIES.map(|(name, _)| name);
+            format!("unknown policy `{name}`, expected one of {names:?}")
+        })?;
+
+    let path = format!("crate::power_policy::{type_name}");
+    let constructor = match (name, hours) {
+        ("balance", Some(hours)) => {
+            let hours = hours
+                .parse::<u32>()
+                .ok()
+                .filter(|hours| (1..=MAX_BALANCE_HOURS).contains(hours))
+                .ok_or_else(|| {
+                    format!("balance period `{hours}`, expected 1 to {MAX_BALANCE_HOURS} hours")
+                })?;
+            format!("{path}::new({hours})")
+        }
+        ("balance", None) => return Err("expected `balance:<hours>`".to_string()),
+        (_, Some(_)) => return Err(format!("policy `{name}` takes no argument")),
+        (_, None) => path.clone(),
+    };
+
+    Ok(Policy {
+        name,
+        path,
+        constructor,
+    })
+}
+
+/// Returns the pin number of a GPIO name such as `PA15`.
+fn pin_number(pin: &str) -> Option<u8> {
+    let rest = pin.strip_prefix('P')?;
+    let port = rest.chars().next()?;
+    let number: u8 = rest[1..].parse().ok()?;
+    (('A'..='H').contains(&port) && number < 16).then_some(number)
+}
+
+/// Generates the board constants, `LedStrings` and `Peripherals::new`.
+fn generate(board: &Board, policy: &Policy) -> String {
+    let mut doc = String::new();
+    let mut out = String::new();
+    let names = board.string_names();
+    let backend_name = board.backend();
+
+    let mut outputs = vec![
+        (
+            "power.main_n".to_string(),
+            &board.power.main_n,
+            "Low",
+            "main battery ON",
+        ),
+        (
+            "power.backup_n".to_string(),
+            &board.power.backup_n,
+            "High",
+            "backup battery OFF",
+        ),
+    ];
+    if let Some(shift_register) = &board.shift_register {
+        outputs.extend([
+            (
+                "shift_register.ser".to_string(),
+                &shift_register.ser,
+                "Low",
+                "",
+            ),
+            (
+                "shift_register.srclk".to_string(),
+                &shift_register.srclk,
+                "Low",
+                "",
+            ),
+            (
+                "shift_register.rclk".to_string(),
+                &shift_register.rclk,
+                "Low",
+                "",
+            ),
+        ]);
+    }
+    if let Some(rgb) = &board.rgb {
+        outputs.push((
+            "rgb.power_n".to_string(),
+            &rgb.power_n,
+            "High",
+            "LED supply OFF",
+        ));
+    }
+    for (name, string) in &board.string {
+        outputs.extend([
+            (
+                format!("string.{name}.pre_n"),
+                &string.pre_n,
+                "High",
+                "preset inactive",
+            ),
+            (
+                format!("string.{name}.clr_n"),
+                &string.clr_n,
+                "High",
+                "clear inactive",
+            ),
+            (format!("string.{name}.data"), &string.data, "Low", ""),
+            (format!("string.{name}.clk"), &string.clk, "Low", ""),
+        ]);
+    }
+    for (role, pin, level, note) in outputs {
+        let note = if note.is_empty() {
+            String::new()
+        } else {
+            format!(" ({note})")
+        };
+        writeln!(doc, "    /// - {pin} ({role}): {level}{note}").unwrap();
+    }
+    if let Some(rgb) = &board.rgb {
+        writeln!(doc, "    /// - {} (rgb.data): SPI1 MOSI", rgb.data).unwrap();
+    }
+    if let Some(charlieplex) = &board.charlieplex {
+        let pins: Vec<String> = charlieplex.pins.iter().map(Pin::to_string).collect();
+        writeln!(
+            doc,
+            "    /// - {} (charlieplex.pins): Floating until scanned",
+            pins.join(", ")
+        )
+        .unwrap();
+    }
+    if let Some(button) = &board.button {
+        let pin = &button.pin;
+        writeln!(
+            doc,
+            "    /// - {pin} (button.pin): Input with pull-up, EXTI{} both edges",
+            pin.line()
+        )
+        .unwrap();
+    }
+    if let Some(sync) = &board.sync {
+        let pin = &sync.pin;
+        writeln!(
+            doc,
+            "    /// - {pin} (sync.pin): {} link, EXTI{} input with pull-up \
+             (open-drain output on a wire leader)",
+            sync.link.name(),
+            pin.line()
+        )
+        .unwrap();
+    }
+    if let Some(light_sensor) = &board.light_sensor {
+        writeln!(
+            doc,
+            "    /// - {} (light_sensor.led): Analog until measured",
+            light_sensor.led
+        )
+        .unwrap();
+    }
+    if let Some(uart) = &board.uart {
+        writeln!(
+            doc,
+            "    /// - {} / {} (uart): Reserved, left unconfigured",
+            uart.tx, uart.rx
+        )
+        .unwrap();
+    }
+
+    let output =
+        |pin: &Pin, level: &str| format!("Output::new(p.{pin}, Level::{level}, Speed::Low)");
+    let (backend_use, backend_type, backend) = if let Some(rgb) = &board.rgb {
+        (
+            "use crate::rgb_controller::RgbController;\nuse crate::ws2812::Ws2812;\n",
+            "RgbController<STRING_COUNT>",
+            format!(
+                "RgbController::new(Ws2812::new(
+                p.SPI1,
+                p.{},
+                p.DMA1_CH3,
+                {},
+            ))",
+                rgb.data,
+                output(&rgb.power_n, "High"),
+            ),
+        )
+    } else if board.charlieplex.is_some() {
+        (
+            "use crate::charlieplex::Charlieplex;\nuse crate::string_controller::StringController;\n",
+            "StringController<Charlieplex>",
+            "StringController::new(Charlieplex::new())".to_string(),
+        )
+    } else if let Some(shift_register) = &board.shift_register {
+        (
+            "use crate::shift_register::ShiftRegister;\nuse crate::string_controller::StringController;\n",
+            "StringController<ShiftRegister<STRING_COUNT, Output<'static>>>",
+            format!(
+                "StringController::new(ShiftRegister::new(
+                {},
+                {},
+                {},
+            ))",
+                output(&shift_register.ser, "Low"),
+                output(&shift_register.srclk, "Low"),
+                output(&shift_register.rclk, "Low"),
+            ),
+        )
+    } else {
+        let mut flops = String::new();
+        let mut feedback = String::new();
+        for string in board.string.values() {
+            write!(
+                flops,
+                "
+                    FlipFlop::new(
+                        {},
+                        {},
+                        {},
+                        {},
+                    ),",
+                output(&string.pre_n, "High"),
+                output(&string.clr_n, "High"),
+                output(&string.data, "Low"),
+                output(&string.clk, "Low"),
+            )
+            .unwrap();
+            write!(
+                feedback,
+                "
+                    Input::new(p.{}, Pull::None),",
+                string.feedback
+            )
+            .unwrap();
+        }
+        (
+            "use crate::flip_flop::{FlipFlop, FlipFlops};\nuse crate::string_controller::StringController;\n",
+            "StringController<FlipFlops<STRING_COUNT>>",
+            format!(
+                "StringController::new(FlipFlops::new(
+                [{flops}
+                ],
+                [{feedback}
+                ],
+            ))"
+            ),
+        )
+    };
+    let button = match &board.button {
+        Some(button) => format!(
+            "Some(ExtiInput::new(p.{}, p.EXTI{}, Pull::Up))",
+            button.pin,
+            button.pin.line()
+        ),
+        None => "None".to_string(),
+    };
+    let mut sync_field = String::new();
+    if let Some(sync) = &board.sync {
+        let pin = &sync.pin;
+        let leader = match sync.link {
+            SyncLink::Wire => format!(
+                "SyncLink::WireLeader(OutputOpenDrain::new(p.{pin}, Level::High, Speed::Low))"
+            ),
+            SyncLink::Optical => "SyncLink::OpticalLeader".to_string(),
+        };
+        write!(
+            sync_field,
+            "
+            sync: if cfg!(feature = \"sync-leader\") {{
+                {leader}
+            }} else {{
+                SyncLink::Follower(ExtiInput::new(p.{pin}, p.EXTI{}, Pull::Up))
+            }},",
+            pin.line()
+        )
+        .unwrap();
+    }
+
+    let mut gpio = vec!["Level", "Output", "Speed"];
+    if backend_name == "flip_flop" {
+        gpio.insert(0, "Input");
+    }
+    if backend_name == "charlieplex" || board.light_sensor.is_some() {
+        gpio.insert(0, "Flex");
+    }
+    if board
+        .sync
+        .as_ref()
+        .is_some_and(|sync| sync.link == SyncLink::Wire)
+    {
+        gpio.push("OutputOpenDrain");
+    }
+    if backend_name == "flip_flop" || board.button.is_some() || board.sync.is_some() {
+        gpio.push("Pull");
+    }
+    writeln!(out, "use embassy_stm32::flash::Flash;").unwrap();
+    writeln!(out, "use embassy_stm32::gpio::{{{}}};", gpio.join(", ")).unwrap();
+    writeln!(out).unwrap();
+    write!(out, "{backend_use}").unwrap();
+    if board.light_sensor.is_some() {
+        writeln!(out, "use crate::sensor_led::SensorLed;").unwrap();
+    } else {
+        writeln!(out, "use crate::light_sensor::NoLightSensor;").unwrap();
+    }
+    if board.sync.is_some() {
+        writeln!(out, "use crate::sync::SyncLink;").unwrap();
+    }
+    writeln!(out, "use crate::vdd_monitor::VddMonitor;").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "/// Board description the firmware was built for.").unwrap();
+    writeln!(out, "pub const BOARD_NAME: &str = {:?};", board.name).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "/// Number of LED strings on the board.").unwrap();
+    writeln!(out, "pub const STRING_COUNT: usize = {};", names.len()).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "/// Names of the LED strings, in string index order.").unwrap();
+    writeln!(
+        out,
+        "pub const STRING_NAMES: [&str; STRING_COUNT] = {names:?};"
+    )
+    .unwrap();
+    writeln!(out).unwrap();
+    let mut charlieplex_field = String::new();
+    if let Some(charlieplex) = &board.charlieplex {
+        let pins = &charlieplex.pins;
+        writeln!(out, "/// Number of charlieplex pins on the board.").unwrap();
+        writeln!(out, "pub const CHARLIEPLEX_PINS: usize = {};", pins.len()).unwrap();
+        writeln!(out).unwrap();
+        let flex: Vec<String> = pins
+            .iter()
+            .map(|pin| format!("Flex::new(p.{pin})"))
+            .collect();
+        write!(
+            charlieplex_field,
+            "
+            charlieplex_pins: [{}],",
+            flex.join(", ")
+        )
+        .unwrap();
+    }
+    writeln!(out, "/// LED pattern engine for the board's LED backend.").unwrap();
+    writeln!(out, "pub type LedController = {backend_type};").unwrap();
+    writeln!(out).unwrap();
+    writeln!(
+        out,
+        "/// Battery selection policy the firmware was built with."
+    )
+    .unwrap();
+    writeln!(out, "pub type BatteryPolicy = {};", policy.path).unwrap();
+    writeln!(out).unwrap();
+    let (sensor_type, sensor) = match &board.light_sensor {
+        Some(light_sensor) => (
+            "SensorLed",
+            format!("SensorLed::new(Flex::new(p.{}))", light_sensor.led),
+        ),
+        None => ("NoLightSensor", "NoLightSensor".to_string()),
+    };
+    writeln!(out, "/// Ambient light sensor on the board, if any.").unwrap();
+    writeln!(out, "pub type AmbientSensor = {sensor_type};").unwrap();
+    writeln!(out).unwrap();
+    write!(
+        out,
+        r#"impl Peripherals {{
+    /// Initializes all peripherals from STM32 peripheral singleton.
+    ///
+    /// Generated by `build.rs` from the board description. Consumes the
+    /// embassy-stm32 Peripherals struct and creates GPIO outputs/inputs for
+    /// all hardware controllers.
+    ///
+    /// # Initial GPIO States
+    ///
+{doc}    ///
+    /// # Arguments
+    ///
+    /// * `p` - STM32 peripheral singleton from embassy_stm32::init()
+    ///
+    /// # Returns
+    ///
+    /// Initialized Peripherals struct ready for use
+    pub fn new(p: embassy_stm32::Peripherals) -> Self {{
+        Self {{
+            pwr_ctrl: PowerController::new(
+                {},
+                {},
+                VddMonitor::new(p.ADC1),
+                p.PWR,
+                {policy},
+            ),
+            led_ctrl: {backend},
+            eeprom: Eeprom::new(Flash::new_blocking(p.FLASH)),
+            button: {button},
+            light_sensor: {sensor},{charlieplex_field}{sync_field}
+        }}
+    }}
+}}
+"#,
+        output(&board.power.main_n, "Low"),
+        output(&board.power.backup_n, "High"),
+        policy = policy.constructor,
+    )
+    .unwrap();
+
+    out
 }
@@ -0,0 +1,87 @@
+//! Host tests for the firmware's pure modules.
+//!
+//! The firmware only builds for the STM32, so the modules that have no
+//! hardware dependencies are compiled into this crate straight from
+//! `../src`, under the same names so their `crate::` paths resolve. They
+//! may use embassy-time's types, at the firmware's 32.768 kHz tick rate.
+//! The tests live in `tests/`, one file per module.
+//!
+//! [`ram_storage`] is the only module of this crate's own: storage in RAM
+//! that can lose power partway through a write.
+
+// Lints for public library APIs, which the firmware, a binary, never trips
+#![allow(clippy::new_without_default)]
+// Items of policies not configured for the firmware expect to be dead code,
+// but are public here
+#![allow(unfulfilled_lint_expectations)]
+
+#[path = "../../src/activity_meter.rs"]
+pub mod activity_meter;
+
+#[path = "../../src/battery_stats.rs"]
+pub mod battery_stats;
+
+#[path = "../../src/charlieplex_scan.rs"]
+pub mod charlieplex_scan;
+
+#[path = "../../src/checksum.rs"]
+pub mod checksum;
+
+#[path = "../../src/cue_sheet.rs"]
+pub mod cue_sheet;
+
+#[path = "../../src/droop.rs"]
+pub mod droop;
+
+#[path = "../../src/event_log.rs"]
+pub mod event_log;
+
+#[path = "../../src/fuel_gauge.rs"]
+pub mod fuel_gauge;
+
+#[path = "../../src/gesture.rs"]
+pub mod gesture;
+
+#[path = "../../src/kv_store.rs"]
+pub mod kv_store;
+
+#[path = "../../src/led_backend.rs"]
+pub mod led_backend;
+
+#[path = "../../src/light_sensor.rs"]
+pub mod light_sensor;
+
+#[path = "../../src/morse.rs"]
+pub mod morse;
+
+#[path = "../../src/pattern.rs"]
+pub mod pattern;
+
+#[path = "../../src/power_policy.rs"]
+pub mod power_policy;
+
+#[path = "../../src/power_state.rs"]
+pub mod power_state;
+
+#[path = "../../src/prng.rs"]
+pub mod prng;
+
+pub mod ram_storage;
+
+#[path = "../../src/rgb.rs"]
+pub mod rgb;
+
+#[path = "../../src/shift_register.rs"]
+pub mod shift_register;
+
+#[path = "../../src/step_schedule.rs"]
+pub mod step_schedule;
+
+#[path = "../../src/sync_protocol.rs"]
+pub mod sync_protocol;
+
+#[path = "../../src/twinkle.rs"]
+pub mod twinkle;
+
+#[path = "../../src/ws2812_encoder.rs"]
+pub mod ws2812_encoder;
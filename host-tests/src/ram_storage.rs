@@ -0,0 +1,86 @@
+//! Simulated EEPROM for the storage tests.
+
+use embedded_storage::{ReadStorage, Storage};
+
+/// Storage kept in RAM, with optional power-failure injection.
+///
+/// Starts blank (all zeros, like erased data EEPROM).
+#[derive(Clone)]
+pub struct RamStorage<const N: usize> {
+    /// Contents of the storage
+    pub bytes: [u8; N],
+    /// Number of times each byte was written, for wear checks
+    pub writes: [u32; N],
+    /// Bytes that can still be written before power fails, if limited
+    write_budget: Option<usize>,
+}
+
+/// Error from [`RamStorage`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RamError {
+    /// Access beyond the end of the storage
+    OutOfBounds,
+    /// Power failed during a write
+    PowerLoss,
+}
+
+impl<const N: usize> RamStorage<N> {
+    /// Creates blank storage.
+    pub const fn new() -> Self {
+        Self {
+            bytes: [0; N],
+            writes: [0; N],
+            write_budget: None,
+        }
+    }
+
+    /// Makes power fail once `bytes` more bytes have been written.
+    ///
+    /// The write in progress stops there with [`RamError::PowerLoss`], as
+    /// does every write after it until [`RamStorage::restore_power`].
+    pub fn fail_after(&mut self, bytes: usize) {
+        self.write_budget = Some(bytes);
+    }
+
+    /// Lets writes succeed again.
+    pub fn restore_power(&mut self) {
+        self.write_budget = None;
+    }
+
+    /// Returns the range of `len` bytes at `offset`, if within the storage.
+    fn range(offset: u32, len: usize) -> Result<core::ops::Range<usize>, RamError> {
+        let start = offset as usize;
+        let end = start.checked_add(len).filter(|&end| end <= N);
+        end.map(|end| start..end).ok_or(RamError::OutOfBounds)
+    }
+}
+
+impl<const N: usize> ReadStorage for RamStorage<N> {
+    type Error = RamError;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        bytes.copy_from_slice(&self.bytes[Self::range(offset, bytes.len())?]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<const N: usize> Storage for RamStorage<N> {
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        let range = Self::range(offset, bytes.len())?;
+        for (n, &byte) in range.zip(bytes) {
+            if let Some(budget) = self.write_budget.as_mut() {
+                if *budget == 0 {
+                    return Err(RamError::PowerLoss);
+                }
+                *budget -= 1;
+            }
+            self.bytes[n] = byte;
+            self.writes[n] += 1;
+        }
+        Ok(())
+    }
+}
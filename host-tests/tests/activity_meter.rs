@@ -0,0 +1,83 @@
+//! Duty cycle statistics tests with scripted wake-ups.
+
+use embassy_time::TICK_HZ;
+use host_tests::activity_meter::{ActivityMeter, Task};
+
+/// Plays one active stretch: a wake-up at `at` ticks, the task polls one
+/// after another, then sleep a tick after the last one.
+fn stretch(meter: &mut ActivityMeter, at: u64, polls: &[(Task, u64)]) {
+    meter.wake(at);
+    let mut now = at;
+    for &(task, ticks) in polls {
+        meter.polled(task, ticks);
+        now += ticks;
+    }
+    meter.sleep(now + 1);
+}
+
+#[test]
+fn stretches_are_counted_and_timed() {
+    let mut meter = ActivityMeter::new(1_000);
+    stretch(&mut meter, 2_000, &[(Task::Led, 29)]);
+    stretch(&mut meter, 5_000, &[(Task::Power, 9)]);
+
+    let stats = meter.stats(11_000);
+    assert_eq!(stats.elapsed_ticks, 10_000);
+    assert_eq!(stats.wakes, 2);
+    assert_eq!(stats.active_ticks, 40);
+    assert_eq!(stats.longest_ticks, 30);
+}
+
+#[test]
+fn longest_stretch_is_blamed_on_its_busiest_task() {
+    let mut meter = ActivityMeter::new(0);
+    stretch(&mut meter, 100, &[(Task::Led, 5), (Task::Power, 20)]);
+    assert_eq!(meter.stats(200).longest_cause, Some(Task::Power));
+
+    stretch(&mut meter, 200, &[(Task::Button, 40), (Task::Led, 30)]);
+    assert_eq!(meter.stats(300).longest_cause, Some(Task::Button));
+
+    // A shorter stretch does not change the blame
+    stretch(&mut meter, 300, &[(Task::Led, 50)]);
+    let stats = meter.stats(400);
+    assert_eq!(stats.longest_ticks, 71);
+    assert_eq!(stats.longest_cause, Some(Task::Button));
+}
+
+#[test]
+fn stretch_without_tracked_polls_has_no_cause() {
+    let mut meter = ActivityMeter::new(0);
+    stretch(&mut meter, 100, &[]);
+    stretch(&mut meter, 200, &[]);
+    meter.wake(300);
+    meter.sleep(320);
+    assert_eq!(meter.stats(400).longest_cause, None);
+}
+
+#[test]
+fn an_hour_of_millisecond_wakes() {
+    let mut meter = ActivityMeter::new(0);
+    for second in 0..3600 {
+        let at = second * TICK_HZ;
+        meter.wake(at);
+        meter.polled(Task::Led, 32);
+        meter.sleep(at + 33);
+    }
+
+    let stats = meter.stats(3600 * TICK_HZ);
+    assert_eq!(stats.wakes_per_hour(), 3600);
+    // 33 ticks of 30.5 µs
+    assert_eq!(stats.average_active_us(), 1007);
+    assert_eq!(stats.active_ppm(), 1007);
+    assert_eq!(stats.longest_us(), 1007);
+}
+
+#[test]
+fn empty_statistics_do_not_divide_by_zero() {
+    let stats = ActivityMeter::new(0).stats(0);
+    assert_eq!(stats.wakes, 0);
+    assert_eq!(stats.wakes_per_hour(), 0);
+    assert_eq!(stats.average_active_us(), 0);
+    assert_eq!(stats.active_ppm(), 0);
+    assert_eq!(stats.longest_us(), 0);
+}
@@ -0,0 +1,140 @@
+//! Battery statistics, cell replacement detection and the EEPROM record.
+
+use host_tests::battery_stats::{
+    BatteryStats, FRESH_CELL_JUMP_MV, FRESH_CELL_MIN_MV, RECORD_SIZE, SlotStats,
+};
+use host_tests::power_state::PowerState::{BackupPower as Backup, MainPower as Main};
+
+/// Feeds VDD readings from the main slot.
+///
+/// # Returns
+///
+/// The indices of the readings reported as a fresh cell
+fn replacements(stats: &mut BatteryStats, readings: &[u16]) -> Vec<usize> {
+    (0..readings.len())
+        .filter(|&n| stats.record_vdd(&Main, readings[n]))
+        .collect()
+}
+
+#[test]
+fn fresh_cell_after_a_drop_is_a_replacement() {
+    let mut stats = BatteryStats::default();
+    // A cell running down, then a fresh one
+    let readings = [2950, 2900, 2800, 2650, 2500, 2300, 3000, 2980];
+    assert_eq!(replacements(&mut stats, &readings), [6]);
+    assert_eq!(stats.slot(&Main).replacements, 1);
+    assert_eq!(stats.last_vdd_mv(&Main), 2980);
+
+    // The other slot is untouched
+    assert_eq!(stats.slot(&Backup), &SlotStats::default());
+    assert_eq!(stats.last_vdd_mv(&Backup), 0);
+}
+
+#[test]
+fn first_reading_is_not_a_replacement() {
+    let mut stats = BatteryStats::default();
+    assert!(!stats.record_vdd(&Main, 3000));
+    assert!(!stats.record_vdd(&Backup, 3000));
+    assert_eq!(stats.slot(&Main).replacements, 0);
+}
+
+#[test]
+fn noise_is_not_a_replacement() {
+    let mut stats = BatteryStats::default();
+
+    // Readings scattered just under the jump around a healthy cell
+    let jitter = FRESH_CELL_JUMP_MV - 1;
+    let readings: Vec<u16> = (0..200)
+        .map(|n| 2800 - jitter / 2 + (n * 37 % 3) as u16 * jitter / 2)
+        .collect();
+    assert_eq!(replacements(&mut stats, &readings), []);
+
+    // A full jump that stays below a fresh cell's voltage, as when the
+    // load on a tired cell goes away
+    let recovering = [2400, 2400 + FRESH_CELL_JUMP_MV, FRESH_CELL_MIN_MV - 1];
+    assert_eq!(replacements(&mut stats, &recovering), []);
+
+    // A fresh cell's voltage reached without the jump
+    let rising = [2600, 2600 + FRESH_CELL_JUMP_MV - 1, 3000 - 1, 3000];
+    assert_eq!(replacements(&mut stats, &rising), []);
+    assert_eq!(stats.slot(&Main).replacements, 0);
+}
+
+#[test]
+fn swap_into_the_idle_slot_shows_once_it_is_active() {
+    let mut stats = BatteryStats::default();
+    stats.record_vdd(&Main, 2900);
+    stats.record_vdd(&Backup, 2400);
+
+    // Main runs for a while; the backup cell is swapped meanwhile
+    for _ in 0..10 {
+        assert!(!stats.record_vdd(&Main, 2850));
+    }
+    assert!(stats.record_vdd(&Backup, 3010));
+    assert_eq!(stats.slot(&Backup).replacements, 1);
+    assert_eq!(stats.slot(&Main).replacements, 0);
+}
+
+#[test]
+fn active_time_and_switches_accumulate() {
+    let mut stats = BatteryStats::default();
+    for _ in 0..5 {
+        stats.record_active(&Main, 400);
+    }
+    assert_eq!(stats.slot(&Main).active_secs, 2);
+
+    // Part seconds carry over to the next update
+    stats.record_active(&Backup, 700);
+    assert_eq!(stats.slot(&Backup).active_secs, 0);
+    stats.record_active(&Backup, 300);
+    assert_eq!(stats.slot(&Backup).active_secs, 1);
+    stats.record_active(&Backup, u32::MAX);
+    assert_eq!(stats.slot(&Backup).active_secs, 1 + u32::MAX / 1000);
+
+    stats.record_switch(&Backup);
+    stats.record_switch(&Backup);
+    stats.record_switch(&Main);
+    assert_eq!(stats.slot(&Main).switches, 1);
+    assert_eq!(stats.slot(&Backup).switches, 2);
+}
+
+/// Returns statistics with every field set.
+fn sample() -> BatteryStats {
+    let mut stats = BatteryStats::default();
+    stats.record_active(&Main, 90_000);
+    stats.record_active(&Backup, 4_000);
+    stats.record_switch(&Backup);
+    stats.record_vdd(&Main, 2500);
+    stats.record_vdd(&Main, 3000);
+    stats.record_vdd(&Backup, 2800);
+    stats
+}
+
+#[test]
+fn record_round_trips() {
+    let stats = sample();
+    let restored = BatteryStats::from_record(&stats.to_record()).unwrap();
+    for cell in [Main, Backup] {
+        assert_eq!(restored.slot(&cell), stats.slot(&cell));
+        assert_eq!(restored.last_vdd_mv(&cell), stats.last_vdd_mv(&cell));
+    }
+    assert_eq!(restored.to_record(), stats.to_record());
+}
+
+#[test]
+fn corrupted_record_is_rejected() {
+    let record = sample().to_record();
+    assert!(BatteryStats::from_record(&[0; RECORD_SIZE]).is_none());
+    assert!(BatteryStats::from_record(&[0xFF; RECORD_SIZE]).is_none());
+
+    for byte in 0..RECORD_SIZE {
+        for bit in 0..8 {
+            let mut corrupted = record;
+            corrupted[byte] ^= 1 << bit;
+            assert!(
+                BatteryStats::from_record(&corrupted).is_none(),
+                "byte {byte}, bit {bit}"
+            );
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Charlieplex pin table and scan tests.
+
+use embassy_time::Duration;
+use host_tests::charlieplex_scan::{
+    PinState, SCAN_WAKE_BUDGET_HZ, led_pins, next_lit, pin_table, slot_duration,
+};
+use host_tests::led_backend::Frame;
+
+use PinState::{Floating as Z, High as H, Low as L};
+
+/// Returns the LEDs that conduct with the pins in `states`, out of every
+/// LED that `pins` pins can address.
+fn conducting(states: &[PinState]) -> Vec<usize> {
+    let pins = states.len();
+    (0..pins * (pins - 1))
+        .filter(|&led| {
+            let (anode, cathode) = led_pins(led, pins);
+            states[anode] == H && states[cathode] == L
+        })
+        .collect()
+}
+
+/// Plays `slots` scan slots of a frame, the way the scan task does.
+///
+/// Returns the LED lit in each slot.
+fn scan(frame: Frame, leds: usize, slots: usize) -> Vec<usize> {
+    let mut led = 0;
+    (0..slots)
+        .map(|_| {
+            led = next_lit(frame, led, leds);
+            let lit = led;
+            led = (led + 1) % leds;
+            lit
+        })
+        .collect()
+}
+
+#[test]
+fn every_pin_pair_has_one_led() {
+    for pins in 2..=6 {
+        let mut pairs: Vec<(usize, usize)> = (0..pins * (pins - 1))
+            .map(|led| led_pins(led, pins))
+            .collect();
+        assert!(pairs.iter().all(|&(anode, cathode)| anode != cathode));
+        pairs.sort();
+        pairs.dedup();
+        assert_eq!(pairs.len(), pins * (pins - 1), "{pins} pins");
+    }
+}
+
+#[test]
+fn three_pin_table_matches_golden() {
+    assert_eq!(
+        pin_table::<3, 6>(),
+        [
+            [H, L, Z],
+            [H, Z, L],
+            [L, H, Z],
+            [Z, H, L],
+            [L, Z, H],
+            [Z, L, H],
+        ]
+    );
+}
+
+#[test]
+fn each_slot_lights_exactly_its_own_led() {
+    fn check<const P: usize, const N: usize>() {
+        for (led, states) in pin_table::<P, N>().iter().enumerate() {
+            assert_eq!(conducting(states), [led], "{P} pins, LED {led}");
+        }
+    }
+
+    check::<2, 2>();
+    check::<3, 6>();
+    check::<4, 12>();
+    check::<5, 20>();
+    // A partly populated matrix, like the star topper
+    check::<4, 10>();
+}
+
+#[test]
+fn scan_visits_each_lit_led_once_per_refresh() {
+    let frame = Frame(0b1000_1010_0101);
+    let lit: Vec<usize> = (0..12).filter(|&led| frame.is_on(led)).collect();
+
+    let slots = scan(frame, 12, 3 * lit.len());
+    for refresh in slots.chunks(lit.len()) {
+        assert_eq!(refresh, lit);
+    }
+}
+
+#[test]
+fn scan_of_a_single_led_stays_on_it() {
+    assert_eq!(scan(Frame::single(7), 12, 4), [7, 7, 7, 7]);
+    assert_eq!(scan(Frame::single(0), 12, 2), [0, 0]);
+}
+
+#[test]
+fn slots_refresh_at_the_target_rate_within_budget() {
+    assert_eq!(slot_duration(0), Duration::from_millis(10));
+    assert_eq!(slot_duration(1), Duration::from_millis(10));
+    assert_eq!(slot_duration(4), Duration::from_micros(2500));
+    // Refresh slows down rather than exceeding the wake-up budget
+    assert_eq!(slot_duration(10), Duration::from_millis(1));
+    assert_eq!(slot_duration(30), Duration::from_millis(1));
+
+    let shortest = Duration::from_micros(1_000_000 / u64::from(SCAN_WAKE_BUDGET_HZ));
+    assert!((0..=32).all(|lit| slot_duration(lit) >= shortest));
+}
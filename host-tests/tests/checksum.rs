@@ -0,0 +1,28 @@
+//! Check word and CRC tests.
+
+use host_tests::checksum::{check_word, crc16};
+
+#[test]
+fn crc_matches_the_standard_check_value() {
+    // CRC-16/CCITT-FALSE of the standard check input
+    assert_eq!(crc16(0xFFFF, b"123456789"), 0x29B1);
+}
+
+#[test]
+fn crcs_chain() {
+    let bytes = b"sequence, kind, argument";
+    let (head, tail) = bytes.split_at(9);
+    assert_eq!(crc16(crc16(0xFFFF, head), tail), crc16(0xFFFF, bytes));
+}
+
+#[test]
+fn crc_depends_on_byte_order() {
+    assert_ne!(crc16(0xFFFF, &[1, 2, 0]), crc16(0xFFFF, &[2, 1, 0]));
+}
+
+#[test]
+fn check_word_ignores_a_partial_last_word() {
+    let bytes = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+    assert_eq!(check_word(0, &bytes), check_word(0, &bytes[..8]));
+    assert_ne!(check_word(0, &bytes), check_word(1, &bytes));
+}
@@ -0,0 +1,167 @@
+//! Cue sheet compiler: parsing, errors and the encoded light sequence.
+
+use host_tests::cue_sheet::{HEADER, MAX_SEQUENCE_LEN, compile_show, parse_strings, parse_time};
+
+/// Strings of rev-a.
+const NAMES: [&str; 2] = ["red", "green"];
+
+/// Returns the cues of a light sequence as (delay, mask) pairs.
+fn cues(sequence: &[u8]) -> Vec<(u16, u32)> {
+    assert_eq!(sequence[..3], HEADER);
+    let width = usize::from(sequence[3]);
+    sequence[4..]
+        .chunks(2 + width)
+        .map(|cue| {
+            let mut mask = [0; 4];
+            mask[..width].copy_from_slice(&cue[2..]);
+            (
+                u16::from_le_bytes([cue[0], cue[1]]),
+                u32::from_le_bytes(mask),
+            )
+        })
+        .collect()
+}
+
+/// Compiles a sheet for [`NAMES`], expecting it to be valid.
+fn compile(text: &str) -> Vec<(u16, u32)> {
+    cues(&compile_show(text, &NAMES).unwrap())
+}
+
+#[test]
+fn times_parse_to_milliseconds() {
+    let table = [
+        ("0", Some(0)),
+        ("1.5", Some(1_500)),
+        ("0.001", Some(1)),
+        ("12.34", Some(12_340)),
+        ("2:03.250", Some(123_250)),
+        ("10:00", Some(600_000)),
+        ("1.", Some(1_000)),
+        ("1.2345", None),
+        ("1.-5", None),
+        ("-1", None),
+        ("a", None),
+        ("1:a", None),
+        ("", None),
+    ];
+    for (time, expected) in table {
+        assert_eq!(parse_time(time), expected, "`{time}`");
+    }
+}
+
+#[test]
+fn strings_parse_by_name_index_or_keyword() {
+    assert_eq!(parse_strings("-", &NAMES), Ok(0));
+    assert_eq!(parse_strings("all", &NAMES), Ok(0b11));
+    assert_eq!(parse_strings("red", &NAMES), Ok(0b01));
+    assert_eq!(parse_strings("1", &NAMES), Ok(0b10));
+    assert_eq!(parse_strings("green  0", &NAMES), Ok(0b11));
+    assert!(parse_strings("blue", &NAMES).is_err());
+    assert!(parse_strings("2", &NAMES).is_err());
+
+    let names: Vec<String> = (0..32).map(|n| format!("s{n}")).collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    assert_eq!(parse_strings("all", &names), Ok(u32::MAX));
+    assert_eq!(parse_strings("31", &names), Ok(1 << 31));
+}
+
+#[test]
+fn valid_sheet_compiles_to_timed_cues() {
+    let sheet = "\
+        # Comment lines and blank lines are skipped
+
+        0.000, red      # first beat
+        0.400, -
+        0.500, green
+        1:00.250, all
+        1:01, end
+    ";
+    assert_eq!(
+        compile(sheet),
+        [(0, 0b01), (400, 0), (100, 0b10), (59_750, 0b11), (750, 0)]
+    );
+}
+
+#[test]
+fn late_start_gets_an_all_off_cue() {
+    assert_eq!(
+        compile("2.5, all\n3, end"),
+        [(0, 0), (2_500, 0b11), (500, 0)]
+    );
+}
+
+#[test]
+fn long_gaps_are_bridged() {
+    // 200 s between cues needs four delays of at most 65.535 s
+    assert_eq!(
+        compile("0, red\n3:20, green\n3:21, end"),
+        [
+            (0, 0b01),
+            (65_535, 0b01),
+            (65_535, 0b01),
+            (65_535, 0b01),
+            (3_395, 0b10),
+            (1_000, 0)
+        ]
+    );
+    assert_eq!(compile("0, red\n65.535, end"), [(0, 0b01), (65_535, 0)]);
+}
+
+#[test]
+fn frame_width_follows_the_string_count() {
+    for (strings, width) in [(1, 1), (8, 1), (9, 2), (16, 2), (17, 3), (32, 4)] {
+        let names: Vec<String> = (0..strings).map(|n| format!("s{n}")).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let sequence = compile_show("0, all\n1, end", &names).unwrap();
+        assert_eq!(sequence[3], width, "{strings} strings");
+        assert_eq!(sequence.len(), 4 + 2 * (2 + usize::from(width)));
+    }
+}
+
+#[test]
+fn invalid_sheets_name_the_line() {
+    let table = [
+        (
+            "0, red\n0.5 red\n1, end",
+            "line 2: expected `time, strings`",
+        ),
+        ("0, red\nsoon, green\n1, end", "line 2: invalid time `soon`"),
+        (
+            "0, red\n1, green\n1, end",
+            "line 3: cue times must increase",
+        ),
+        (
+            "1, red\n0.5, green\n2, end",
+            "line 2: cue times must increase",
+        ),
+        ("0, blue\n1, end", "line 1: unknown string `blue`"),
+        ("0, red\n1, end\n2, green", "line 3: cue after `end`"),
+        ("0, red\n1, green", "missing `time, end` line"),
+        ("", "missing `time, end` line"),
+        ("0, end", "the show must end after time 0"),
+    ];
+    for (sheet, expected) in table {
+        let error = compile_show(sheet, &NAMES).unwrap_err();
+        assert!(error.starts_with(expected), "{sheet:?}: {error}");
+    }
+}
+
+#[test]
+fn overlong_sheet_is_rejected() {
+    // Three bytes per cue after the 4-byte header, and the final cue
+    let fits = (MAX_SEQUENCE_LEN - 4) / 3 - 1;
+    let sheet = |cues: usize| {
+        let mut sheet: String = (0..cues).map(|n| format!("{n}, {}\n", n % 2)).collect();
+        sheet.push_str(&format!("{cues}, end\n"));
+        sheet
+    };
+
+    let sequence = compile_show(&sheet(fits), &NAMES).unwrap();
+    assert!(sequence.len() <= MAX_SEQUENCE_LEN);
+    let error = compile_show(&sheet(fits + 1), &NAMES).unwrap_err();
+    assert!(error.contains("more than the 4096 allowed"), "{error}");
+
+    // Bridging counts towards the limit too: 25 hours take 1374 cues
+    let error = compile_show("0, red\n1500:00, end", &NAMES).unwrap_err();
+    assert!(error.contains("more than the 4096 allowed"), "{error}");
+}
@@ -0,0 +1,128 @@
+//! Droop confirmation tests with scripted VDD glitches.
+
+use host_tests::droop::{CONFIRM_READS, DroopFilter, RECOVERED_MV, Reading, SETTLE_TIME, Verdict};
+
+/// VDD over time after a PVD low-voltage event: stretches of milliseconds
+/// with VDD below the PVD threshold or not, the last one lasting forever.
+type Script = [(u64, bool)];
+
+/// Returns true if VDD is below the threshold `ms` after the event.
+fn low_at(script: &Script, ms: u64) -> bool {
+    let mut end = 0;
+    for &(length, low) in script {
+        end += length;
+        if ms < end {
+            return low;
+        }
+    }
+    script.last().is_some_and(|&(_, low)| low)
+}
+
+/// Confirms an event the way the power task does: PVDO re-reads every
+/// [`SETTLE_TIME`] while pending, then, with no string lit, a VDD
+/// measurement.
+///
+/// # Arguments
+///
+/// * `filter` - Droop filter
+/// * `script` - VDD after the event
+/// * `vdd_mv` - VDD measured once the re-reads confirm, if no string is lit
+///
+/// # Returns
+///
+/// The verdict and the time it took, in ms
+fn confirm(filter: &mut DroopFilter, script: &Script, vdd_mv: Option<u16>) -> (Verdict, u64) {
+    filter.begin();
+
+    let mut ms = 0;
+    let mut verdict = Verdict::Pending;
+    while verdict == Verdict::Pending {
+        ms += SETTLE_TIME.as_millis();
+        verdict = filter.update(Reading::PvdLow(low_at(script, ms)));
+    }
+    if let (Verdict::Confirmed, Some(vdd_mv)) = (verdict, vdd_mv) {
+        verdict = filter.update(Reading::VddMv(vdd_mv));
+    }
+    (verdict, ms)
+}
+
+/// Time taken by all the PVDO re-reads, in ms.
+const ALL_READS_MS: u64 = CONFIRM_READS as u64 * SETTLE_TIME.as_millis();
+
+#[test]
+fn inrush_shorter_than_the_settle_time_is_rejected() {
+    let mut filter = DroopFilter::new();
+    let script = [(5, true), (0, false)];
+    assert_eq!(
+        confirm(&mut filter, &script, None),
+        (Verdict::Rejected, SETTLE_TIME.as_millis())
+    );
+    assert_eq!(filter.rejected(), 1);
+}
+
+#[test]
+fn droop_recovering_before_the_last_read_is_rejected() {
+    let mut filter = DroopFilter::new();
+    let script = [(ALL_READS_MS - 1, true), (0, false)];
+    assert_eq!(
+        confirm(&mut filter, &script, None),
+        (Verdict::Rejected, ALL_READS_MS)
+    );
+}
+
+#[test]
+fn chattering_supply_is_rejected_at_the_first_high_read() {
+    let mut filter = DroopFilter::new();
+    // Low 7 ms, high 7 ms, ..., low at the first read and high at the second
+    let script: Vec<(u64, bool)> = (0..20).map(|n| (7, n % 2 == 0)).collect();
+    assert_eq!(
+        confirm(&mut filter, &script, None),
+        (Verdict::Rejected, 2 * SETTLE_TIME.as_millis())
+    );
+}
+
+#[test]
+fn failing_cell_with_strings_lit_is_confirmed_by_the_reads() {
+    let mut filter = DroopFilter::new();
+    assert_eq!(
+        confirm(&mut filter, &[(0, true)], None),
+        (Verdict::Confirmed, ALL_READS_MS)
+    );
+    assert_eq!(filter.rejected(), 0);
+}
+
+#[test]
+fn vdd_measurement_overturns_the_reads() {
+    let mut filter = DroopFilter::new();
+    let low = [(0, true)];
+    assert_eq!(
+        confirm(&mut filter, &low, Some(RECOVERED_MV)).0,
+        Verdict::Rejected
+    );
+    assert_eq!(confirm(&mut filter, &low, Some(2900)).0, Verdict::Rejected);
+    assert_eq!(
+        confirm(&mut filter, &low, Some(RECOVERED_MV - 1)).0,
+        Verdict::Confirmed
+    );
+    assert_eq!(filter.rejected(), 2);
+}
+
+#[test]
+fn every_event_gets_all_its_reads() {
+    let mut filter = DroopFilter::new();
+    for _ in 0..10 {
+        confirm(&mut filter, &[(30, true), (0, false)], None);
+    }
+    assert_eq!(filter.rejected(), 10);
+
+    // A confirmed event needs every read again, even right after others
+    assert_eq!(
+        confirm(&mut filter, &[(0, true)], None),
+        (Verdict::Confirmed, ALL_READS_MS)
+    );
+    assert_eq!(
+        confirm(&mut filter, &[(0, true)], None),
+        (Verdict::Confirmed, ALL_READS_MS)
+    );
+    assert_eq!(filter.rejected(), 10);
+}
@@ -0,0 +1,220 @@
+//! Event log ring tests, with power lost partway through writes.
+
+use host_tests::event_log::{Entry, EventLog, Kind, RECORD_SIZE, Record, ResetCause, SLOTS};
+use host_tests::ram_storage::{RamError, RamStorage};
+
+/// Storage offset of the ring, as in data EEPROM.
+const OFFSET: u32 = 0x100;
+
+/// Data EEPROM, with the ring at [`OFFSET`] filling the rest of it.
+type Eeprom = RamStorage<{ OFFSET as usize + SLOTS as usize * RECORD_SIZE }>;
+
+/// Returns the `n`th test event.
+fn entry(n: u32) -> Entry {
+    Entry {
+        uptime_secs: n * 60,
+        kind: Kind::Pattern,
+        arg: n as u8,
+    }
+}
+
+/// Appends the test events numbered in `events` to a log.
+fn append(log: &mut EventLog, eeprom: &mut Eeprom, events: core::ops::Range<u32>) {
+    for n in events {
+        log.append(eeprom, entry(n)).unwrap();
+    }
+}
+
+/// Returns the records of a log, oldest first.
+fn records(log: &EventLog, eeprom: &mut Eeprom) -> Vec<Record> {
+    let mut records = Vec::new();
+    log.for_each(eeprom, |record| records.push(record));
+    records
+}
+
+/// Returns the test events numbered in records.
+fn events(records: &[Record]) -> Vec<u32> {
+    records
+        .iter()
+        .map(|record| record.entry.uptime_secs / 60)
+        .collect()
+}
+
+#[test]
+fn blank_ring_is_empty() {
+    let mut eeprom = Eeprom::new();
+    let log = EventLog::recover(&mut eeprom, OFFSET);
+    assert_eq!(records(&log, &mut eeprom), []);
+}
+
+#[test]
+fn full_ring_keeps_the_newest_records_in_order() {
+    let mut eeprom = Eeprom::new();
+    let mut log = EventLog::recover(&mut eeprom, OFFSET);
+    append(&mut log, &mut eeprom, 0..300);
+
+    let records = records(&log, &mut eeprom);
+    assert_eq!(events(&records), (300 - SLOTS..300).collect::<Vec<_>>());
+    // Sequence numbers count up across their wrap
+    for pair in records.windows(2) {
+        assert_eq!(pair[1].seq, pair[0].seq.wrapping_add(1));
+    }
+
+    // Nothing outside the ring was written
+    assert!(
+        eeprom.bytes[..OFFSET as usize]
+            .iter()
+            .all(|&byte| byte == 0)
+    );
+}
+
+#[test]
+fn recovery_resumes_after_the_newest_record() {
+    for written in [1, 10, SLOTS - 1, SLOTS, SLOTS + 1, 255, 256, 1000] {
+        let mut eeprom = Eeprom::new();
+        let mut log = EventLog::recover(&mut eeprom, OFFSET);
+        append(&mut log, &mut eeprom, 0..written);
+
+        let mut log = EventLog::recover(&mut eeprom, OFFSET);
+        append(&mut log, &mut eeprom, written..written + 3);
+        let first = (written + 3).saturating_sub(SLOTS);
+        assert_eq!(
+            events(&records(&log, &mut eeprom)),
+            (first..written + 3).collect::<Vec<_>>(),
+            "{written} written"
+        );
+    }
+}
+
+#[test]
+fn torn_record_is_skipped_and_overwritten() {
+    // Tearing a record into a blank slot, and over the oldest record
+    for written in [10, SLOTS + 10] {
+        for budget in 0..RECORD_SIZE {
+            let mut eeprom = Eeprom::new();
+            let mut log = EventLog::recover(&mut eeprom, OFFSET);
+            append(&mut log, &mut eeprom, 0..written);
+            let before = events(&records(&log, &mut eeprom));
+
+            eeprom.fail_after(budget);
+            assert_eq!(
+                log.append(&mut eeprom, entry(written)),
+                Err(RamError::PowerLoss)
+            );
+            eeprom.restore_power();
+
+            // After the reset, the torn record is gone and with it, unless
+            // power failed before the first byte, the oldest one it was
+            // written over
+            let mut log = EventLog::recover(&mut eeprom, OFFSET);
+            let mut expected = before;
+            if written >= SLOTS && budget > 0 {
+                expected.remove(0);
+            }
+            assert_eq!(
+                events(&records(&log, &mut eeprom)),
+                expected,
+                "{written} written, torn after {budget} bytes"
+            );
+
+            // Writing resumes in the torn record's slot
+            append(&mut log, &mut eeprom, 100..103);
+            let records = records(&log, &mut eeprom);
+            assert_eq!(records.len(), (written as usize + 3).min(SLOTS as usize));
+            assert_eq!(events(&records[records.len() - 3..]), [100, 101, 102]);
+        }
+    }
+}
+
+#[test]
+fn corrupted_record_is_skipped() {
+    let mut eeprom = Eeprom::new();
+    let mut log = EventLog::recover(&mut eeprom, OFFSET);
+    append(&mut log, &mut eeprom, 0..10);
+
+    // A bit flipped in the timestamp of record 4
+    eeprom.bytes[OFFSET as usize + 4 * RECORD_SIZE + 3] ^= 0x10;
+
+    let mut log = EventLog::recover(&mut eeprom, OFFSET);
+    let expected = [0, 1, 2, 3, 5, 6, 7, 8, 9];
+    assert_eq!(events(&records(&log, &mut eeprom)), expected);
+
+    // Two runs of records end at a gap; the later one holds the newest
+    append(&mut log, &mut eeprom, 10..11);
+    assert_eq!(events(&records(&log, &mut eeprom)).last(), Some(&10));
+    assert_eq!(records(&log, &mut eeprom).len(), 10);
+}
+
+#[test]
+fn failed_write_uses_up_its_slot() {
+    let mut eeprom = Eeprom::new();
+    let mut log = EventLog::recover(&mut eeprom, OFFSET);
+    append(&mut log, &mut eeprom, 0..3);
+
+    eeprom.fail_after(4);
+    assert!(log.append(&mut eeprom, entry(3)).is_err());
+    eeprom.restore_power();
+    append(&mut log, &mut eeprom, 4..6);
+
+    // Without a reset the slot is not retried
+    assert_eq!(events(&records(&log, &mut eeprom)), [0, 1, 2, 4, 5]);
+}
+
+#[test]
+fn entries_round_trip() {
+    let mut eeprom = Eeprom::new();
+    let mut log = EventLog::recover(&mut eeprom, OFFSET);
+    let entries = [
+        Entry {
+            uptime_secs: 0,
+            kind: Kind::Boot,
+            arg: ResetCause::PowerOn as u8,
+        },
+        Entry {
+            uptime_secs: 3_600,
+            kind: Kind::BatterySwitch,
+            arg: 1,
+        },
+        Entry {
+            uptime_secs: 0xFF_FFFF,
+            kind: Kind::Shutdown,
+            arg: 0,
+        },
+    ];
+    for entry in entries {
+        log.append(&mut eeprom, entry).unwrap();
+    }
+
+    let read: Vec<Entry> = records(&log, &mut eeprom)
+        .iter()
+        .map(|record| record.entry)
+        .collect();
+    assert_eq!(read, entries);
+}
+
+#[test]
+fn uptime_saturates_at_24_bits() {
+    let mut eeprom = Eeprom::new();
+    let mut log = EventLog::recover(&mut eeprom, OFFSET);
+    let mut late = entry(0);
+    late.uptime_secs = u32::MAX;
+    log.append(&mut eeprom, late).unwrap();
+    assert_eq!(records(&log, &mut eeprom)[0].entry.uptime_secs, 0xFF_FFFF);
+}
+
+#[test]
+fn reset_cause_follows_flag_precedence() {
+    let table = [
+        // Power-on also sets the pin flag
+        (0x0C, ResetCause::PowerOn),
+        (0x04, ResetCause::Pin),
+        (0x10, ResetCause::Software),
+        (0x20, ResetCause::Watchdog),
+        (0x44, ResetCause::Watchdog),
+        (0x80, ResetCause::LowPower),
+        (0x00, ResetCause::Other),
+    ];
+    for (flags, cause) in table {
+        assert_eq!(ResetCause::from_flags(flags), cause, "flags {flags:#04x}");
+    }
+}
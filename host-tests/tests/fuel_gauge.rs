@@ -0,0 +1,129 @@
+//! Fuel gauge accounting and its EEPROM record.
+
+use host_tests::checksum::check_word;
+use host_tests::fuel_gauge::{
+    CELL_CAPACITY_UAS, FuelGauge, IDLE_CURRENT_UA, RECORD_SIZE, STRING_CURRENT_UA,
+};
+use host_tests::power_state::PowerState::{BackupPower as Backup, MainPower as Main};
+
+const HOUR_MS: u32 = 3_600_000;
+
+/// Returns the charge a gauge has drawn from each slot, in µA·s, as stored
+/// in its record.
+fn drawn(gauge: &FuelGauge) -> [u32; 2] {
+    let record = gauge.to_record();
+    let u32_at = |i: usize| u32::from_le_bytes(record[i..i + 4].try_into().unwrap());
+    [u32_at(4), u32_at(8)]
+}
+
+#[test]
+fn draw_accumulates_the_modeled_load() {
+    let mut gauge = FuelGauge::default();
+    assert_eq!(drawn(&gauge), [0, 0]);
+
+    // An hour idle, then an hour with one string lit
+    gauge.draw(&Main, HOUR_MS, 0);
+    assert_eq!(drawn(&gauge), [IDLE_CURRENT_UA * 3_600, 0]);
+    gauge.draw(&Main, HOUR_MS, HOUR_MS);
+    let hours = (2 * IDLE_CURRENT_UA + STRING_CURRENT_UA) * 3_600;
+    assert_eq!(drawn(&gauge), [hours, 0]);
+
+    // Only the active cell is charged
+    gauge.draw(&Backup, 1_000, 2_000);
+    assert_eq!(
+        drawn(&gauge),
+        [hours, IDLE_CURRENT_UA + 2 * STRING_CURRENT_UA]
+    );
+}
+
+#[test]
+fn draw_saturates_at_capacity() {
+    let mut gauge = FuelGauge::default();
+    for _ in 0..400 {
+        gauge.draw(&Main, HOUR_MS, 3 * HOUR_MS);
+    }
+    assert_eq!(drawn(&gauge), [CELL_CAPACITY_UAS, 0]);
+    assert_eq!(gauge.percent_remaining(&Main), 0);
+
+    // Even from a single huge update
+    let mut gauge = FuelGauge::default();
+    gauge.draw(&Backup, u32::MAX, u32::MAX);
+    assert_eq!(drawn(&gauge), [0, CELL_CAPACITY_UAS]);
+}
+
+#[test]
+fn percent_follows_the_charge_drawn() {
+    let mut gauge = FuelGauge::default();
+    assert_eq!(gauge.percent_remaining(&Main), 100);
+
+    // 225 mAh at one lit string: about 293 hours, rounded down to whole
+    // percents
+    let mut percents = Vec::new();
+    for _ in 0..300 {
+        gauge.draw(&Main, HOUR_MS, HOUR_MS);
+        percents.push(gauge.percent_remaining(&Main));
+    }
+    assert!(percents.windows(2).all(|pair| pair[1] <= pair[0]));
+    assert_eq!(percents[145], 50);
+    assert_eq!(percents[146], 49);
+    assert_eq!(percents[299], 0);
+    assert_eq!(gauge.percent_remaining(&Backup), 100);
+}
+
+#[test]
+fn mark_empty_and_reset() {
+    let mut gauge = FuelGauge::default();
+    gauge.draw(&Main, HOUR_MS, 0);
+    gauge.draw(&Backup, HOUR_MS, 0);
+
+    gauge.mark_empty(&Main);
+    assert_eq!(gauge.percent_remaining(&Main), 0);
+    assert_eq!(drawn(&gauge)[1], IDLE_CURRENT_UA * 3_600);
+
+    gauge.reset(&Main);
+    assert_eq!(gauge.percent_remaining(&Main), 100);
+    assert_eq!(drawn(&gauge), [0, IDLE_CURRENT_UA * 3_600]);
+}
+
+#[test]
+fn record_round_trips() {
+    let mut gauge = FuelGauge::default();
+    gauge.draw(&Main, 5 * HOUR_MS, HOUR_MS);
+    gauge.mark_empty(&Backup);
+
+    let restored = FuelGauge::from_record(&gauge.to_record()).unwrap();
+    assert_eq!(drawn(&restored), drawn(&gauge));
+}
+
+#[test]
+fn corrupted_record_is_rejected() {
+    let mut gauge = FuelGauge::default();
+    gauge.draw(&Main, 5 * HOUR_MS, HOUR_MS);
+    let record = gauge.to_record();
+    assert!(FuelGauge::from_record(&[0; RECORD_SIZE]).is_none());
+    assert!(FuelGauge::from_record(&[0xFF; RECORD_SIZE]).is_none());
+
+    for byte in 0..RECORD_SIZE {
+        for bit in 0..8 {
+            let mut corrupted = record;
+            corrupted[byte] ^= 1 << bit;
+            assert!(
+                FuelGauge::from_record(&corrupted).is_none(),
+                "byte {byte}, bit {bit}"
+            );
+        }
+    }
+}
+
+#[test]
+fn out_of_range_record_is_rejected() {
+    // A well-formed record claiming more than a cell holds
+    let mut record = FuelGauge::default().to_record();
+    record[8..12].copy_from_slice(&(CELL_CAPACITY_UAS + 1).to_le_bytes());
+    let check = check_word(
+        u32::from_le_bytes(record[0..4].try_into().unwrap()),
+        &record[..12],
+    );
+    record[12..16].copy_from_slice(&check.to_le_bytes());
+    assert!(FuelGauge::from_record(&record).is_none());
+}
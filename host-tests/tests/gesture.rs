@@ -0,0 +1,123 @@
+//! Gesture decoder tests with simulated button presses.
+
+use host_tests::gesture::{
+    DEBOUNCE_MS, DOUBLE_PRESS_WINDOW_MS, Gesture, GestureDecoder, LONG_PRESS_MS, VERY_LONG_PRESS_MS,
+};
+
+/// Plays raw button levels, each held for a number of ms, the way the
+/// button task does: an update on every edge and at every deadline. Ends
+/// with the button released long enough for any gesture to complete.
+///
+/// Returns each gesture with the time it was reported.
+fn play(levels: &[(bool, u64)]) -> Vec<(u64, Gesture)> {
+    let mut decoder = GestureDecoder::new();
+    let mut gestures = Vec::new();
+    let mut now = 0;
+
+    for &(pressed, hold_ms) in levels.iter().chain(&[(false, 10_000)]) {
+        let until = now + hold_ms;
+        gestures.extend(decoder.update(pressed, now).map(|gesture| (now, gesture)));
+        while let Some(deadline) = decoder.deadline().filter(|&deadline| deadline < until) {
+            assert!(deadline >= now, "deadline {deadline} in the past at {now}");
+            now = deadline;
+            gestures.extend(decoder.update(pressed, now).map(|gesture| (now, gesture)));
+        }
+        now = until;
+    }
+    gestures
+}
+
+/// Returns just the gestures played.
+fn gestures(levels: &[(bool, u64)]) -> Vec<Gesture> {
+    play(levels)
+        .into_iter()
+        .map(|(_, gesture)| gesture)
+        .collect()
+}
+
+#[test]
+fn idle_decoder_needs_no_polling() {
+    let mut decoder = GestureDecoder::new();
+    assert_eq!(decoder.deadline(), None);
+    assert_eq!(decoder.update(false, 1000), None);
+    assert_eq!(decoder.deadline(), None);
+}
+
+#[test]
+fn short_press_is_reported_when_the_double_press_window_closes() {
+    assert_eq!(
+        play(&[(true, 100), (false, 0)]),
+        [(100 + DOUBLE_PRESS_WINDOW_MS, Gesture::Short)]
+    );
+}
+
+#[test]
+fn bounces_are_ignored() {
+    let bouncy_press = [
+        (true, 5),
+        (false, 3),
+        (true, 8),
+        (false, 2),
+        (true, 100),
+        (false, 4),
+        (true, 6),
+        (false, 0),
+    ];
+    assert_eq!(gestures(&bouncy_press), [Gesture::Short]);
+}
+
+#[test]
+fn glitch_shorter_than_debounce_is_no_press() {
+    assert_eq!(gestures(&[(true, DEBOUNCE_MS - 1), (false, 0)]), []);
+}
+
+#[test]
+fn two_quick_presses_are_a_double_press() {
+    assert_eq!(
+        gestures(&[(true, 100), (false, 200), (true, 100), (false, 0)]),
+        [Gesture::Double]
+    );
+}
+
+#[test]
+fn presses_further_apart_are_two_short_presses() {
+    let gap = DOUBLE_PRESS_WINDOW_MS + 100;
+    assert_eq!(
+        gestures(&[(true, 100), (false, gap), (true, 100), (false, 0)]),
+        [Gesture::Short, Gesture::Short]
+    );
+}
+
+#[test]
+fn holds_are_long_and_very_long_presses() {
+    assert_eq!(
+        gestures(&[(true, LONG_PRESS_MS + 100), (false, 0)]),
+        [Gesture::Long]
+    );
+    assert_eq!(
+        gestures(&[(true, VERY_LONG_PRESS_MS + 100), (false, 0)]),
+        [Gesture::VeryLong]
+    );
+}
+
+#[test]
+fn short_press_before_a_long_press_is_not_lost() {
+    let played = play(&[(true, 100), (false, 200), (true, 2000), (false, 0)]);
+    assert_eq!(
+        played,
+        [
+            // Second press went down at 300 ms and became long
+            (300 + LONG_PRESS_MS, Gesture::Short),
+            // Released at 2300 ms, reported once debounced
+            (2300 + DEBOUNCE_MS, Gesture::Long),
+        ]
+    );
+}
+
+#[test]
+fn short_press_before_a_very_long_press_is_not_lost() {
+    assert_eq!(
+        gestures(&[(true, 100), (false, 200), (true, 6000), (false, 0)]),
+        [Gesture::Short, Gesture::VeryLong]
+    );
+}
@@ -0,0 +1,236 @@
+//! Key-value store tests, with power lost partway through updates.
+
+use host_tests::kv_store::{Error, Key, KvStore};
+use host_tests::ram_storage::{RamError, RamStorage};
+
+/// Storage offset of the store.
+const BASE: u32 = 0x40;
+/// Page size, in bytes.
+const PAGE_SIZE: u32 = 64;
+/// Number of pages.
+const PAGES: u32 = 3;
+
+/// Data EEPROM, with the store at [`BASE`] filling the rest of it.
+type Eeprom = RamStorage<{ (BASE + PAGES * PAGE_SIZE) as usize }>;
+/// Store under test.
+type Store = KvStore<Eeprom>;
+
+const COUNT: Key<u32> = Key::new(1);
+const NAME: Key<[u8; 5]> = Key::new(2);
+const LARGE: Key<[u8; 20]> = Key::new(3);
+const OTHER_LARGE: Key<[u8; 20]> = Key::new(4);
+
+/// Opens the store on `eeprom`.
+fn open(eeprom: Eeprom) -> Store {
+    KvStore::open(eeprom, BASE, PAGE_SIZE, PAGES).unwrap()
+}
+
+/// Closes the store and opens it again, as after a reset.
+fn reopen(store: Store) -> Store {
+    open(store.release())
+}
+
+#[test]
+fn blank_store_is_empty() {
+    let mut store = open(Eeprom::new());
+    assert_eq!(store.fetch(&COUNT), Ok(None));
+    assert_eq!(store.fetch(&NAME), Ok(None));
+
+    // Formatting only writes the first page
+    let eeprom = store.release();
+    let written: Vec<usize> = (0..eeprom.bytes.len())
+        .filter(|&n| eeprom.writes[n] > 0)
+        .collect();
+    let page = BASE as usize;
+    assert_eq!(
+        written,
+        (page..page + PAGE_SIZE as usize).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn values_survive_a_reset() {
+    let mut store = open(Eeprom::new());
+    store.store(&COUNT, &7).unwrap();
+    store.store(&NAME, b"tree1").unwrap();
+    store.store(&COUNT, &8).unwrap();
+
+    let mut store = reopen(store);
+    assert_eq!(store.fetch(&COUNT), Ok(Some(8)));
+    assert_eq!(store.fetch(&NAME), Ok(Some(*b"tree1")));
+
+    // Updates carry on after the reset
+    store.store(&COUNT, &9).unwrap();
+    assert_eq!(reopen(store).fetch(&COUNT), Ok(Some(9)));
+}
+
+#[test]
+fn value_of_another_size_is_not_read() {
+    let mut store = open(Eeprom::new());
+    store.store(&COUNT, &7).unwrap();
+    assert_eq!(store.fetch(&Key::<[u8; 2]>::new(1)), Ok(None));
+    assert_eq!(store.fetch(&Key::<[u8; 4]>::new(1)), Ok(Some([7, 0, 0, 0])));
+}
+
+#[test]
+fn updates_move_through_the_pages() {
+    // Counts that fit in a page beside the name, and updates to write
+    const COUNTS_PER_PAGE: u32 = (PAGE_SIZE - 4 - 9) / 8;
+    const UPDATES: u32 = 700;
+
+    let mut store = open(Eeprom::new());
+    store.store(&NAME, b"tree1").unwrap();
+    for n in 0..UPDATES {
+        store.store(&COUNT, &n).unwrap();
+    }
+
+    let mut store = reopen(store);
+    assert_eq!(store.fetch(&COUNT), Ok(Some(UPDATES - 1)));
+    assert_eq!(store.fetch(&NAME), Ok(Some(*b"tree1")));
+
+    // Each byte is written once per fill of its page, with the fills
+    // shared evenly between the pages, and once more by formatting
+    let eeprom = store.release();
+    let most = eeprom.writes.iter().max().copied().unwrap();
+    let fills = UPDATES.div_ceil(COUNTS_PER_PAGE);
+    assert!(
+        most <= fills.div_ceil(PAGES) + 1,
+        "{most} writes to one byte"
+    );
+    assert!(eeprom.writes[..BASE as usize].iter().all(|&n| n == 0));
+}
+
+#[test]
+fn store_reports_full_when_the_values_outgrow_a_page() {
+    let mut store = open(Eeprom::new());
+    store.store(&LARGE, &[1; 20]).unwrap();
+    store.store(&OTHER_LARGE, &[2; 20]).unwrap();
+    assert_eq!(store.store(&Key::new(5), &[3u8; 20]), Err(Error::Full));
+
+    // The stored values are kept, and can still be replaced
+    let mut store = reopen(store);
+    assert_eq!(store.fetch(&LARGE), Ok(Some([1; 20])));
+    assert_eq!(store.fetch(&OTHER_LARGE), Ok(Some([2; 20])));
+    assert_eq!(store.fetch(&Key::<[u8; 20]>::new(5)), Ok(None));
+    store.store(&LARGE, &[4; 20]).unwrap();
+    assert_eq!(reopen(store).fetch(&LARGE), Ok(Some([4; 20])));
+}
+
+#[test]
+fn corrupted_header_is_not_trusted() {
+    // Filling the first page moves the store to the second
+    let mut store = open(Eeprom::new());
+    for n in 0..8 {
+        store.store(&COUNT, &n).unwrap();
+    }
+    let mut eeprom = store.release();
+    let second = (BASE + PAGE_SIZE) as usize;
+    assert_eq!(eeprom.writes[second], 1);
+
+    // A bit flipped in the active page's generation falls back to the
+    // values of the page before
+    eeprom.bytes[second] ^= 0x01;
+    let mut store = open(eeprom);
+    assert_eq!(store.fetch(&COUNT), Ok(Some(6)));
+
+    // With no valid header left, the store starts empty, without the
+    // records left in the pages
+    let mut eeprom = store.release();
+    eeprom.bytes[BASE as usize + 2] ^= 0x80;
+    let mut store = open(eeprom);
+    assert_eq!(store.fetch(&COUNT), Ok(None));
+    store.store(&NAME, b"tree1").unwrap();
+    let mut store = reopen(store);
+    assert_eq!(store.fetch(&COUNT), Ok(None));
+    assert_eq!(store.fetch(&NAME), Ok(Some(*b"tree1")));
+}
+
+/// Stores `value` under [`COUNT`] with power failing after `budget` bytes,
+/// then resets.
+///
+/// # Arguments
+///
+/// * `eeprom` - Storage holding the store before the update
+/// * `value` - Value to store
+/// * `budget` - Bytes written before power fails
+///
+/// # Returns
+///
+/// The store after the reset, and whether the update completed
+fn store_with_power_loss(eeprom: &Eeprom, value: u32, budget: usize) -> (Store, bool) {
+    let mut eeprom = eeprom.clone();
+    eeprom.fail_after(budget);
+    let mut store = open(eeprom);
+    let completed = match store.store(&COUNT, &value) {
+        Ok(()) => true,
+        Err(error) => {
+            assert_eq!(error, Error::Storage(RamError::PowerLoss));
+            false
+        }
+    };
+
+    let mut eeprom = store.release();
+    eeprom.restore_power();
+    (open(eeprom), completed)
+}
+
+/// Checks a store after an update torn at every byte, until the budget is
+/// large enough for the update to complete.
+///
+/// # Arguments
+///
+/// * `updates` - Updates to [`COUNT`] before the torn one
+///
+/// # Returns
+///
+/// The number of bytes the torn update writes
+fn check_torn_updates(updates: u32) -> usize {
+    let mut store = open(Eeprom::new());
+    store.store(&NAME, b"tree1").unwrap();
+    for n in 0..updates {
+        store.store(&COUNT, &n).unwrap();
+    }
+    let eeprom = store.release();
+    let before = updates.checked_sub(1);
+
+    for budget in 0.. {
+        let (mut store, completed) = store_with_power_loss(&eeprom, 1000, budget);
+
+        // Either the old value or the new one, never anything else
+        let expected = if completed { Some(1000) } else { before };
+        assert_eq!(
+            store.fetch(&COUNT),
+            Ok(expected),
+            "{updates} updates, torn after {budget} bytes"
+        );
+        assert_eq!(store.fetch(&NAME), Ok(Some(*b"tree1")));
+
+        // The store carries on where the torn update left it
+        store.store(&COUNT, &2000).unwrap();
+        let mut store = reopen(store);
+        assert_eq!(store.fetch(&COUNT), Ok(Some(2000)));
+        assert_eq!(store.fetch(&NAME), Ok(Some(*b"tree1")));
+
+        if completed {
+            return budget;
+        }
+    }
+    unreachable!()
+}
+
+#[test]
+fn torn_append_keeps_the_old_value() {
+    // Key, length, value and check word
+    assert_eq!(check_torn_updates(0), 8);
+    assert_eq!(check_torn_updates(3), 8);
+}
+
+#[test]
+fn torn_compaction_keeps_the_old_values() {
+    // The name record and 6 counts fill the first page: the torn update
+    // copies the name, then writes the count and the next page's header
+    assert_eq!(check_torn_updates(6), 9 + 8 + 4);
+
+    // Compaction into a page holding stale records of an older generation
+    assert_eq!(check_torn_updates(6 + 2 * 6), 9 + 8 + 4);
+}
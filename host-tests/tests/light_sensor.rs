@@ -0,0 +1,107 @@
+//! Day/night detector tests with scripted decay times.
+
+use host_tests::light_sensor::{Ambient, CONFIRM_SAMPLES, DarkDetector};
+
+/// Decay time in daylight, in µs.
+const DAY_US: u32 = 100;
+
+/// Decay time at night, in µs.
+const NIGHT_US: u32 = 1_000;
+
+/// Feeds decay times to a detector.
+///
+/// Returns the changes it reported, by sample index.
+fn feed(detector: &mut DarkDetector, samples: &[u32]) -> Vec<(usize, Ambient)> {
+    samples
+        .iter()
+        .enumerate()
+        .filter_map(|(n, &decay_us)| Some((n, detector.update(decay_us)?)))
+        .collect()
+}
+
+/// Returns a detector calibrated on one night and one day sample, in
+/// daylight.
+fn calibrated() -> DarkDetector {
+    let mut detector = DarkDetector::new();
+    feed(&mut detector, &[NIGHT_US, DAY_US, DAY_US, DAY_US]);
+    assert_eq!(detector.ambient(), Ambient::Light);
+    detector
+}
+
+#[test]
+fn uncalibrated_detector_reports_darkness() {
+    let mut detector = DarkDetector::new();
+    assert_eq!(detector.ambient(), Ambient::Dark);
+
+    // Too little contrast to tell day from night
+    assert_eq!(feed(&mut detector, &[600, 400, 700, 400, 400, 400]), []);
+    assert_eq!(detector.ambient(), Ambient::Dark);
+}
+
+#[test]
+fn changes_need_confirming_samples() {
+    let mut detector = DarkDetector::new();
+    let samples = [NIGHT_US, DAY_US, DAY_US, DAY_US];
+    let confirmed = CONFIRM_SAMPLES as usize;
+    assert_eq!(feed(&mut detector, &samples), [(confirmed, Ambient::Light)]);
+
+    assert_eq!(
+        feed(&mut detector, &[NIGHT_US; 5]),
+        [(confirmed - 1, Ambient::Dark)]
+    );
+}
+
+#[test]
+fn short_glitches_are_ignored() {
+    let mut detector = calibrated();
+
+    // Headlights at night, and the ornament's own LEDs by day
+    let mut samples = vec![NIGHT_US; 10];
+    samples.insert(4, DAY_US);
+    samples.insert(5, DAY_US);
+    let changes = feed(&mut detector, &samples);
+    assert_eq!(changes, [(2, Ambient::Dark)]);
+
+    let mut samples = vec![DAY_US; 5];
+    samples.insert(1, NIGHT_US);
+    samples.insert(3, NIGHT_US);
+    assert_eq!(feed(&mut detector, &samples), [(6, Ambient::Light)]);
+}
+
+#[test]
+fn dusk_in_the_hysteresis_band_changes_nothing() {
+    let mut detector = calibrated();
+
+    // Around halfway from day to night, briefly: the calibration drifts
+    // towards the samples
+    let dusk: Vec<u32> = (0..30).map(|n| 480 + n % 40).collect();
+    assert_eq!(feed(&mut detector, &dusk), []);
+    assert_eq!(detector.ambient(), Ambient::Light);
+
+    assert_eq!(feed(&mut detector, &[700; 3]), [(2, Ambient::Dark)]);
+    assert_eq!(feed(&mut detector, &dusk), []);
+    assert_eq!(detector.ambient(), Ambient::Dark);
+}
+
+#[test]
+fn calibration_recovers_from_an_outlier() {
+    let mut detector = calibrated();
+
+    // One very long decay, as if the sensing LED were covered
+    feed(&mut detector, &[20_000]);
+    feed(&mut detector, &[DAY_US; 3]);
+    assert_eq!(detector.ambient(), Ambient::Light);
+    // Nights now look like daylight...
+    assert_eq!(feed(&mut detector, &[NIGHT_US; 3]), []);
+
+    // ...until a few days and nights have drifted the darkest decay back
+    let days: Vec<u32> = (0..2000)
+        .map(|n| if n % 200 < 100 { DAY_US } else { NIGHT_US })
+        .collect();
+    feed(&mut detector, &days);
+    let changes = feed(
+        &mut detector,
+        &[DAY_US, DAY_US, DAY_US, NIGHT_US, NIGHT_US, NIGHT_US],
+    );
+    assert_eq!(changes.last(), Some(&(5, Ambient::Dark)));
+}
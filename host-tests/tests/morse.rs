@@ -0,0 +1,213 @@
+//! Morse codes, unit timing and the EEPROM record.
+
+use host_tests::morse::{Cursor, Message, RECORD_SIZE, Step, code};
+
+/// ITU codes of the characters the ornament can play.
+const ITU: [(u8, &str); 46] = [
+    (b'A', ".-"),
+    (b'B', "-..."),
+    (b'C', "-.-."),
+    (b'D', "-.."),
+    (b'E', "."),
+    (b'F', "..-."),
+    (b'G', "--."),
+    (b'H', "...."),
+    (b'I', ".."),
+    (b'J', ".---"),
+    (b'K', "-.-"),
+    (b'L', ".-.."),
+    (b'M', "--"),
+    (b'N', "-."),
+    (b'O', "---"),
+    (b'P', ".--."),
+    (b'Q', "--.-"),
+    (b'R', ".-."),
+    (b'S', "..."),
+    (b'T', "-"),
+    (b'U', "..-"),
+    (b'V', "...-"),
+    (b'W', ".--"),
+    (b'X', "-..-"),
+    (b'Y', "-.--"),
+    (b'Z', "--.."),
+    (b'0', "-----"),
+    (b'1', ".----"),
+    (b'2', "..---"),
+    (b'3', "...--"),
+    (b'4', "....-"),
+    (b'5', "....."),
+    (b'6', "-...."),
+    (b'7', "--..."),
+    (b'8', "---.."),
+    (b'9', "----."),
+    (b'.', ".-.-.-"),
+    (b',', "--..--"),
+    (b'?', "..--.."),
+    (b'\'', ".----."),
+    (b'!', "-.-.--"),
+    (b'/', "-..-."),
+    (b'&', ".-..."),
+    (b'=', "-...-"),
+    (b'+', ".-.-."),
+    (b'-', "-....-"),
+];
+
+/// Returns the code of an ITU table entry.
+fn itu(c: u8) -> &'static str {
+    ITU.iter().find(|(mark, _)| *mark == c).unwrap().1
+}
+
+/// Unpacks a code into `.` and `-`, first symbol in bit 0 up to the
+/// terminating 1 bit.
+fn symbols(mut packed: u8) -> String {
+    let mut symbols = String::new();
+    while packed > 1 {
+        symbols.push(if packed & 1 != 0 { '-' } else { '.' });
+        packed >>= 1;
+    }
+    symbols
+}
+
+#[test]
+fn codes_match_the_itu_table() {
+    for (c, expected) in ITU {
+        assert_eq!(
+            code(c).map(symbols).as_deref(),
+            Some(expected),
+            "{}",
+            c as char
+        );
+    }
+
+    // Letters are case-insensitive
+    for c in b'a'..=b'z' {
+        assert_eq!(code(c), code(c.to_ascii_uppercase()));
+    }
+}
+
+#[test]
+fn unknown_bytes_have_no_code() {
+    for c in 0..=255u8 {
+        let known = ITU.iter().any(|(mark, _)| *mark == c.to_ascii_uppercase());
+        assert_eq!(code(c).is_some(), known, "{c:#04x}");
+    }
+}
+
+/// Returns the steps of a message as runs of equal steps, over one
+/// repetition.
+fn runs(text: &[u8]) -> Vec<(Step, u32)> {
+    let mut cursor = Cursor::new();
+    let mut runs: Vec<(Step, u32)> = Vec::new();
+    loop {
+        let step = cursor.next(text);
+        if let Some((last, units)) = runs.last_mut()
+            && *last == step
+        {
+            *units += 1;
+            continue;
+        }
+        // The message gap ends one repetition
+        if runs.last() == Some(&(Step::WordGap, 14)) {
+            return runs;
+        }
+        runs.push((step, 1));
+    }
+}
+
+#[test]
+fn merry_xmas_follows_standard_timing() {
+    // Built from the ITU table: 1 unit per dit, 3 per dah, 1 between
+    // symbols, 3 between letters, 7 between words, 14 before repeating
+    let mut expected = Vec::new();
+    for (w, word) in ["MERRY", "XMAS"].iter().enumerate() {
+        if w > 0 {
+            expected.push((Step::WordGap, 7));
+        }
+        for (l, c) in word.bytes().enumerate() {
+            if l > 0 {
+                expected.push((Step::Space, 3));
+            }
+            for (s, symbol) in itu(c).chars().enumerate() {
+                if s > 0 {
+                    expected.push((Step::Space, 1));
+                }
+                expected.push((Step::Mark, if symbol == '-' { 3 } else { 1 }));
+            }
+        }
+    }
+    expected.push((Step::WordGap, 14));
+
+    let runs = runs(b"MERRY XMAS");
+    assert_eq!(runs, expected);
+
+    // Unit totals of the marks, the spaces and the word gaps
+    let total = |step| -> u32 {
+        runs.iter()
+            .filter(|(s, _)| *s == step)
+            .map(|(_, units)| units)
+            .sum()
+    };
+    assert_eq!(total(Step::Mark), 48);
+    assert_eq!(total(Step::Space), 36);
+    assert_eq!(total(Step::WordGap), 21);
+}
+
+#[test]
+fn characters_without_a_code_are_word_gaps() {
+    assert_eq!(runs(b"E#E"), runs(b"E E"));
+    assert_eq!(
+        runs(b"E E"),
+        [
+            (Step::Mark, 1),
+            (Step::WordGap, 7),
+            (Step::Mark, 1),
+            (Step::WordGap, 14)
+        ]
+    );
+
+    // Nothing to play lights the word marker
+    let mut cursor = Cursor::new();
+    for _ in 0..100 {
+        assert_eq!(cursor.next(b"  #"), Step::WordGap);
+    }
+}
+
+#[test]
+fn message_is_truncated_and_its_speed_clamped() {
+    let message = Message::new(&[b'A'; 40], 50);
+    assert_eq!(message.text(), [b'A'; 32]);
+    assert_eq!(message.unit_ms(), 1200 / 20);
+    assert_eq!(Message::new(b"HI", 0).unit_ms(), 1200 / 2);
+    assert_eq!(Message::default().text(), b"MERRY XMAS");
+}
+
+#[test]
+fn record_round_trips() {
+    for message in [
+        Message::default(),
+        Message::new(b"", 2),
+        Message::new(b"SEASON'S GREETINGS TO ALL OF YOU", 20),
+    ] {
+        assert_eq!(Message::from_record(&message.to_record()), Some(message));
+    }
+}
+
+#[test]
+fn corrupted_record_is_rejected() {
+    let record = Message::new(b"NOEL", 12).to_record();
+    assert_eq!(Message::from_record(&[0; RECORD_SIZE]), None);
+    assert_eq!(Message::from_record(&[0xFF; RECORD_SIZE]), None);
+
+    // A bit flipped anywhere, reserved bytes and padding included
+    for byte in 0..RECORD_SIZE {
+        for bit in 0..8 {
+            let mut corrupted = record;
+            corrupted[byte] ^= 1 << bit;
+            assert_eq!(
+                Message::from_record(&corrupted),
+                None,
+                "byte {byte}, bit {bit}"
+            );
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Battery policy decision tables.
+
+use host_tests::fuel_gauge::LOW_BATTERY_PERCENT;
+use host_tests::power_policy::{Action, Balance, Failover, PowerPolicy, Reserve, SingleCell};
+use host_tests::power_state::PowerState;
+
+use Action::{Stay, Switch};
+use PowerState::{BackupPower as Backup, MainPower as Main};
+
+const LOW: u8 = LOW_BATTERY_PERCENT;
+const HOUR_MS: u32 = 3_600_000;
+
+/// Returns each policy, by name.
+fn policies() -> [(&'static str, Box<dyn PowerPolicy>); 4] {
+    [
+        ("failover", Box::new(Failover)),
+        ("balance", Box::new(Balance::new(4))),
+        ("reserve", Box::new(Reserve)),
+        ("single", Box::new(SingleCell)),
+    ]
+}
+
+#[test]
+fn low_voltage_actions() {
+    // Policy, then the action with main and backup failing
+    let table = [
+        ("failover", Switch, Switch),
+        ("balance", Switch, Switch),
+        ("reserve", Switch, Stay),
+        ("single", Stay, Stay),
+    ];
+    for ((name, mut policy), (expected_name, main, backup)) in policies().into_iter().zip(table) {
+        assert_eq!(name, expected_name);
+        assert_eq!(policy.on_low_voltage(Main), main, "{name} on main");
+        assert_eq!(policy.on_low_voltage(Backup), backup, "{name} on backup");
+    }
+}
+
+#[test]
+fn warnings() {
+    // Active battery and charge remaining, then whether failover, balance,
+    // reserve and single raise the warning
+    let table = [
+        (Main, [100, 100], [false, false, false, false]),
+        (Main, [LOW + 1, LOW + 1], [false, false, false, false]),
+        (Main, [LOW, 100], [true, true, true, true]),
+        (Main, [100, LOW], [false, true, false, false]),
+        (Backup, [100, 100], [true, false, true, true]),
+        (Backup, [0, 100], [true, true, true, true]),
+        (Backup, [0, LOW], [true, true, true, true]),
+    ];
+    for (active, percent, expected) in table {
+        for ((name, policy), expected) in policies().iter().zip(expected) {
+            assert_eq!(
+                policy.warning_required(active, percent),
+                expected,
+                "{name} on {active:?} at {percent:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn only_reserve_shows_the_warning_alone() {
+    for (name, policy) in policies() {
+        assert!(!policy.warning_only(Main), "{name}");
+        assert_eq!(policy.warning_only(Backup), name == "reserve", "{name}");
+    }
+}
+
+#[test]
+fn only_balance_switches_on_updates() {
+    for (name, mut policy) in policies() {
+        let actions: Vec<Action> = (0..24)
+            .map(|_| policy.on_update(Main, HOUR_MS, [50, 50]))
+            .collect();
+        assert_eq!(actions.contains(&Switch), name == "balance", "{name}");
+    }
+}
+
+/// Feeds a policy hourly updates while following its switches.
+///
+/// # Arguments
+///
+/// * `policy` - Policy under test
+/// * `active` - Battery active at the start, updated on each switch
+/// * `hours` - Number of updates
+/// * `percent` - Charge remaining reported with each update
+///
+/// # Returns
+///
+/// The hours, counted from 1, after which the policy switched
+fn run(policy: &mut Balance, active: &mut PowerState, hours: u32, percent: [u8; 2]) -> Vec<u32> {
+    (1..=hours)
+        .filter(|_| {
+            let switched = policy.on_update(*active, HOUR_MS, percent) == Switch;
+            if switched {
+                *active = other(*active);
+            }
+            switched
+        })
+        .collect()
+}
+
+/// Returns the battery in the other slot.
+fn other(cell: PowerState) -> PowerState {
+    match cell {
+        Main => Backup,
+        Backup => Main,
+    }
+}
+
+#[test]
+fn balance_alternates_every_period() {
+    let mut policy = Balance::new(4);
+    let mut active = Main;
+    assert_eq!(
+        run(&mut policy, &mut active, 20, [80, 80]),
+        [4, 8, 12, 16, 20]
+    );
+    assert_eq!(active, Backup);
+
+    // Longer updates count in full
+    let mut policy = Balance::new(4);
+    assert_eq!(policy.on_update(Main, 4 * HOUR_MS - 1, [80, 80]), Stay);
+    assert_eq!(policy.on_update(Main, 1, [80, 80]), Switch);
+}
+
+#[test]
+fn balance_keeps_off_a_nearly_empty_cell() {
+    let mut policy = Balance::new(4);
+    let mut active = Main;
+    assert_eq!(run(&mut policy, &mut active, 10, [80, LOW]), []);
+
+    // The backup is replaced: the overdue switch happens at once
+    assert_eq!(run(&mut policy, &mut active, 5, [80, 100]), [1, 5]);
+    assert_eq!(active, Main);
+}
+
+#[test]
+fn balance_restarts_its_period_after_a_failure() {
+    let mut policy = Balance::new(4);
+    let mut active = Main;
+    assert_eq!(run(&mut policy, &mut active, 3, [50, 50]), []);
+
+    assert_eq!(policy.on_low_voltage(active), Switch);
+    active = other(active);
+    // A full period on the backup, had the main cell recovered
+    assert_eq!(run(&mut policy, &mut active, 4, [50, 50]), [4]);
+
+    // With the failed cell marked empty, the backup is run on its own
+    let mut policy = Balance::new(4);
+    let mut active = Backup;
+    assert_eq!(run(&mut policy, &mut active, 20, [0, 50]), []);
+}
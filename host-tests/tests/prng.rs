@@ -0,0 +1,56 @@
+//! Xorshift32 sequences and the seed hash.
+
+use host_tests::prng::{Xorshift32, mix};
+
+#[test]
+fn fixed_seed_gives_the_xorshift32_sequence() {
+    // Marsaglia's xorshift32 with shifts 13, 17 and 5, from seed 1
+    let mut rng = Xorshift32::new(1);
+    let values: Vec<u32> = (0..5).map(|_| rng.next_u32()).collect();
+    assert_eq!(
+        values,
+        [
+            270_369,
+            67_634_689,
+            2_647_435_461,
+            307_599_695,
+            2_398_689_233
+        ]
+    );
+
+    // The same seed, the same sequence
+    let mut again = Xorshift32::new(1);
+    assert_eq!(again.next_u32(), 270_369);
+}
+
+#[test]
+fn zero_seed_is_replaced() {
+    let mut rng = Xorshift32::new(0);
+    let values: Vec<u32> = (0..3).map(|_| rng.next_u32()).collect();
+    assert_eq!(values, [0xe124_b63a, 0x8b9a_74ab, 0x64e1_b3ac]);
+    assert!((0..10_000).all(|_| rng.next_u32() != 0));
+}
+
+#[test]
+fn range_stays_within_its_bounds() {
+    let mut rng = Xorshift32::new(0x1234_5678);
+    let mut seen = [false; 8];
+    for _ in 0..10_000 {
+        let value = rng.range(3, 10);
+        assert!((3..=10).contains(&value), "{value}");
+        seen[value as usize - 3] = true;
+    }
+    assert!(seen.iter().all(|&seen| seen), "every value drawn");
+
+    assert!((0..100).all(|_| rng.range(7, 7) == 7));
+    // The full range has no span to take the remainder of
+    let mut full = Xorshift32::new(1);
+    assert_eq!(full.range(0, u32::MAX), 270_369);
+}
+
+#[test]
+fn mix_is_the_murmur3_finalizer() {
+    assert_eq!(mix(0, 0), 0);
+    assert_eq!(mix(0, 1), 0x514e_28b7);
+    assert_eq!(mix(0x1234_5678, 0x9abc_def0), 0x1d6e_f8ae);
+}
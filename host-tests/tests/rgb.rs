@@ -0,0 +1,128 @@
+//! RGB renderer tests against golden frames.
+
+use host_tests::rgb::{FADE_STEPS, PALETTE, Renderer, Rgb, RgbPattern, gradient};
+
+const RED: Rgb = PALETTE[0];
+const GREEN: Rgb = PALETTE[1];
+const GOLD: Rgb = PALETTE[2];
+const WARM_WHITE: Rgb = PALETTE[3];
+const BLACK: Rgb = Rgb::BLACK;
+
+/// Renders `count` frames of a pattern on four LEDs, starting from black.
+fn render(pattern: RgbPattern, seed: u32, count: usize) -> Vec<[Rgb; 4]> {
+    let mut renderer = Renderer::<4>::new(seed);
+    renderer.set_pattern(pattern);
+    (0..count).map(|_| *renderer.render()).collect()
+}
+
+#[test]
+fn colors_scale_and_blend() {
+    assert_eq!(RED.scale(128), Rgb::new(127, 0, 0));
+    assert_eq!(RED.scale(0), BLACK);
+    assert_eq!(RED.lerp(GREEN, 0), RED);
+    assert_eq!(RED.lerp(GREEN, 128), Rgb::new(127, 127, 0));
+    assert!(BLACK.is_black());
+    assert!(!Rgb::new(0, 0, 1).is_black());
+}
+
+#[test]
+fn gradient_passes_through_the_palette() {
+    assert_eq!(gradient(0), RED);
+    assert_eq!(gradient(64), GREEN);
+    assert_eq!(gradient(128), GOLD);
+    assert_eq!(gradient(192), WARM_WHITE);
+    // 40/256 of the way from red to green
+    assert_eq!(gradient(10), Rgb::new(215, 39, 0));
+    // Wraps from warm white back to red
+    assert_eq!(gradient(255), Rgb::new(255, 3, 1));
+}
+
+#[test]
+fn gradient_frames_match_golden() {
+    let frames = render(RgbPattern::Gradient, 1, FADE_STEPS as usize + 1);
+
+    // Cross-fades in from black
+    assert_eq!(frames[0], [BLACK; 4]);
+
+    // Fully faded in, rotated by 5 steps of 2/256ths
+    let golden = [
+        Rgb::new(215, 39, 0),
+        Rgb::new(39, 240, 0),
+        Rgb::new(255, 166, 18),
+        Rgb::new(255, 168, 101),
+    ];
+    assert_eq!(frames[FADE_STEPS as usize], golden);
+}
+
+#[test]
+fn chase_frames_match_golden() {
+    let frames = render(RgbPattern::Chase, 1, 46);
+
+    // Each string pattern frame is held for 20 rendered frames, faded in
+    // to 255/256 over the first few; frame 1 is the dark gap after LED 0
+    assert_eq!(frames[19], [Rgb::new(254, 0, 0), BLACK, BLACK, BLACK]);
+    assert_eq!(frames[25], [BLACK; 4]);
+    // Fading in LED 1, 102/256 of the way
+    assert_eq!(frames[42], [BLACK, Rgb::new(0, 101, 0), BLACK, BLACK]);
+    assert_eq!(frames[45], [BLACK, Rgb::new(0, 254, 0), BLACK, BLACK]);
+}
+
+#[test]
+fn pattern_change_cross_fades_from_the_last_frame() {
+    let mut renderer = Renderer::<4>::new(1);
+    renderer.set_pattern(RgbPattern::Gradient);
+    let last = (0..40).map(|_| *renderer.render()).last().unwrap();
+
+    renderer.set_pattern(RgbPattern::Chase);
+    assert_eq!(*renderer.render(), last);
+    for _ in 1..FADE_STEPS {
+        renderer.render();
+    }
+    // Chase frame 0, faded in from the dark gap before it
+    assert_eq!(
+        *renderer.render(),
+        [Rgb::new(254, 0, 0), BLACK, BLACK, BLACK]
+    );
+}
+
+#[test]
+fn random_patterns_depend_only_on_the_seed() {
+    for pattern in [RgbPattern::Sparkle, RgbPattern::Twinkle] {
+        let frames = render(pattern, 0x1234_5678, 200);
+        assert_eq!(frames, render(pattern, 0x1234_5678, 200));
+        assert_ne!(frames, render(pattern, 0x8765_4321, 200));
+        assert!(frames.iter().flatten().any(|led| !led.is_black()));
+    }
+}
+
+#[test]
+fn sparkles_fade_out() {
+    let frames = render(RgbPattern::Sparkle, 7, 400);
+    for pair in frames[FADE_STEPS as usize..].windows(2) {
+        for (before, after) in pair[0].iter().zip(&pair[1]) {
+            // Either a new sparkle in a palette color or the old one dimmed
+            assert!(
+                PALETTE.contains(after) || *after == before.scale(200),
+                "{before:?} -> {after:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn twinkling_leds_keep_their_palette_color() {
+    let frames = render(RgbPattern::Twinkle, 7, 400);
+    for frame in &frames[FADE_STEPS as usize..] {
+        for (n, led) in frame.iter().enumerate() {
+            let color = PALETTE[n % PALETTE.len()];
+            let dimmed = (0..40).scan(color, |c, _| {
+                *c = c.scale(200);
+                Some(*c)
+            });
+            assert!(
+                *led == color || dimmed.into_iter().any(|c| c == *led),
+                "LED {n} is {led:?}"
+            );
+        }
+    }
+}
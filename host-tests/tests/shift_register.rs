@@ -0,0 +1,215 @@
+//! Shift-register backend tests against a simulated 74HC595 chain.
+
+use std::cell::RefCell;
+use std::convert::Infallible;
+use std::rc::Rc;
+
+use embedded_hal::digital::v2::OutputPin;
+use host_tests::led_backend::{Frame, LedBackend};
+use host_tests::shift_register::{ShiftRegister, shift_order};
+
+/// Strings driven in the tests, spread over two registers.
+const STRINGS: usize = 12;
+
+/// Stages in the simulated chain of two registers.
+const STAGES: usize = 16;
+
+/// Something the chain did on a rising clock edge.
+#[derive(Debug, PartialEq)]
+enum Edge {
+    /// SRCLK rose, shifting SER in
+    Shift,
+    /// RCLK rose, copying the stages to these outputs
+    Latch([bool; STAGES]),
+}
+
+/// Two 74HC595s with QH' of the first feeding SER of the second.
+struct Chain {
+    ser: bool,
+    srclk: bool,
+    rclk: bool,
+    /// Shift stages, QA of the first register first
+    stages: [bool; STAGES],
+    /// Outputs, QA of the first register first
+    outputs: [bool; STAGES],
+    edges: Vec<Edge>,
+}
+
+impl Chain {
+    /// Creates a chain with undefined, here all on, power-up outputs.
+    fn new() -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self {
+            ser: false,
+            srclk: false,
+            rclk: false,
+            stages: [true; STAGES],
+            outputs: [true; STAGES],
+            edges: Vec::new(),
+        }))
+    }
+
+    /// Returns the outputs wired to strings.
+    fn strings(&self) -> [bool; STRINGS] {
+        self.outputs[..STRINGS].try_into().unwrap()
+    }
+}
+
+/// The MCU pin wired to one of the chain's inputs.
+#[derive(Clone, Copy)]
+enum Line {
+    Ser,
+    Srclk,
+    Rclk,
+}
+
+struct Pin {
+    line: Line,
+    chain: Rc<RefCell<Chain>>,
+}
+
+impl Pin {
+    fn set(&mut self, level: bool) {
+        let mut chain = self.chain.borrow_mut();
+        match self.line {
+            Line::Ser => chain.ser = level,
+            Line::Srclk => {
+                if level && !chain.srclk {
+                    let ser = chain.ser;
+                    chain.stages.copy_within(..STAGES - 1, 1);
+                    chain.stages[0] = ser;
+                    chain.edges.push(Edge::Shift);
+                }
+                chain.srclk = level;
+            }
+            Line::Rclk => {
+                if level && !chain.rclk {
+                    assert!(!chain.srclk, "latched with SRCLK high");
+                    chain.outputs = chain.stages;
+                    let outputs = chain.outputs;
+                    chain.edges.push(Edge::Latch(outputs));
+                }
+                chain.rclk = level;
+            }
+        }
+    }
+}
+
+impl OutputPin for Pin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.set(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.set(true);
+        Ok(())
+    }
+}
+
+/// Returns a driver wired to a new chain, and the chain.
+fn wire() -> (ShiftRegister<STRINGS, Pin>, Rc<RefCell<Chain>>) {
+    let chain = Chain::new();
+    let pin = |line| Pin {
+        line,
+        chain: chain.clone(),
+    };
+    let driver = ShiftRegister::new(pin(Line::Ser), pin(Line::Srclk), pin(Line::Rclk));
+    (driver, chain)
+}
+
+/// Returns the string states in a frame.
+fn strings(frame: Frame) -> [bool; STRINGS] {
+    core::array::from_fn(|n| frame.is_on(n))
+}
+
+#[test]
+fn last_string_is_shifted_first() {
+    let frame = Frame(0b1000_0000_0011);
+    let bits: Vec<bool> = shift_order(frame, STRINGS).collect();
+    let mut expected = [false; STRINGS];
+    expected[0] = true;
+    expected[10] = true;
+    expected[11] = true;
+    assert_eq!(bits, expected);
+}
+
+#[test]
+fn reset_latches_all_strings_off() {
+    let (mut driver, chain) = wire();
+    driver.reset();
+    assert_eq!(chain.borrow().strings(), [false; STRINGS]);
+    assert_eq!(driver.latched(), Frame::OFF);
+}
+
+#[test]
+fn each_string_lands_on_its_own_output() {
+    let (mut driver, chain) = wire();
+    driver.reset();
+    for n in 0..STRINGS {
+        driver.apply(Frame::single(n));
+        assert_eq!(
+            chain.borrow().strings(),
+            strings(Frame::single(n)),
+            "string {n}"
+        );
+    }
+}
+
+#[test]
+fn frames_span_both_registers() {
+    let (mut driver, chain) = wire();
+    for frame in [
+        Frame(0b1010_0101_1100),
+        Frame(0b0101_1010_0011),
+        Frame::all(STRINGS),
+    ] {
+        driver.apply(frame);
+        assert_eq!(chain.borrow().strings(), strings(frame));
+        assert_eq!(driver.latched(), frame);
+    }
+}
+
+#[test]
+fn outputs_change_once_after_every_bit_is_shifted() {
+    let (mut driver, chain) = wire();
+    driver.reset();
+    chain.borrow_mut().edges.clear();
+
+    let frame = Frame(0b0110_1001_0110);
+    driver.apply(frame);
+
+    let chain = chain.borrow();
+    let (latch, shifts) = chain.edges.split_last().unwrap();
+    assert_eq!(shifts.len(), STRINGS);
+    assert!(shifts.iter().all(|edge| *edge == Edge::Shift));
+    let Edge::Latch(outputs) = latch else {
+        panic!("last edge {latch:?} is not a latch");
+    };
+    assert_eq!(outputs[..STRINGS], strings(frame));
+    assert!(!chain.srclk && !chain.rclk, "clocks left high");
+}
+
+#[test]
+fn unchanged_frame_is_not_shifted() {
+    let (mut driver, chain) = wire();
+    driver.apply(Frame::single(3));
+    chain.borrow_mut().edges.clear();
+
+    driver.apply(Frame::single(3));
+    assert_eq!(chain.borrow().edges, []);
+}
+
+#[test]
+fn bits_beyond_the_last_string_are_ignored() {
+    let (mut driver, chain) = wire();
+    driver.apply(Frame(u32::MAX));
+    assert_eq!(driver.latched(), Frame::all(STRINGS));
+    assert_eq!(chain.borrow().strings(), [true; STRINGS]);
+
+    // Already latched once masked, so nothing to shift
+    chain.borrow_mut().edges.clear();
+    driver.apply(Frame::all(STRINGS));
+    assert_eq!(chain.borrow().edges, []);
+}
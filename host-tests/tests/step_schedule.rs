@@ -0,0 +1,87 @@
+//! Step schedule tests over virtual time.
+
+use embassy_time::{Duration, Instant, TICK_HZ};
+use host_tests::step_schedule::StepSchedule;
+
+/// Milliseconds in a day.
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// Time spent showing each step before the schedule is advanced.
+const LATCH_TIME: Duration = Duration::from_millis(3);
+
+/// Runs `steps` steps of `step_ms`, each taking [`LATCH_TIME`] to show,
+/// from `start`.
+///
+/// Returns the deadline of the step after the last one.
+fn run(start: Instant, step_ms: u32, steps: u64) -> Instant {
+    let mut schedule = StepSchedule::starting_at(start);
+    for _ in 0..steps {
+        let now = schedule.deadline() + LATCH_TIME;
+        schedule.advance(step_ms, now);
+    }
+    schedule.deadline()
+}
+
+/// Returns how far `at` is from `start` plus exactly `ms`, in ticks.
+fn error_ticks(start: Instant, ms: u64, at: Instant) -> f64 {
+    let ideal = start.as_ticks() as f64 + ms as f64 * TICK_HZ as f64 / 1000.0;
+    at.as_ticks() as f64 - ideal
+}
+
+#[test]
+fn ticks_are_the_firmware_rate() {
+    assert_eq!(TICK_HZ, 32_768);
+}
+
+#[test]
+fn a_day_of_steps_does_not_drift() {
+    let start = Instant::from_ticks(12_345);
+    // The string, RGB, Morse and a follower's measured step durations;
+    // none but the first is a whole number of ticks
+    for step_ms in [1000, 50, 120, 333, 1017] {
+        let steps = DAY_MS / u64::from(step_ms);
+        let end = run(start, step_ms, steps);
+        let error = error_ticks(start, steps * u64::from(step_ms), end);
+        assert!(
+            (0.0..1.0).contains(&error),
+            "{step_ms} ms steps drifted {error} ticks in a day"
+        );
+    }
+}
+
+#[test]
+fn waiting_a_step_after_each_step_would_drift() {
+    // What the schedule avoids: every step rounded to ticks, plus the time
+    // spent showing it
+    let start = Instant::from_ticks(0);
+    let mut at = start;
+    for _ in 0..DAY_MS / 333 {
+        at += LATCH_TIME + Duration::from_millis(333);
+    }
+    let drift = error_ticks(start, DAY_MS / 333 * 333, at) / TICK_HZ as f64;
+    assert!(drift > 60.0, "only {drift} s");
+}
+
+#[test]
+fn steps_follow_their_own_durations() {
+    let start = Instant::from_ticks(0);
+    let mut schedule = StepSchedule::starting_at(start);
+    for (step_ms, total_ms) in [(100, 100), (300, 400), (100, 500), (700, 1200)] {
+        schedule.advance(step_ms, schedule.deadline());
+        assert_eq!(schedule.deadline(), start + Duration::from_millis(total_ms));
+    }
+}
+
+#[test]
+fn overdue_schedule_restarts_instead_of_bursting() {
+    let start = Instant::from_ticks(0);
+    let mut schedule = StepSchedule::starting_at(start);
+
+    // An overlay held the task up for 2.5 steps
+    let late = start + Duration::from_millis(2500);
+    schedule.advance(1000, late);
+    assert_eq!(schedule.deadline(), late);
+
+    schedule.advance(1000, late + LATCH_TIME);
+    assert_eq!(schedule.deadline(), late + Duration::from_millis(1000));
+}
@@ -0,0 +1,340 @@
+//! Sync framing tests and a phase-lock simulation of several ornaments.
+
+use std::collections::BTreeMap;
+
+use embassy_time::Duration;
+use host_tests::pattern::Pattern;
+use host_tests::sync_protocol::{
+    DATA_BITS, FrameDecoder, GAP_MS, MAX_PERIOD_ERROR_PPM, PhaseLock, SyncFrame, pulses,
+};
+
+/// Nominal step period of leader and followers, in µs.
+const STEP_US: u64 = 1_000_000;
+
+const PATTERNS: [Pattern; 6] = [
+    Pattern::Alternate,
+    Pattern::Swap,
+    Pattern::Together,
+    Pattern::Twinkle,
+    Pattern::Message,
+    Pattern::Show,
+];
+
+/// Returns the (start, end) times in µs of a frame's pulses sent from
+/// `start_us`, with every pulse and gap stretched by `scale`.
+fn transmit(frame: SyncFrame, start_us: u64, scale: f64) -> Vec<(u64, u64)> {
+    let mut at = start_us;
+    pulses(frame)
+        .map(|width| {
+            let end = at + (width as f64 * 1000.0 * scale) as u64;
+            let pulse = (at, end);
+            at = end + (GAP_MS as f64 * 1000.0 * scale) as u64;
+            pulse
+        })
+        .collect()
+}
+
+/// Feeds pulses to a new decoder.
+///
+/// Returns every frame decoded and the time its start pulse began.
+fn receive(pulses: &[(u64, u64)]) -> Vec<(SyncFrame, u64)> {
+    let mut decoder = FrameDecoder::new();
+    pulses
+        .iter()
+        .filter_map(|&(start, end)| decoder.pulse(start, end))
+        .collect()
+}
+
+#[test]
+fn every_frame_round_trips_through_its_bits() {
+    for pattern in PATTERNS {
+        for step in 0..=255 {
+            let frame = SyncFrame { pattern, step };
+            assert_eq!(SyncFrame::from_bits(frame.to_bits()), Some(frame));
+            assert!(frame.to_bits() < 1 << DATA_BITS);
+        }
+    }
+}
+
+#[test]
+fn any_single_bit_error_is_rejected() {
+    let frame = SyncFrame {
+        pattern: Pattern::Together,
+        step: 0x5a,
+    };
+    for bit in 0..DATA_BITS {
+        assert_eq!(SyncFrame::from_bits(frame.to_bits() ^ (1 << bit)), None);
+    }
+}
+
+#[test]
+fn unknown_patterns_are_rejected() {
+    // Pattern codes 6 and 7, step 0, with even parity
+    assert_eq!(SyncFrame::from_bits(0b1100_0000_0000), None);
+    assert_eq!(SyncFrame::from_bits(0b1110_0000_0001), None);
+}
+
+#[test]
+fn pulses_match_golden() {
+    let frame = SyncFrame {
+        pattern: Pattern::Swap,
+        step: 0x81,
+    };
+    // Start, pattern 001, step 1000_0001, odd data so parity 1
+    assert_eq!(
+        pulses(frame).collect::<Vec<_>>(),
+        [8, 2, 2, 4, 4, 2, 2, 2, 2, 2, 2, 4, 4]
+    );
+}
+
+#[test]
+fn frames_last_at_most_82_ms() {
+    for pattern in PATTERNS {
+        for step in 0..=255 {
+            let length: u64 = pulses(SyncFrame { pattern, step })
+                .map(|width| width + GAP_MS)
+                .sum();
+            assert!(length <= 82, "{pattern:?} step {step}: {length} ms");
+        }
+    }
+}
+
+#[test]
+fn frames_decode_within_timing_tolerance() {
+    let frame = SyncFrame {
+        pattern: Pattern::Twinkle,
+        step: 200,
+    };
+    for scale in [0.8, 1.0, 1.2] {
+        assert_eq!(
+            receive(&transmit(frame, 5_000, scale)),
+            [(frame, 5_000)],
+            "scale {scale}"
+        );
+    }
+}
+
+#[test]
+fn new_start_pulse_restarts_the_frame() {
+    let frame = SyncFrame {
+        pattern: Pattern::Alternate,
+        step: 3,
+    };
+    let mut sent = transmit(frame, 0, 1.0)[..6].to_vec();
+    sent.extend(transmit(frame, 40_000, 1.0));
+    assert_eq!(receive(&sent), [(frame, 40_000)]);
+}
+
+#[test]
+fn damaged_frames_are_discarded() {
+    let frame = SyncFrame {
+        pattern: Pattern::Show,
+        step: 77,
+    };
+    let sent = transmit(frame, 0, 1.0);
+
+    // A glitch between two data pulses
+    let mut glitched = sent.clone();
+    let (_, end) = glitched[4];
+    glitched.insert(5, (end + 500, end + 700));
+    assert_eq!(receive(&glitched), []);
+
+    // A pulse too long to be a start pulse, like a lit LED seen by an
+    // optical follower
+    let mut lit = sent.clone();
+    lit[6].1 = lit[6].0 + 20_000;
+    assert_eq!(receive(&lit), []);
+
+    // A missed pulse leaves an overlong gap
+    let mut missed = sent.clone();
+    missed.remove(7);
+    assert_eq!(receive(&missed), []);
+
+    // A data pulse read as the other bit fails parity
+    let mut flipped = sent;
+    let (start, end) = flipped[9];
+    let width = if end - start > 3_000 { 2_000 } else { 4_000 };
+    flipped[9].1 = start + width;
+    assert_eq!(receive(&flipped), []);
+}
+
+/// Clock of a virtual ornament, running `ppm` parts per million fast.
+#[derive(Clone, Copy)]
+struct Clock {
+    ppm: f64,
+}
+
+impl Clock {
+    /// Reads the clock in µs at a real time, in 32.768 kHz ticks like
+    /// `Instant::now().as_micros()`.
+    fn now_us(self, real_us: f64) -> u64 {
+        let ticks = (real_us * (1.0 + self.ppm / 1e6) * 32_768.0 / 1e6) as u64;
+        ticks * 1_000_000 / 32_768
+    }
+
+    /// Returns the real time in µs at which the clock reads `local_us`.
+    fn real_us(self, local_us: u64) -> f64 {
+        local_us as f64 / (1.0 + self.ppm / 1e6)
+    }
+}
+
+/// A leader step and the frame broadcast for it.
+struct Broadcast {
+    /// Leader step number
+    step: u64,
+    /// Real time of the step, in µs
+    at: f64,
+    /// Real (start, end) times of the frame's pulses, in µs
+    pulses: Vec<(f64, f64)>,
+}
+
+/// Simulates a leader stepping `steps` times, and broadcasting a frame at
+/// each step.
+///
+/// # Arguments
+///
+/// * `clock` - Leader's clock
+/// * `steps` - Number of steps
+/// * `restart` - Step at which the leader restarts its step timing, and
+///   how much later than due that step comes, in µs
+fn lead(clock: Clock, steps: u64, restart: Option<(u64, u64)>) -> Vec<Broadcast> {
+    (1..=steps)
+        .map(|step| {
+            let shift = restart
+                .filter(|&(at, _)| step >= at)
+                .map_or(0, |(_, by)| by);
+            let local = step * STEP_US + shift;
+            let frame = SyncFrame {
+                pattern: Pattern::Swap,
+                step: step as u8,
+            };
+            Broadcast {
+                step,
+                at: clock.real_us(local),
+                pulses: transmit(frame, local, 1.0)
+                    .into_iter()
+                    .map(|(start, end)| (clock.real_us(start), clock.real_us(end)))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// A follower's steps, by leader step number, at the real times they were
+/// shown, and its final measured period.
+struct Followed {
+    shown: BTreeMap<u64, f64>,
+    period: Duration,
+}
+
+impl Followed {
+    /// Returns the largest gap between this follower's and the leader's
+    /// steps from leader step `from` on, in µs.
+    fn max_error_us(&self, leader: &[Broadcast], from: u64) -> f64 {
+        leader
+            .iter()
+            .filter(|broadcast| broadcast.step >= from)
+            .filter_map(|broadcast| {
+                let shown = self.shown.get(&broadcast.step)?;
+                Some((shown - broadcast.at).abs())
+            })
+            .fold(0.0, f64::max)
+    }
+}
+
+/// Simulates a follower receiving the broadcasts it hears, stepping the
+/// way the LED task does: from the next step given by the phase lock,
+/// every period rounded to whole milliseconds, until the next frame.
+fn follow(clock: Clock, leader: &[Broadcast], hears: impl Fn(u64) -> bool) -> Followed {
+    let mut decoder = FrameDecoder::new();
+    let mut lock = PhaseLock::new(Duration::from_micros(STEP_US));
+    let mut shown = BTreeMap::new();
+
+    for broadcast in leader.iter().filter(|broadcast| hears(broadcast.step)) {
+        for &(start, end) in &broadcast.pulses {
+            let Some((frame, frame_start)) = decoder.pulse(clock.now_us(start), clock.now_us(end))
+            else {
+                continue;
+            };
+            assert_eq!(u64::from(frame.step), broadcast.step % 256);
+
+            let next_step = lock.lock(frame_start);
+            let step_us = (lock.period().as_micros() + 500) / 1000 * 1000;
+            shown.retain(|&step, _| step <= broadcast.step);
+            for k in 0..10 {
+                let local = next_step + k * step_us;
+                shown.insert(broadcast.step + 1 + k, clock.real_us(local));
+            }
+        }
+    }
+
+    Followed {
+        shown,
+        period: lock.period(),
+    }
+}
+
+/// Clock rates of the virtual followers, in ppm.
+const FOLLOWERS: [f64; 5] = [-12_000.0, -500.0, 0.0, 9_000.0, 15_000.0];
+
+/// Clock rate of the virtual leader, in ppm.
+const LEADER: Clock = Clock { ppm: 4_000.0 };
+
+#[test]
+fn followers_lock_onto_the_leader() {
+    let leader = lead(LEADER, 60, None);
+    for ppm in FOLLOWERS {
+        let followed = follow(Clock { ppm }, &leader, |_| true);
+        let error = followed.max_error_us(&leader, 30);
+        assert!(error < 1_000.0, "{ppm} ppm follower off by {error} µs");
+    }
+}
+
+#[test]
+fn followers_measure_the_leader_period() {
+    let leader = lead(LEADER, 60, None);
+    for ppm in FOLLOWERS {
+        let followed = follow(Clock { ppm }, &leader, |_| true);
+        let expected = STEP_US as f64 * (1.0 + ppm / 1e6) / (1.0 + LEADER.ppm / 1e6);
+        let error = (followed.period.as_micros() as f64 - expected).abs();
+        assert!(error < 50.0, "{ppm} ppm follower period off by {error} µs");
+    }
+}
+
+#[test]
+fn followers_keep_in_step_through_missed_frames() {
+    let leader = lead(LEADER, 400, None);
+    for ppm in FOLLOWERS {
+        // Hears one frame in five, and misses more now and then
+        let followed = follow(Clock { ppm }, &leader, |step| {
+            step % 5 == 0 && step % 35 != 0
+        });
+        // Each step between frames adds up to 0.5 ms from the period
+        // being rounded to whole milliseconds
+        let error = followed.max_error_us(&leader, 200);
+        assert!(error < 5_000.0, "{ppm} ppm follower off by {error} µs");
+    }
+}
+
+#[test]
+fn leader_restart_only_reanchors_followers() {
+    let leader = lead(LEADER, 80, Some((50, 300_000)));
+    for ppm in FOLLOWERS {
+        let followed = follow(Clock { ppm }, &leader, |_| true);
+        let error = followed.max_error_us(&leader, 51);
+        assert!(error < 1_000.0, "{ppm} ppm follower off by {error} µs");
+        let expected = STEP_US as f64 * (1.0 + ppm / 1e6) / (1.0 + LEADER.ppm / 1e6);
+        let period_error = (followed.period.as_micros() as f64 - expected).abs();
+        assert!(period_error < 50.0, "{ppm} ppm follower lost its period");
+    }
+}
+
+#[test]
+fn leader_out_of_tolerance_is_not_measured() {
+    let ppm = -(MAX_PERIOD_ERROR_PPM as f64) - 10_000.0;
+    let leader = lead(LEADER, 30, None);
+    let followed = follow(Clock { ppm }, &leader, |_| true);
+    assert_eq!(followed.period, Duration::from_micros(STEP_US));
+    // Still re-anchored at every frame, a step period late by the rate error
+    assert!(followed.max_error_us(&leader, 2) < 40_000.0);
+}
@@ -0,0 +1,118 @@
+//! Twinkle timing bounds and duty limit.
+
+use host_tests::prng::Xorshift32;
+use host_tests::twinkle::{MAX_DUTY_PERCENT, Twinkle, TwinkleConfig};
+
+/// LEDs twinkled in each test.
+const LEDS: usize = 8;
+
+/// Steps run per configuration and seed.
+const STEPS: usize = 20_000;
+
+/// Returns whether each LED was lit, step by step.
+fn run(config: TwinkleConfig, seed: u32) -> Vec<[bool; LEDS]> {
+    let mut rng = Xorshift32::new(seed);
+    let mut twinkle = Twinkle::<LEDS>::new(config);
+    twinkle.restart(&mut rng);
+    (0..STEPS)
+        .map(|_| {
+            let frame = twinkle.step(&mut rng);
+            core::array::from_fn(|n| frame.is_on(n))
+        })
+        .collect()
+}
+
+/// Returns the runs of one LED as (lit, length), the first and last of
+/// which may be cut short.
+fn runs(steps: &[[bool; LEDS]], led: usize) -> Vec<(bool, usize)> {
+    let mut runs: Vec<(bool, usize)> = Vec::new();
+    for step in steps {
+        match runs.last_mut() {
+            Some((lit, length)) if *lit == step[led] => *length += 1,
+            _ => runs.push((step[led], 1)),
+        }
+    }
+    runs
+}
+
+/// Configurations under test, with their on- and off-time bounds.
+const CONFIGS: [((u16, u16), (u16, u16)); 4] = [
+    ((1, 3), (3, 8)),
+    ((1, 1), (1, 1)),
+    ((2, 5), (5, 20)),
+    ((4, 4), (6, 6)),
+];
+
+#[test]
+fn on_and_off_times_stay_within_bounds() {
+    for (on, off) in CONFIGS {
+        for seed in [1, 42, 0xdead_beef] {
+            let steps = run(TwinkleConfig::new(on, off), seed);
+            for led in 0..LEDS {
+                let runs = runs(&steps, led);
+                assert!(runs.len() > 2, "{on:?} {off:?}: LED {led} never changed");
+                for &(lit, length) in &runs[1..runs.len() - 1] {
+                    let (min, max) = if lit { on } else { off };
+                    assert!(
+                        (usize::from(min)..=usize::from(max)).contains(&length),
+                        "{on:?} {off:?} seed {seed}: LED {led} {lit} for {length} steps"
+                    );
+                }
+                // The first run is no longer than a full one
+                let (first_lit, first_length) = runs[0];
+                let longest = if first_lit { on.1 } else { off.1 };
+                assert!(first_length <= usize::from(longest));
+            }
+        }
+    }
+}
+
+#[test]
+fn duty_never_exceeds_its_bound() {
+    for (on, off) in CONFIGS {
+        let config = TwinkleConfig::new(on, off);
+        let bound = config.max_duty_percent();
+        assert!(bound <= MAX_DUTY_PERCENT);
+
+        // In every window of one worst-case period, at most `max_on` steps
+        // are lit
+        let period = usize::from(on.1 + off.0);
+        for seed in [7, 1234] {
+            let steps = run(config, seed);
+            for led in 0..LEDS {
+                for window in steps.windows(period) {
+                    let lit = window.iter().filter(|step| step[led]).count();
+                    assert!(lit <= usize::from(on.1), "{on:?} {off:?}: LED {led}");
+                }
+                let lit = steps.iter().filter(|step| step[led]).count();
+                assert!(lit * 100 <= STEPS * bound as usize, "{on:?} {off:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn worst_case_duty_rounds_up() {
+    assert_eq!(TwinkleConfig::new((1, 3), (3, 8)).max_duty_percent(), 50);
+    assert_eq!(TwinkleConfig::new((1, 1), (2, 2)).max_duty_percent(), 34);
+    assert_eq!(TwinkleConfig::new((1, 1), (1, 1)).max_duty_percent(), 50);
+}
+
+#[test]
+#[should_panic(expected = "twinkle duty above MAX_DUTY_PERCENT")]
+fn duty_above_the_limit_is_refused() {
+    TwinkleConfig::new((1, 4), (3, 8));
+}
+
+#[test]
+#[should_panic(expected = "invalid twinkle on-time bounds")]
+fn reversed_bounds_are_refused() {
+    TwinkleConfig::new((3, 1), (3, 8));
+}
+
+#[test]
+fn same_seed_twinkles_alike() {
+    let config = TwinkleConfig::new((1, 3), (3, 8));
+    assert_eq!(run(config, 99), run(config, 99));
+    assert_ne!(run(config, 99), run(config, 100));
+}
@@ -0,0 +1,104 @@
+//! WS2812 encoder tests against golden SPI buffers.
+
+use host_tests::rgb::Rgb;
+use host_tests::ws2812_encoder::{BYTES_PER_LED, encode};
+
+/// Encoded bytes of eight WS2812 0 bits (`100` each).
+const ZEROS: [u8; 3] = [0x92, 0x49, 0x24];
+
+/// Encoded bytes of eight WS2812 1 bits (`110` each).
+const ONES: [u8; 3] = [0xdb, 0x6d, 0xb6];
+
+/// Encodes colors into a buffer of exactly the encoded length.
+fn encoded(colors: &[Rgb]) -> Vec<u8> {
+    let mut out = vec![0; colors.len() * BYTES_PER_LED];
+    let len = encode(colors, &mut out);
+    assert_eq!(len, out.len());
+    out
+}
+
+/// Decodes an SPI bit stream back into colors, checking that every data
+/// bit is a high pulse followed by low.
+fn decode(stream: &[u8]) -> Vec<Rgb> {
+    let bits: Vec<bool> = stream
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |bit| byte & (1 << bit) != 0))
+        .collect();
+    let data: Vec<bool> = bits
+        .as_chunks::<3>()
+        .0
+        .iter()
+        .map(|code| match code {
+            [true, false, false] => false,
+            [true, true, false] => true,
+            _ => panic!("invalid bit code {code:?}"),
+        })
+        .collect();
+    data.as_chunks::<24>()
+        .0
+        .iter()
+        .map(|led| {
+            let byte = |n: usize| {
+                led[n * 8..][..8]
+                    .iter()
+                    .fold(0, |byte, &bit| (byte << 1) | u8::from(bit))
+            };
+            Rgb::new(byte(1), byte(0), byte(2))
+        })
+        .collect()
+}
+
+#[test]
+fn black_and_white_match_golden() {
+    assert_eq!(encoded(&[Rgb::BLACK]), [ZEROS, ZEROS, ZEROS].concat());
+    assert_eq!(
+        encoded(&[Rgb::new(255, 255, 255)]),
+        [ONES, ONES, ONES].concat()
+    );
+}
+
+#[test]
+fn channels_are_sent_green_red_blue() {
+    assert_eq!(
+        encoded(&[Rgb::new(255, 0, 0)]),
+        [ZEROS, ONES, ZEROS].concat()
+    );
+    assert_eq!(
+        encoded(&[Rgb::new(0, 255, 0)]),
+        [ONES, ZEROS, ZEROS].concat()
+    );
+    assert_eq!(
+        encoded(&[Rgb::new(0, 0, 255)]),
+        [ZEROS, ZEROS, ONES].concat()
+    );
+}
+
+#[test]
+fn bits_are_sent_most_significant_first() {
+    // Green 0x80 then 0x01: a one then seven zeros, seven zeros then a one
+    assert_eq!(encoded(&[Rgb::new(0, 0x80, 0)])[..3], [0xd2, 0x49, 0x24]);
+    assert_eq!(encoded(&[Rgb::new(0, 0x01, 0)])[..3], [0x92, 0x49, 0x26]);
+}
+
+#[test]
+fn first_led_is_sent_first() {
+    let colors = [Rgb::new(255, 0, 0), Rgb::BLACK, Rgb::new(0, 0, 255)];
+    assert_eq!(
+        encoded(&colors),
+        [ZEROS, ONES, ZEROS, ZEROS, ZEROS, ZEROS, ZEROS, ZEROS, ONES].concat()
+    );
+}
+
+#[test]
+fn any_color_round_trips() {
+    let colors: Vec<Rgb> = (0..=255)
+        .step_by(5)
+        .map(|n: u8| Rgb::new(n, n.rotate_left(3) ^ 0xa5, !n))
+        .collect();
+    assert_eq!(decode(&encoded(&colors)), colors);
+}
+
+#[test]
+fn empty_frame_encodes_nothing() {
+    assert_eq!(encode(&[], &mut []), 0);
+}
@@ -0,0 +1,127 @@
+//! Wake-up and active time accounting.
+//!
+//! The MCU should spend nearly all its time in STOP mode, waking for about
+//! a millisecond per pattern step. A regression that keeps it awake drains
+//! the coin cells in days rather than a season, so the firmware measures
+//! its own duty cycle in the field.
+//!
+//! # Measurement
+//!
+//! [`Executor`] is embassy's thread-mode executor with a meter around each
+//! pass: a wake-up starts when WFE returns (after STOP, the clocks are
+//! already restored) and ends when the executor has polled every ready task
+//! and is about to sleep again. [`ActivityMeter`] times these stretches on
+//! the time driver's 32.768 kHz tick (30.5 µs) and keeps running
+//! statistics: wake-ups, total active time and the longest stretch.
+//!
+//! The RTC sub-second register would work across STOP as well, but ticks
+//! at only 256 Hz with the default prescalers, coarser than a typical
+//! wake-up.
+//!
+//! Every return from WFE counts as a wake-up, including interrupts whose
+//! handler wakes no task, such as a time driver alarm that finds no timer
+//! due. Their stretch covers just the executor's empty pass and has no
+//! cause.
+//!
+//! # Causes
+//!
+//! Each task runs its body through [`tracked`], which times every poll.
+//! The cause of an active stretch is the [`Task`] polled longest within
+//! it; time spent in the `main` task is not attributed.
+//!
+//! The meter itself is a pure state machine fed with tick counts, in
+//! [`crate::activity_meter`].
+
+use core::cell::RefCell;
+use core::future::{Future, poll_fn};
+use core::marker::PhantomData;
+use core::pin::pin;
+
+use embassy_executor::{Spawner, raw};
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use embassy_time::Instant;
+
+use crate::activity_meter::{ActivityMeter, ActivityStats, Task};
+
+/// Pender context of a thread-mode executor: pending it is just SEV.
+const THREAD_PENDER: usize = usize::MAX;
+
+/// Meter fed by [`Executor`] and [`tracked`].
+static METER: Mutex<CriticalSectionRawMutex, RefCell<ActivityMeter>> =
+    Mutex::new(RefCell::new(ActivityMeter::new(0)));
+
+/// Feeds the meter an event stamped with the current time.
+fn record(event: impl FnOnce(&mut ActivityMeter, u64)) {
+    let now = now_ticks();
+    METER.lock(|meter| event(&mut meter.borrow_mut(), now));
+}
+
+/// Returns the wake-up statistics since boot.
+pub fn stats() -> ActivityStats {
+    let now = now_ticks();
+    METER.lock(|meter| meter.borrow().stats(now))
+}
+
+/// Runs a task body, charging the time spent polling it to `task`.
+///
+/// # Arguments
+///
+/// * `task` - Task the body belongs to
+/// * `body` - Body of the task
+pub async fn tracked<F: Future>(task: Task, body: F) -> F::Output {
+    let mut body = pin!(body);
+    poll_fn(|cx| {
+        let start = now_ticks();
+        let poll = body.as_mut().poll(cx);
+        record_poll(task, start);
+        poll
+    })
+    .await
+}
+
+/// Charges a poll that started at `start` ticks to `task`.
+#[inline(never)]
+fn record_poll(task: Task, start: u64) {
+    record(|meter, now| meter.polled(task, now - start));
+}
+
+/// Returns the current time in ticks.
+#[inline(never)]
+fn now_ticks() -> u64 {
+    Instant::now().as_ticks()
+}
+
+/// Thread-mode executor timing each wake-up.
+///
+/// Behaves like `embassy_executor::Executor`, sleeping with WFE whenever
+/// no task is ready; selected with the `executor` argument of
+/// `#[embassy_executor::main]`.
+pub struct Executor {
+    /// Executor polling the tasks
+    inner: raw::Executor,
+    /// Tasks must stay on the thread that spawned them
+    not_send: PhantomData<*mut ()>,
+}
+
+impl Executor {
+    /// Creates a new executor.
+    pub fn new() -> Self {
+        Self {
+            inner: raw::Executor::new(THREAD_PENDER as *mut ()),
+            not_send: PhantomData,
+        }
+    }
+
+    /// Spawns the initial tasks with `init`, then runs them forever.
+    pub fn run(&'static mut self, init: impl FnOnce(Spawner)) -> ! {
+        init(self.inner.spawner());
+
+        loop {
+            record(ActivityMeter::wake);
+            // Safety: only called from this loop, on the executor's thread
+            unsafe { self.inner.poll() };
+            record(ActivityMeter::sleep);
+            cortex_m::asm::wfe();
+        }
+    }
+}
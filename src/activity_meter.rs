@@ -0,0 +1,145 @@
+//! Duty cycle statistics from timed active stretches.
+//!
+//! [`ActivityMeter`] is fed the tick counts at which the MCU wakes and goes
+//! back to sleep, and how long each task was polled in between, by
+//! [`crate::activity`]. It keeps running statistics: wake-ups, total active
+//! time, and the longest stretch with the task that kept the MCU busiest
+//! in it.
+//!
+//! The meter has no hardware dependencies, so the host tests in
+//! `host-tests/tests/activity_meter.rs` play scripted wake-ups through it.
+
+use embassy_time::TICK_HZ;
+
+/// Task that can keep the MCU awake.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Task {
+    /// Battery switching, fuel gauge and statistics
+    Power,
+    /// Pattern steps and LED commands
+    Led,
+    /// Push-button gestures
+    Button,
+    /// Charlieplexed LED scanning
+    #[cfg(led_backend = "charlieplex")]
+    Charlieplex,
+    /// Pattern synchronization between ornaments
+    #[cfg(sync_link)]
+    Sync,
+}
+
+/// Running wake-up statistics since boot.
+#[derive(Clone, Copy, defmt::Format)]
+pub struct ActivityStats {
+    /// Time covered by the statistics, in ticks
+    pub elapsed_ticks: u64,
+    /// Number of wake-ups
+    pub wakes: u32,
+    /// Total active time, in ticks
+    pub active_ticks: u64,
+    /// Longest active stretch, in ticks
+    pub longest_ticks: u32,
+    /// Task polled longest during the longest stretch
+    pub longest_cause: Option<Task>,
+}
+
+impl ActivityStats {
+    /// Returns the average number of wake-ups per hour.
+    pub fn wakes_per_hour(&self) -> u32 {
+        (u64::from(self.wakes) * 3600 * TICK_HZ / self.elapsed_ticks.max(1)) as u32
+    }
+
+    /// Returns the average active time per wake-up, in microseconds.
+    pub fn average_active_us(&self) -> u32 {
+        ticks_to_us(self.active_ticks / u64::from(self.wakes.max(1)))
+    }
+
+    /// Returns the fraction of time spent active, in parts per million.
+    pub fn active_ppm(&self) -> u32 {
+        (self.active_ticks * 1_000_000 / self.elapsed_ticks.max(1)) as u32
+    }
+
+    /// Returns the longest active stretch, in microseconds.
+    pub fn longest_us(&self) -> u32 {
+        ticks_to_us(self.longest_ticks.into())
+    }
+}
+
+/// Converts a duration in ticks to microseconds.
+fn ticks_to_us(ticks: u64) -> u32 {
+    (ticks * 1_000_000 / TICK_HZ) as u32
+}
+
+/// Times active stretches and the tasks polled in them.
+pub struct ActivityMeter {
+    /// Statistics so far
+    stats: ActivityStats,
+    /// Time counting started, in ticks
+    since: u64,
+    /// Start of the current active stretch, in ticks
+    woke_at: u64,
+    /// Task polled longest in the current stretch
+    busiest: Option<Task>,
+    /// Time that task was polled, in ticks
+    busiest_ticks: u64,
+}
+
+impl ActivityMeter {
+    /// Creates a meter counting from `now`.
+    pub const fn new(now: u64) -> Self {
+        Self {
+            stats: ActivityStats {
+                elapsed_ticks: 0,
+                wakes: 0,
+                active_ticks: 0,
+                longest_ticks: 0,
+                longest_cause: None,
+            },
+            since: now,
+            woke_at: now,
+            busiest: None,
+            busiest_ticks: 0,
+        }
+    }
+
+    /// Marks the MCU waking up.
+    pub fn wake(&mut self, now: u64) {
+        self.woke_at = now;
+        self.busiest = None;
+        self.busiest_ticks = 0;
+    }
+
+    /// Records a task poll within the current stretch.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - Task polled
+    /// * `ticks` - Duration of the poll, in ticks
+    pub fn polled(&mut self, task: Task, ticks: u64) {
+        if ticks >= self.busiest_ticks {
+            self.busiest = Some(task);
+            self.busiest_ticks = ticks;
+        }
+    }
+
+    /// Marks the MCU going back to sleep.
+    pub fn sleep(&mut self, now: u64) {
+        let active = now - self.woke_at;
+        self.stats.wakes = self.stats.wakes.saturating_add(1);
+        self.stats.active_ticks += active;
+
+        let active = u32::try_from(active).unwrap_or(u32::MAX);
+        if active > self.stats.longest_ticks {
+            self.stats.longest_ticks = active;
+            self.stats.longest_cause = self.busiest;
+        }
+    }
+
+    /// Returns the statistics up to `now`.
+    pub fn stats(&self, now: u64) -> ActivityStats {
+        ActivityStats {
+            elapsed_ticks: now - self.since,
+            ..self.stats
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Lifetime battery usage statistics and cell replacement detection.
+//!
+//! The firmware cannot see a coin cell being swapped, but a fresh cell
+//! shows up as a jump in VDD compared to the last reading taken from the
+//! same slot. Since VDD can only be measured for the active battery, a cell
+//! swapped into the idle slot is detected the first time that slot becomes
+//! active again.
+//!
+//! Statistics are kept per battery slot and persisted to EEPROM together
+//! with the fuel gauge.
+
+use crate::checksum::check_word;
+use crate::power_state::PowerState;
+
+/// Minimum rise in VDD over the last reading that indicates a fresh cell, in mV.
+pub const FRESH_CELL_JUMP_MV: u16 = 250;
+
+/// Minimum VDD for a reading to be treated as a fresh cell, in mV.
+///
+/// A fresh CR2032 at ~3.0V less the BAT54J forward drop.
+pub const FRESH_CELL_MIN_MV: u16 = 2750;
+
+/// Size of the serialized statistics record in bytes.
+pub const RECORD_SIZE: usize = 28;
+
+/// Marks a valid statistics record in EEPROM ("STAT").
+const RECORD_MAGIC: u32 = 0x5354_4154;
+
+/// Lifetime statistics for one battery slot.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct SlotStats {
+    /// Total time this slot has powered the ornament, in seconds
+    pub active_secs: u32,
+    /// Number of times the firmware switched to this slot
+    pub switches: u16,
+    /// Number of cell replacements detected in this slot
+    pub replacements: u16,
+}
+
+/// Usage statistics for both battery slots.
+#[derive(Default)]
+pub struct BatteryStats {
+    /// Per-slot statistics, indexed by [`PowerState::slot`]
+    slots: [SlotStats; 2],
+    /// Last VDD reading taken from each slot in mV (0 = never measured)
+    last_vdd_mv: [u16; 2],
+    /// Active time not yet counted as a whole second, in ms
+    pending_ms: u32,
+}
+
+impl BatteryStats {
+    /// Returns the statistics for a battery slot.
+    pub fn slot(&self, cell: &PowerState) -> &SlotStats {
+        &self.slots[cell.slot()]
+    }
+
+    /// Returns the last VDD reading taken from a battery slot in mV.
+    pub fn last_vdd_mv(&self, cell: &PowerState) -> u16 {
+        self.last_vdd_mv[cell.slot()]
+    }
+
+    /// Adds active time to a battery slot.
+    pub fn record_active(&mut self, cell: &PowerState, elapsed_ms: u32) {
+        let total_ms = self.pending_ms.saturating_add(elapsed_ms);
+        let slot = &mut self.slots[cell.slot()];
+        slot.active_secs = slot.active_secs.saturating_add(total_ms / 1000);
+        self.pending_ms = total_ms % 1000;
+    }
+
+    /// Records a switch to a battery slot.
+    pub fn record_switch(&mut self, cell: &PowerState) {
+        let slot = &mut self.slots[cell.slot()];
+        slot.switches = slot.switches.saturating_add(1);
+    }
+
+    /// Records a VDD reading and checks it for a fresh cell.
+    ///
+    /// # Arguments
+    ///
+    /// * `cell` - Battery slot that was active during the reading
+    /// * `vdd_mv` - Measured VDD in millivolts
+    ///
+    /// # Returns
+    ///
+    /// True if the reading indicates the cell in this slot was replaced
+    pub fn record_vdd(&mut self, cell: &PowerState, vdd_mv: u16) -> bool {
+        let last = core::mem::replace(&mut self.last_vdd_mv[cell.slot()], vdd_mv);
+
+        let replaced = last != 0
+            && vdd_mv >= FRESH_CELL_MIN_MV
+            && vdd_mv >= last.saturating_add(FRESH_CELL_JUMP_MV);
+
+        if replaced {
+            let slot = &mut self.slots[cell.slot()];
+            slot.replacements = slot.replacements.saturating_add(1);
+        }
+
+        replaced
+    }
+
+    /// Serializes the statistics for storage in EEPROM.
+    ///
+    /// Layout (little-endian): magic, then per slot active seconds,
+    /// switches and replacements, then per slot last VDD, then check word.
+    pub fn to_record(&self) -> [u8; RECORD_SIZE] {
+        let mut record = [0u8; RECORD_SIZE];
+        record[0..4].copy_from_slice(&RECORD_MAGIC.to_le_bytes());
+        for (i, slot) in self.slots.iter().enumerate() {
+            let base = 4 + i * 8;
+            record[base..base + 4].copy_from_slice(&slot.active_secs.to_le_bytes());
+            record[base + 4..base + 6].copy_from_slice(&slot.switches.to_le_bytes());
+            record[base + 6..base + 8].copy_from_slice(&slot.replacements.to_le_bytes());
+        }
+        for (i, vdd) in self.last_vdd_mv.iter().enumerate() {
+            let base = 20 + i * 2;
+            record[base..base + 2].copy_from_slice(&vdd.to_le_bytes());
+        }
+        let check = check_word(RECORD_MAGIC, &record[..24]);
+        record[24..28].copy_from_slice(&check.to_le_bytes());
+        record
+    }
+
+    /// Restores statistics from an EEPROM record.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the record is blank or corrupted
+    pub fn from_record(record: &[u8; RECORD_SIZE]) -> Option<Self> {
+        let u16_at = |i: usize| u16::from_le_bytes([record[i], record[i + 1]]);
+        let u32_at =
+            |i: usize| u32::from_le_bytes([record[i], record[i + 1], record[i + 2], record[i + 3]]);
+
+        if u32_at(0) != RECORD_MAGIC || u32_at(24) != check_word(RECORD_MAGIC, &record[..24]) {
+            return None;
+        }
+
+        let slot_at = |base: usize| SlotStats {
+            active_secs: u32_at(base),
+            switches: u16_at(base + 4),
+            replacements: u16_at(base + 6),
+        };
+
+        Some(Self {
+            slots: [slot_at(4), slot_at(12)],
+            last_vdd_mv: [u16_at(20), u16_at(22)],
+            pending_ms: 0,
+        })
+    }
+}
@@ -0,0 +1,83 @@
+//! User push-button input.
+//!
+//! On boards with a `[button]` section a single push-button shorts its pin
+//! to GND when pressed (internal pull-up). It is read through an EXTI line,
+//! so a press wakes the MCU from STOP mode and the button costs no power
+//! while idle.
+//!
+//! # Gestures
+//!
+//! | Gesture   | Input                                  | Action                   |
+//! |-----------|----------------------------------------|--------------------------|
+//! | Short     | Press < 1.5s, no second press in 400ms | Next pattern             |
+//! | Double    | Two short presses within 400ms         | Previous pattern         |
+//! | Long      | Hold 1.5s - 5s                         | Turn LEDs off / on       |
+//! | Very long | Hold >= 5s                             | Enter diagnostics        |
+//!
+//! Gestures are decoded by [`GestureDecoder`]. Next and previous pattern
+//! and turning the LEDs off or on are sent as commands relative to what the
+//! LED task shows, so they follow pattern changes made by a sync leader or
+//! the power task. The LED task records them in the event log (see
+//! [`crate::event_log`]).
+
+use embassy_futures::select::select;
+use embassy_stm32::exti::ExtiInput;
+use embassy_time::{Instant, Timer};
+
+use crate::activity;
+use crate::activity_meter::Task;
+use crate::diagnostics;
+use crate::gesture::{Gesture, GestureDecoder};
+use crate::string_controller::{LED_COMMANDS, LedCommand};
+
+/// Async task for decoding button gestures and acting on them.
+///
+/// Sleeps on the button's EXTI line, waking only for edges and for the
+/// debounce, double press and long press timeouts that follow them. Gestures are
+/// turned into [`LedCommand`]s and diagnostic requests.
+///
+/// # Arguments
+///
+/// * `button` - EXTI input for the push-button, active low (takes ownership)
+#[embassy_executor::task]
+pub async fn button_task(button: ExtiInput<'static>) {
+    activity::tracked(Task::Button, run_button(button)).await
+}
+
+/// Body of [`button_task`].
+async fn run_button(mut button: ExtiInput<'static>) {
+    #[cfg(feature = "debug-mode")]
+    defmt::info!("Button task started");
+
+    let mut decoder = GestureDecoder::new();
+
+    loop {
+        match decoder.deadline() {
+            Some(deadline_ms) => {
+                // An edge or the deadline: either way the decoder needs an update
+                let deadline = Timer::at(Instant::from_millis(deadline_ms));
+                select(button.wait_for_any_edge(), deadline).await;
+            }
+            None => button.wait_for_any_edge().await,
+        }
+
+        let Some(gesture) = decoder.update(button.is_low(), Instant::now().as_millis()) else {
+            continue;
+        };
+
+        #[cfg(feature = "debug-mode")]
+        defmt::info!("Button gesture: {}", gesture);
+
+        let command = match gesture {
+            Gesture::Short => LedCommand::NextPattern,
+            Gesture::Double => LedCommand::PreviousPattern,
+            Gesture::Long => LedCommand::ToggleLeds,
+            Gesture::VeryLong => {
+                diagnostics::request_report();
+                LedCommand::SelfTest
+            }
+        };
+
+        LED_COMMANDS.send(command).await;
+    }
+}
@@ -0,0 +1,152 @@
+//! Charlieplexed LED backend.
+//!
+//! Drives up to `P × (P - 1)` LEDs directly from `P` MCU pins, for the
+//! multi-LED star topper. There are no latches, so unlike the flip-flop and
+//! shift-register backends the LEDs must be scanned: [`charlieplex_task`]
+//! lights one LED at a time, cycling through the lit LEDs of the current
+//! frame fast enough to look steady. The pattern frontend is the same
+//! [`StringController`](crate::string_controller::StringController) the
+//! flip-flop ornament uses, with [`Charlieplex`] as its [`LedBackend`].
+//!
+//! # Wiring
+//!
+//! Every ordered pair of pins has one LED between them, anode on the first
+//! pin and cathode on the second. LED `k` uses anode pin `k / (P - 1)` and
+//! the `k % (P - 1)`th of the remaining pins as cathode (see
+//! [`led_pins`](crate::charlieplex_scan::led_pins)).
+//! A slot drives exactly two pins (anode high, cathode low) and leaves the
+//! others floating, so exactly one LED is lit per scan slot.
+//!
+//! # Scan Scheduling
+//!
+//! The MCU sleeps in STOP mode between slots with the current LED still
+//! driven, so each slot costs one wake-up. [`slot_duration`] keeps the
+//! wake-up rate within
+//! [`SCAN_WAKE_BUDGET_HZ`](crate::charlieplex_scan::SCAN_WAKE_BUDGET_HZ):
+//! with few LEDs lit they are refreshed at
+//! [`TARGET_REFRESH_HZ`](crate::charlieplex_scan::TARGET_REFRESH_HZ), with
+//! many lit the refresh rate drops rather than the wake-up rate rising.
+//! Only lit LEDs get a slot, and with every LED off the pins float and the
+//! scan task sleeps until the next frame.
+//!
+//! Only one LED draws current at any time, so the LED load reported to the
+//! fuel gauge is at most one string.
+
+use embassy_futures::select::{Either, select};
+use embassy_stm32::gpio::{Flex, Speed};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::Timer;
+
+use crate::activity;
+use crate::activity_meter::Task;
+use crate::charlieplex_scan::{PinState, next_lit, pin_table, slot_duration};
+use crate::hardware::{CHARLIEPLEX_PINS, STRING_COUNT};
+use crate::led_backend::{Frame, LedBackend};
+
+/// Pin states of every LED on the board.
+const PIN_TABLE: [[PinState; CHARLIEPLEX_PINS]; STRING_COUNT] = pin_table();
+
+/// Latest frame for [`charlieplex_task`] to scan.
+static SCAN_FRAME: Signal<CriticalSectionRawMutex, Frame> = Signal::new();
+
+/// Charlieplex backend handle for the pattern frontend.
+///
+/// Applying a frame hands it to [`charlieplex_task`], which owns the pins.
+pub struct Charlieplex {
+    /// Frame being scanned
+    frame: Frame,
+}
+
+impl Charlieplex {
+    /// Creates a charlieplex backend with every LED off.
+    pub const fn new() -> Self {
+        Self { frame: Frame::OFF }
+    }
+}
+
+impl LedBackend for Charlieplex {
+    const STRINGS: usize = STRING_COUNT;
+
+    fn reset(&mut self) {
+        self.frame = Frame::OFF;
+        SCAN_FRAME.signal(Frame::OFF);
+    }
+
+    fn latched(&self) -> Frame {
+        self.frame
+    }
+
+    fn apply(&mut self, frame: Frame) {
+        let frame = Frame(frame.0 & Frame::all(STRING_COUNT).0);
+        if frame != self.frame {
+            self.frame = frame;
+            SCAN_FRAME.signal(frame);
+        }
+    }
+
+    /// LEDs are lit one at a time, so at most one draws current.
+    fn lit_strings(&self) -> u32 {
+        self.frame.0.count_ones().min(1)
+    }
+}
+
+/// Drives the pins for one scan slot.
+fn drive(pins: &mut [Flex<'static>; CHARLIEPLEX_PINS], states: &[PinState; CHARLIEPLEX_PINS]) {
+    // Float every pin first so two LEDs are never lit during the change
+    for pin in pins.iter_mut() {
+        pin.set_as_analog();
+    }
+
+    for (pin, state) in pins.iter_mut().zip(states) {
+        match state {
+            PinState::High => {
+                pin.set_high();
+                pin.set_as_output(Speed::Low);
+            }
+            PinState::Low => {
+                pin.set_low();
+                pin.set_as_output(Speed::Low);
+            }
+            PinState::Floating => {}
+        }
+    }
+}
+
+/// Async task that scans the charlieplexed LEDs.
+///
+/// Lights each LED of the latest frame in turn for [`slot_duration`], and
+/// floats every pin and waits for the next frame while all LEDs are off.
+///
+/// # Arguments
+///
+/// * `pins` - Charlieplex pins in board description order (takes ownership)
+#[embassy_executor::task]
+pub async fn charlieplex_task(pins: [Flex<'static>; CHARLIEPLEX_PINS]) {
+    activity::tracked(Task::Charlieplex, run_charlieplex(pins)).await
+}
+
+/// Body of [`charlieplex_task`].
+async fn run_charlieplex(mut pins: [Flex<'static>; CHARLIEPLEX_PINS]) {
+    #[cfg(feature = "debug-mode")]
+    defmt::info!("Charlieplex task started");
+
+    let mut frame = Frame::OFF;
+    let mut led = 0;
+
+    loop {
+        if frame == Frame::OFF {
+            drive(&mut pins, &[PinState::Floating; CHARLIEPLEX_PINS]);
+            frame = SCAN_FRAME.wait().await;
+            continue;
+        }
+
+        led = next_lit(frame, led, STRING_COUNT);
+        drive(&mut pins, &PIN_TABLE[led]);
+        led = (led + 1) % STRING_COUNT;
+
+        let slot = Timer::after(slot_duration(frame.0.count_ones()));
+        if let Either::Second(new_frame) = select(slot, SCAN_FRAME.wait()).await {
+            frame = new_frame;
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Charlieplex pin-state tables and scan scheduling.
+//!
+//! The pure half of the charlieplex backend (see [`crate::charlieplex`]):
+//! which pins light which LED, which LED each scan slot lights and how long
+//! a slot lasts. It has no hardware dependencies, so the host tests in
+//! `host-tests/tests/charlieplex_scan.rs` check that every slot lights
+//! exactly one LED.
+
+use embassy_time::Duration;
+
+use crate::led_backend::Frame;
+
+/// Maximum scan slots per second, bounding STOP-mode wake-ups.
+pub const SCAN_WAKE_BUDGET_HZ: u32 = 1000;
+
+/// Refresh rate aimed for when the wake-up budget allows it.
+pub const TARGET_REFRESH_HZ: u32 = 100;
+
+/// State of one charlieplex pin during a scan slot.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum PinState {
+    /// Driven high (LED anode)
+    High,
+    /// Driven low (LED cathode)
+    Low,
+    /// Floating, so no current flows through the pin
+    Floating,
+}
+
+/// Returns the (anode, cathode) pins of LED `led` on `pins` pins.
+pub const fn led_pins(led: usize, pins: usize) -> (usize, usize) {
+    let anode = led / (pins - 1);
+    let mut cathode = led % (pins - 1);
+    if cathode >= anode {
+        cathode += 1;
+    }
+    (anode, cathode)
+}
+
+/// Generates the pin-state table for `L` LEDs on `P` pins.
+///
+/// Entry `k` gives the state of every pin while LED `k` is lit: its anode
+/// high, its cathode low and every other pin floating.
+pub const fn pin_table<const P: usize, const L: usize>() -> [[PinState; P]; L] {
+    assert!(P >= 2 && L <= P * (P - 1), "too many LEDs for the pins");
+
+    let mut table = [[PinState::Floating; P]; L];
+    let mut led = 0;
+    while led < L {
+        let (anode, cathode) = led_pins(led, P);
+        table[led][anode] = PinState::High;
+        table[led][cathode] = PinState::Low;
+        led += 1;
+    }
+    table
+}
+
+/// Returns how long each scan slot lasts with `lit` LEDs lit.
+///
+/// Aims for [`TARGET_REFRESH_HZ`] but never wakes more often than
+/// [`SCAN_WAKE_BUDGET_HZ`].
+pub fn slot_duration(lit: u32) -> Duration {
+    let slots_per_second = (lit.max(1) * TARGET_REFRESH_HZ).min(SCAN_WAKE_BUDGET_HZ);
+    Duration::from_micros(1_000_000 / u64::from(slots_per_second))
+}
+
+/// Returns the next lit LED at or after `led`, wrapping around.
+///
+/// # Arguments
+///
+/// * `frame` - Frame being scanned, with at least one LED lit
+/// * `led` - LED to start looking from
+/// * `leds` - Number of LEDs on the board
+pub fn next_lit(frame: Frame, mut led: usize, leds: usize) -> usize {
+    while !frame.is_on(led) {
+        led = (led + 1) % leds;
+    }
+    led
+}
@@ -0,0 +1,46 @@
+//! Check words protecting records persisted to EEPROM.
+//!
+//! Records written as one block carry a check word computed over their
+//! bytes, seeded with the record's magic so a record of one kind never
+//! validates as another. It catches blank EEPROM, torn writes and bit rot,
+//! not deliberate tampering.
+//!
+//! Small records appended to a log or store are not made of whole words,
+//! so they carry a [`crc16`] instead, seeded per log or store page.
+
+/// Computes the check word over a record's bytes.
+///
+/// Bytes past the last whole 32-bit word are ignored, so records keep
+/// their checked part word-aligned.
+///
+/// # Arguments
+///
+/// * `magic` - Magic marking the record kind
+/// * `bytes` - Record bytes covered by the check word
+pub fn check_word(magic: u32, bytes: &[u8]) -> u32 {
+    let (words, _) = bytes.as_chunks::<4>();
+    words.iter().fold(!magic, |acc, word| {
+        (acc ^ u32::from_le_bytes(*word)).rotate_left(5)
+    })
+}
+
+/// Computes the CRC-16 (polynomial 0x1021) of `bytes`.
+///
+/// CRCs can be chained: the CRC of two byte strings is the CRC of the
+/// second, with the CRC of the first as initial value.
+///
+/// # Arguments
+///
+/// * `init` - Initial value
+/// * `bytes` - Bytes covered by the CRC
+pub fn crc16(init: u16, bytes: &[u8]) -> u16 {
+    bytes.iter().fold(init, |crc, &byte| {
+        (0..8).fold(crc ^ (u16::from(byte) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
@@ -0,0 +1,117 @@
+//! Adaptive system clock policy.
+//!
+//! The MCU normally runs MSI at 65.536 kHz, which is plenty to clock the
+//! LED latches and step patterns. Bursty work (ADC sampling, EEPROM writes,
+//! UART bursts) spends most of its time executing code rather than waiting,
+//! so it finishes sooner and costs less charge at a faster clock. Such work
+//! holds a [`ClockBoost`] from [`boost`]: MSI is raised to [`BOOST_RANGE`]
+//! while at least one boost is held and drops back to 65.536 kHz when the
+//! last one is dropped.
+//!
+//! # Dependent Peripherals
+//!
+//! Switching the clock under running peripherals would change their
+//! timing, so each switch also reconfigures them:
+//!
+//! - **Time driver**: embassy-time counts on TIM22, on APB2, with a
+//!   prescaler set at boot. While boosted APB2 is divided by 16; divided
+//!   APB clocks are doubled for the timers, so TIM22 keeps counting at
+//!   65.536 kHz and timers and [`Instant`](embassy_time::Instant)s stay
+//!   exact. Each switch costs at most a fraction of a tick.
+//! - **ADC**: clocked from PCLK2 / 2, so [`crate::vdd_monitor`] selects
+//!   PCLK2 itself while boosted (see [`is_boosted`]) to keep its rate.
+//! - **APB1** (USART2, LPUART1, I2C1) runs 8 times faster while boosted.
+//!   None is in use; a UART burst must set its baud rate for the
+//!   boosted 524.288 kHz clock.
+//!
+//! Drivers that read the frozen clock table of `embassy_stm32::rcc` still
+//! see the boot clock.
+//!
+//! # Availability
+//!
+//! Boosting needs the 65.536 kHz boot clock. `debug-mode` builds (2.097
+//! MHz) and RGB boards (4.194 MHz) already run fast, and there [`boost`]
+//! returns a guard that changes nothing.
+
+use core::cell::Cell;
+
+use embassy_stm32::pac::RCC;
+use embassy_stm32::rcc::{APBPrescaler, MSIRange};
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+
+/// MSI range while boosted.
+pub const BOOST_RANGE: MSIRange = MSIRange::RANGE524K;
+
+/// MSI range at boot and between boosts.
+const IDLE_RANGE: MSIRange = MSIRange::RANGE66K;
+
+/// APB2 prescaler while boosted, keeping TIM22 at its boot rate.
+const BOOST_APB2: APBPrescaler = APBPrescaler::DIV16;
+
+/// True if the boot clock is the idle range boosts switch from.
+const BOOST_AVAILABLE: bool = cfg!(not(any(feature = "debug-mode", led_backend = "rgb")));
+
+/// Number of [`ClockBoost`]s held.
+static HOLDERS: Mutex<CriticalSectionRawMutex, Cell<u8>> = Mutex::new(Cell::new(0));
+
+/// Keeps the system clock raised while held.
+///
+/// Dropping the last boost returns to the idle clock.
+pub struct ClockBoost(());
+
+/// Raises the system clock until the returned guard is dropped.
+///
+/// Boosts nest: the clock stays raised until every holder has dropped its
+/// guard.
+///
+/// # Example
+///
+/// ```no_run
+/// let _boost = clock::boost();
+/// eeprom.write(offset, &record)?;
+/// ```
+pub fn boost() -> ClockBoost {
+    HOLDERS.lock(|holders| {
+        if holders.get() == 0 && BOOST_AVAILABLE {
+            raise();
+        }
+        holders.set(holders.get() + 1);
+    });
+    ClockBoost(())
+}
+
+/// Returns true while the system clock is raised.
+pub fn is_boosted() -> bool {
+    BOOST_AVAILABLE && HOLDERS.lock(|holders| holders.get() > 0)
+}
+
+impl Drop for ClockBoost {
+    fn drop(&mut self) {
+        HOLDERS.lock(|holders| {
+            holders.set(holders.get() - 1);
+            if holders.get() == 0 && BOOST_AVAILABLE {
+                lower();
+            }
+        });
+    }
+}
+
+/// Switches to the boosted clock.
+///
+/// APB2 is divided first, so TIM22 runs slow rather than fast for the few
+/// cycles between the two writes.
+fn raise() {
+    while !RCC.cr().read().msirdy() {}
+    RCC.cfgr().modify(|w| w.set_ppre2(BOOST_APB2));
+    RCC.icscr().modify(|w| w.set_msirange(BOOST_RANGE));
+}
+
+/// Switches back to the idle clock.
+///
+/// MSI is slowed first, so TIM22 runs slow rather than fast for the few
+/// cycles between the two writes.
+fn lower() {
+    while !RCC.cr().read().msirdy() {}
+    RCC.icscr().modify(|w| w.set_msirange(IDLE_RANGE));
+    RCC.cfgr().modify(|w| w.set_ppre2(APBPrescaler::DIV1));
+}
@@ -0,0 +1,139 @@
+//! Cue sheet compiler for light shows.
+//!
+//! Turns a CSV cue sheet (see `shows/demo.csv`) into the binary light
+//! sequence that `src/show.rs` plays. Each line is
+//! `time, strings`: the time as `[minutes:]seconds[.fraction]` and the
+//! strings lit from then on, by name or index separated by spaces, `all`
+//! or `-` for none. A final `time, end` line sets the show's length, and
+//! `#` starts a comment.
+//!
+//! This module is not part of the firmware: `build.rs` includes it to
+//! compile the cue sheet, and the host tests include it to test the
+//! compiler. It needs `std`.
+
+/// Magic and format version starting a light sequence (`show::HEADER`).
+pub const HEADER: [u8; 3] = *b"LS\x01";
+
+/// Largest light sequence compiled, in bytes, so an overlong cue sheet is
+/// reported here rather than as a flash overflow at link time.
+pub const MAX_SEQUENCE_LEN: usize = 4096;
+
+/// Compiles a cue sheet into a light sequence.
+///
+/// A cue turning every string off is added at time 0 if the sheet starts
+/// later, and the `end` line becomes a final all-off cue. Gaps too long
+/// for one cue's delay are bridged by repeating the previous cue.
+///
+/// # Arguments
+///
+/// * `text` - Cue sheet
+/// * `names` - Names of the board's strings, in order
+///
+/// # Returns
+///
+/// The light sequence, or a message naming the offending line
+pub fn compile_show(text: &str, names: &[&str]) -> Result<Vec<u8>, String> {
+    let mut cues: Vec<(u64, u32)> = Vec::new();
+    let mut end = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let line_no = index + 1;
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        if end.is_some() {
+            return Err(format!("line {line_no}: cue after `end`"));
+        }
+
+        let (time, strings) = line
+            .split_once(',')
+            .ok_or_else(|| format!("line {line_no}: expected `time, strings`"))?;
+        let time = parse_time(time.trim())
+            .ok_or_else(|| format!("line {line_no}: invalid time `{}`", time.trim()))?;
+        if cues.last().is_some_and(|&(last, _)| time <= last) {
+            return Err(format!("line {line_no}: cue times must increase"));
+        }
+
+        match strings.trim() {
+            "end" => end = Some(time),
+            strings => {
+                let mask =
+                    parse_strings(strings, names).map_err(|e| format!("line {line_no}: {e}"))?;
+                cues.push((time, mask));
+            }
+        }
+    }
+
+    let end = end.ok_or("missing `time, end` line")?;
+    if end == 0 {
+        return Err("the show must end after time 0".to_string());
+    }
+    if cues.first().is_none_or(|&(time, _)| time > 0) {
+        cues.insert(0, (0, 0));
+    }
+    cues.push((end, 0));
+
+    let width = names.len().div_ceil(8);
+    let mut sequence = HEADER.to_vec();
+    sequence.push(width as u8);
+
+    let mut push = |delay: u64, mask: u32| {
+        sequence.extend_from_slice(&(delay as u16).to_le_bytes());
+        sequence.extend_from_slice(&mask.to_le_bytes()[..width]);
+    };
+    let (mut last_time, mut last_mask) = (0, 0);
+    for (time, mask) in cues {
+        let mut delay = time - last_time;
+        while delay > u64::from(u16::MAX) {
+            push(u64::from(u16::MAX), last_mask);
+            delay -= u64::from(u16::MAX);
+        }
+        push(delay, mask);
+        (last_time, last_mask) = (time, mask);
+    }
+
+    if sequence.len() > MAX_SEQUENCE_LEN {
+        return Err(format!(
+            "the show compiles to {} bytes, more than the {MAX_SEQUENCE_LEN} allowed",
+            sequence.len()
+        ));
+    }
+    Ok(sequence)
+}
+
+/// Parses a cue time, `[minutes:]seconds[.fraction]`, into milliseconds.
+pub fn parse_time(time: &str) -> Option<u64> {
+    let (minutes, seconds) = match time.split_once(':') {
+        Some((minutes, seconds)) => (minutes.parse::<u64>().ok()?, seconds),
+        None => (0, time),
+    };
+    let (whole, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    if fraction.len() > 3 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let whole: u64 = whole.parse().ok()?;
+    let millis: u64 = format!("{fraction:0<3}").parse().ok()?;
+    Some((minutes * 60 + whole) * 1000 + millis)
+}
+
+/// Parses the strings lit by a cue into a frame bitmask: names or indices
+/// separated by spaces, `all`, or `-` for none.
+pub fn parse_strings(strings: &str, names: &[&str]) -> Result<u32, String> {
+    match strings {
+        "-" => return Ok(0),
+        "all" => return Ok(u32::MAX >> (32 - names.len())),
+        _ => {}
+    }
+
+    strings.split_whitespace().try_fold(0, |mask, string| {
+        let index = names
+            .iter()
+            .position(|name| *name == string)
+            .or_else(|| string.parse().ok().filter(|&index| index < names.len()))
+            .ok_or_else(|| {
+                format!("unknown string `{string}`, expected one of {names:?} or an index")
+            })?;
+        Ok(mask | 1 << index)
+    })
+}
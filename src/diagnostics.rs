@@ -0,0 +1,105 @@
+//! Diagnostic reports over the defmt RTT channel.
+//!
+//! Unlike the `debug-mode` trace logs, diagnostic reports are compiled into
+//! every build. Attaching a probe to a returned ornament (for example with
+//! `probe-rs attach`) shows the lifetime statistics read back from EEPROM
+//! without reflashing or erasing them.
+//!
+//! Besides battery statistics, reports include how often and how long the
+//! MCU wakes from STOP mode (see [`crate::activity`]), counted since boot,
+//! and the event log (see [`crate::event_log`]), oldest event first.
+//!
+//! A report is produced at boot and whenever one is requested with
+//! [`request_report`], for example by a very long button press.
+
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+
+use crate::activity;
+use crate::battery_stats::BatteryStats;
+use crate::eeprom::Eeprom;
+use crate::event_log::EventLog;
+use crate::fuel_gauge::FuelGauge;
+use crate::hardware;
+use crate::power_state::PowerState;
+
+/// Static signal for requesting a diagnostic report.
+///
+/// Waited on by the power monitor task, which owns the battery state.
+pub static REPORT_REQUEST: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Requests a diagnostic report from the task owning the battery state.
+pub fn request_report() {
+    REPORT_REQUEST.signal(());
+}
+
+/// Reports fuel gauge estimates and lifetime usage for both battery slots.
+///
+/// # Arguments
+///
+/// * `active` - Currently active battery
+/// * `gauge` - Fuel gauge estimates
+/// * `stats` - Lifetime battery statistics
+pub fn report_batteries(active: &PowerState, gauge: &FuelGauge, stats: &BatteryStats) {
+    defmt::info!(
+        "Board: {}, LED strings: {}",
+        hardware::BOARD_NAME,
+        hardware::STRING_NAMES
+    );
+    defmt::info!("Active battery slot: {}", active.slot());
+
+    for (name, cell) in [
+        ("main", PowerState::MainPower),
+        ("backup", PowerState::BackupPower),
+    ] {
+        let slot = stats.slot(&cell);
+        defmt::info!(
+            "Battery {}: {}% remaining, {} h active, {} switches, {} replacements, last VDD {} mV",
+            name,
+            gauge.percent_remaining(&cell),
+            slot.active_secs / 3600,
+            slot.switches,
+            slot.replacements,
+            stats.last_vdd_mv(&cell)
+        );
+    }
+}
+
+/// Reports PVD low-voltage events rejected as transient droops since boot.
+///
+/// # Arguments
+///
+/// * `rejected` - Number of rejected events
+pub fn report_droops(rejected: u32) {
+    defmt::info!("PVD droops rejected: {}", rejected);
+}
+
+/// Reports wake-ups and active time since boot.
+pub fn report_activity() {
+    let stats = activity::stats();
+    defmt::info!(
+        "Activity: {} wakes/h, {} us average, {} ppm active, longest {} us in {}",
+        stats.wakes_per_hour(),
+        stats.average_active_us(),
+        stats.active_ppm(),
+        stats.longest_us(),
+        stats.longest_cause
+    );
+}
+
+/// Reports the event log, oldest event first.
+///
+/// # Arguments
+///
+/// * `events` - Event log to list
+/// * `eeprom` - EEPROM holding the event log
+pub fn report_events(events: &EventLog, eeprom: &mut Eeprom) {
+    events.for_each(eeprom, |record| {
+        defmt::info!(
+            "Event {}: {} ({}) at {} s",
+            record.seq,
+            record.entry.kind,
+            record.entry.arg,
+            record.entry.uptime_secs
+        );
+    });
+}
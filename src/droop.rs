@@ -0,0 +1,116 @@
+//! Confirmation of PVD low-voltage events.
+//!
+//! A coin cell has an internal resistance of 10 to 30 Ω, so the inrush
+//! when an LED string turns on can pull VDD below the PVD threshold for a
+//! moment even when the cell has plenty of charge left. Switching batteries
+//! on every such droop would wear down the backup cell and mark a healthy
+//! main cell empty.
+//!
+//! # Confirmation
+//!
+//! A low-voltage event is only acted on once it persists:
+//!
+//! 1. The power task waits [`SETTLE_TIME`] and re-reads PVDO, up to
+//!    [`CONFIRM_READS`] times. Any read above the threshold rejects the
+//!    event.
+//! 2. If no LED string is lit, VDD is measured through the ADC as a second
+//!    opinion. A reading at or above [`RECOVERED_MV`] rejects the event.
+//!    With strings lit the measurement would see the same droop and is
+//!    skipped.
+//!
+//! Rejected events are counted and included in diagnostic reports.
+//!
+//! [`DroopFilter`] is a pure state machine fed with readings, so the host
+//! tests in `host-tests/tests/droop.rs` replay scripted glitches through it.
+
+use embassy_time::Duration;
+
+/// Time VDD is given to recover before each PVDO re-read.
+pub const SETTLE_TIME: Duration = Duration::from_millis(20);
+
+/// PVDO re-reads that must all show VDD below the threshold.
+pub const CONFIRM_READS: u8 = 3;
+
+/// VDD measured with all strings off at or above which a droop is rejected, in mV.
+///
+/// The 2.7V PVD threshold plus margin for the accuracy of the VREFINT
+/// measurement.
+pub const RECOVERED_MV: u16 = 2750;
+
+/// Reading taken while confirming a low-voltage event.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Reading {
+    /// PVDO re-read after [`SETTLE_TIME`]: true while VDD is below the
+    /// threshold
+    PvdLow(bool),
+    /// VDD measured with all strings off, in mV
+    VddMv(u16),
+}
+
+/// Outcome of a reading.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum Verdict {
+    /// More PVDO reads are needed
+    Pending,
+    /// VDD stayed low: the battery is failing
+    Confirmed,
+    /// VDD recovered: the event was a transient droop
+    Rejected,
+}
+
+/// Decides whether a low-voltage event persists.
+pub struct DroopFilter {
+    /// PVDO reads still needed to confirm the current event
+    reads_left: u8,
+    /// Number of events rejected as transient droops
+    rejected: u32,
+}
+
+impl DroopFilter {
+    /// Creates a filter with no rejected events.
+    pub const fn new() -> Self {
+        Self {
+            reads_left: 0,
+            rejected: 0,
+        }
+    }
+
+    /// Starts confirming a low-voltage event.
+    pub fn begin(&mut self) {
+        self.reads_left = CONFIRM_READS;
+    }
+
+    /// Feeds a reading taken while confirming an event.
+    ///
+    /// PVDO re-reads come first; once they return [`Verdict::Confirmed`]
+    /// a VDD measurement may still overturn the verdict.
+    ///
+    /// # Returns
+    ///
+    /// The verdict on the event given the readings so far
+    pub fn update(&mut self, reading: Reading) -> Verdict {
+        let recovered = match reading {
+            Reading::PvdLow(low) => !low,
+            Reading::VddMv(vdd_mv) => vdd_mv >= RECOVERED_MV,
+        };
+        if recovered {
+            self.reads_left = 0;
+            self.rejected = self.rejected.saturating_add(1);
+            return Verdict::Rejected;
+        }
+
+        if let Reading::PvdLow(_) = reading {
+            self.reads_left = self.reads_left.saturating_sub(1);
+        }
+        if self.reads_left == 0 {
+            Verdict::Confirmed
+        } else {
+            Verdict::Pending
+        }
+    }
+
+    /// Returns the number of events rejected as transient droops.
+    pub fn rejected(&self) -> u32 {
+        self.rejected
+    }
+}
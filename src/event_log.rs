@@ -0,0 +1,317 @@
+//! Persistent event log in data EEPROM.
+//!
+//! A returned ornament should be able to tell what happened to it. Notable
+//! events (boots and their reset cause, battery switches, rejected PVD
+//! droops, faults, pattern changes and a battery running out) are kept in
+//! a ring of records in data EEPROM, oldest overwritten first, and listed in
+//! every diagnostic report (see [`crate::diagnostics`]).
+//!
+//! # Record Format
+//!
+//! Each record is [`RECORD_SIZE`] bytes:
+//!
+//! | Byte | Contents                                   |
+//! |------|--------------------------------------------|
+//! | 0    | Sequence number (wrapping)                 |
+//! | 1    | Event kind (see [`Kind`])                  |
+//! | 2    | Event argument                             |
+//! | 3-5  | Seconds since boot (little-endian)         |
+//! | 6-7  | CRC-16 of bytes 0-5 (little-endian)        |
+//!
+//! # Power Loss
+//!
+//! EEPROM is written a byte at a time, so power can fail with a record half
+//! written. The check is written last and covers the sequence number, so a
+//! torn record fails its check and is skipped. There is no separate
+//! head pointer: at boot [`EventLog::recover`] finds the newest record as
+//! the one whose successor slot does not hold the next sequence number, and
+//! writing resumes after it, overwriting any torn record.
+//!
+//! # Wear
+//!
+//! Every record goes to the next slot in turn, so each EEPROM byte is
+//! written once per [`SLOTS`] events. Frequent events are batched before
+//! they are logged: rejected droops are counted and logged at most once per
+//! battery state write, hourly.
+//!
+//! [`EventLog`] works on any `embedded-storage` implementation, so the ring
+//! is tested on the host against a simulated EEPROM that loses power
+//! partway through writes.
+
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+use embassy_time::Instant;
+use embedded_storage::{ReadStorage, Storage};
+
+use crate::checksum::crc16;
+
+/// Size of an event record, in bytes.
+pub const RECORD_SIZE: usize = 8;
+
+/// Number of records kept.
+///
+/// Fewer than 128, so the wrapping 8-bit sequence numbers of any two
+/// records in the ring can be ordered.
+pub const SLOTS: u32 = 96;
+
+/// Number of events that can be queued before the power task logs them.
+///
+/// The power task writes queued events on its next wake, at most a minute
+/// later, so the queue holds a pattern change every 4 seconds of button
+/// presses. Events from a longer burst are dropped, which also keeps it
+/// from flooding the log.
+const QUEUE_LEN: usize = 16;
+
+/// Largest timestamp a record can hold, in seconds (about 194 days).
+const MAX_UPTIME_SECS: u32 = 0xFF_FFFF;
+
+/// Cause of the latest reset, from the RCC reset flags.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+#[repr(u8)]
+pub enum ResetCause {
+    /// Power-on or brown-out reset: a battery was inserted
+    PowerOn,
+    /// NRST pin, for example from a debug probe
+    Pin,
+    /// Software reset
+    Software,
+    /// Independent or window watchdog
+    Watchdog,
+    /// Illegal entry into a low-power mode
+    LowPower,
+    /// Option byte loading or firewall
+    Other,
+}
+
+impl ResetCause {
+    /// Decodes the reset flags in bits 24 to 31 of RCC_CSR.
+    ///
+    /// A power-on reset also sets the pin flag, so the flags are checked in
+    /// order of precedence.
+    pub fn from_flags(flags: u8) -> Self {
+        if flags & 0x08 != 0 {
+            ResetCause::PowerOn
+        } else if flags & 0x60 != 0 {
+            ResetCause::Watchdog
+        } else if flags & 0x80 != 0 {
+            ResetCause::LowPower
+        } else if flags & 0x10 != 0 {
+            ResetCause::Software
+        } else if flags & 0x04 != 0 {
+            ResetCause::Pin
+        } else {
+            ResetCause::Other
+        }
+    }
+}
+
+/// Kind of event recorded in the log.
+///
+/// The meaning of the argument byte stored with each event is given per
+/// kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+#[repr(u8)]
+pub enum Kind {
+    /// Firmware started; argument: [`ResetCause`]
+    Boot = 1,
+    /// Switched batteries; argument: slot switched to
+    BatterySwitch,
+    /// PVD low-voltage events rejected as transient droops; argument:
+    /// number since the previous droop record (saturating)
+    Droop,
+    /// Fault raised; argument: [`crate::system_state::Fault`]
+    Fault,
+    /// Pattern selected; argument: pattern index
+    Pattern,
+    /// LEDs turned off by the user; no argument
+    LedsOff,
+    /// The active battery failed with no battery left to switch to; no
+    /// argument
+    Shutdown,
+}
+
+impl Kind {
+    /// Every kind, in encoding order.
+    const ALL: [Kind; 7] = [
+        Kind::Boot,
+        Kind::BatterySwitch,
+        Kind::Droop,
+        Kind::Fault,
+        Kind::Pattern,
+        Kind::LedsOff,
+        Kind::Shutdown,
+    ];
+
+    /// Decodes a kind byte.
+    fn from_u8(kind: u8) -> Option<Self> {
+        Self::ALL.get(usize::from(kind).wrapping_sub(1)).copied()
+    }
+}
+
+/// Event stamped with the time it happened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Entry {
+    /// Seconds since boot
+    pub uptime_secs: u32,
+    /// What happened
+    pub kind: Kind,
+    /// Detail, depending on the kind
+    pub arg: u8,
+}
+
+/// Record read back from the log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Record {
+    /// Sequence number, counting records written (wrapping)
+    pub seq: u8,
+    /// The logged event
+    pub entry: Entry,
+}
+
+impl Record {
+    /// Serializes the record, check byte last.
+    fn to_bytes(self) -> [u8; RECORD_SIZE] {
+        let [t0, t1, t2, _] = self.entry.uptime_secs.min(MAX_UPTIME_SECS).to_le_bytes();
+        let mut bytes = [
+            self.seq,
+            self.entry.kind as u8,
+            self.entry.arg,
+            t0,
+            t1,
+            t2,
+            0,
+            0,
+        ];
+        let check = check_word(&bytes[..6]);
+        bytes[6..].copy_from_slice(&check.to_le_bytes());
+        bytes
+    }
+
+    /// Restores a record from its bytes.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the slot is blank, torn or corrupted
+    fn from_bytes(bytes: &[u8; RECORD_SIZE]) -> Option<Self> {
+        if u16::from_le_bytes([bytes[6], bytes[7]]) != check_word(&bytes[..6]) {
+            return None;
+        }
+        Some(Self {
+            seq: bytes[0],
+            entry: Entry {
+                uptime_secs: u32::from_le_bytes([bytes[3], bytes[4], bytes[5], 0]),
+                kind: Kind::from_u8(bytes[1])?,
+                arg: bytes[2],
+            },
+        })
+    }
+}
+
+/// Computes the check of a record's first six bytes.
+///
+/// The CRC starts from 0xFFFF, so a blank, all-zero slot fails the check.
+/// A torn record mixing old and new bytes passes it by chance once in
+/// 65536 power failures.
+fn check_word(bytes: &[u8]) -> u16 {
+    crc16(0xFFFF, bytes)
+}
+
+/// Ring of event records in storage.
+pub struct EventLog {
+    /// Storage offset of the first slot
+    offset: u32,
+    /// Slot the next record is written to
+    next: u32,
+    /// Sequence number of the next record
+    seq: u8,
+}
+
+impl EventLog {
+    /// Finds where writing resumes in a ring at `offset`.
+    ///
+    /// The newest record is the valid one whose successor slot holds no
+    /// record with the next sequence number. If corruption leaves several
+    /// candidates, the one with the latest sequence number wins. A blank
+    /// ring starts at its first slot.
+    pub fn recover<S: ReadStorage>(storage: &mut S, offset: u32) -> Self {
+        let mut log = Self {
+            offset,
+            next: 0,
+            seq: 0,
+        };
+
+        let mut newest: Option<Record> = None;
+        let mut previous = log.read(storage, SLOTS - 1);
+        for slot in 0..SLOTS {
+            let record = log.read(storage, slot);
+            if let Some(prev) = previous
+                && record.is_none_or(|record| record.seq != prev.seq.wrapping_add(1))
+                && newest.is_none_or(|newest| (prev.seq.wrapping_sub(newest.seq) as i8) > 0)
+            {
+                newest = Some(prev);
+                log.next = slot;
+                log.seq = prev.seq.wrapping_add(1);
+            }
+            previous = record;
+        }
+
+        log
+    }
+
+    /// Appends a record, overwriting the oldest once the ring is full.
+    ///
+    /// The slot is used up even if the write fails, so a failing byte is
+    /// not written again and again.
+    pub fn append<S: Storage>(&mut self, storage: &mut S, entry: Entry) -> Result<(), S::Error> {
+        let record = Record {
+            seq: self.seq,
+            entry,
+        };
+        let result = storage.write(self.slot_offset(self.next), &record.to_bytes());
+
+        self.next = (self.next + 1) % SLOTS;
+        self.seq = self.seq.wrapping_add(1);
+        result
+    }
+
+    /// Calls `f` with each record, oldest first.
+    pub fn for_each<S: ReadStorage>(&self, storage: &mut S, mut f: impl FnMut(Record)) {
+        for i in 0..SLOTS {
+            if let Some(record) = self.read(storage, (self.next + i) % SLOTS) {
+                f(record);
+            }
+        }
+    }
+
+    /// Reads the record in a slot, if it holds a valid one.
+    fn read<S: ReadStorage>(&self, storage: &mut S, slot: u32) -> Option<Record> {
+        let mut bytes = [0u8; RECORD_SIZE];
+        storage.read(self.slot_offset(slot), &mut bytes).ok()?;
+        Record::from_bytes(&bytes)
+    }
+
+    /// Returns the storage offset of a slot.
+    fn slot_offset(&self, slot: u32) -> u32 {
+        self.offset + slot * RECORD_SIZE as u32
+    }
+}
+
+/// Events waiting to be logged by the power monitor task.
+pub static EVENTS: Channel<CriticalSectionRawMutex, Entry, QUEUE_LEN> = Channel::new();
+
+/// Queues an event for the log, stamped with the current time.
+///
+/// The power monitor task, which owns the EEPROM, writes queued events on
+/// its next wake. If the queue is full the event is dropped.
+///
+/// # Arguments
+///
+/// * `kind` - What happened
+/// * `arg` - Detail, as documented for the kind
+pub fn record(kind: Kind, arg: u8) {
+    let entry = Entry {
+        uptime_secs: Instant::now().as_secs() as u32,
+        kind,
+        arg,
+    };
+    EVENTS.try_send(entry).ok();
+}
@@ -0,0 +1,171 @@
+//! Flip-flop LED backend.
+//!
+//! Each LED string is driven by its own D flip-flop (SN74LVC1G74) through
+//! its Q output, costing four GPIOs per string. The flip-flops hold their
+//! state while the MCU sleeps in STOP mode.
+//!
+//! # Hardware Design
+//!
+//! On the rev-a board there are two strings:
+//! - U2 (SN74LVC1G74): Controls LSTR1 (3x RED LEDs: LED1, LED3, LED5)
+//! - U3 (SN74LVC1G74): Controls LSTR2 (3x GREEN LEDs: LED6, LED2, LED4)
+//!
+//! Each flip-flop has:
+//! - D (data input): Sets the value to be clocked in
+//! - CLK (clock): Rising edge latches D input to Q output
+//! - PRE_N (preset, active-low): Asynchronously sets Q high
+//! - CLR_N (clear, active-low): Asynchronously sets Q low
+//! - Q (output): Drives LED string (high = LEDs ON)
+//!
+//! LEDs are driven with 4.7kΩ resistors for ~255µA current at 3.0V.
+//!
+//! The flip-flops are powered from VDD and their Q outputs are push-pull
+//! with no high-impedance state, so an LSTR node is always driven. The
+//! feedback pins wired to the nodes can read the latched state back, but
+//! cannot time a decay through the string to sense light (see
+//! [`crate::light_sensor`]).
+
+use embassy_stm32::gpio::{Input, Output};
+
+use crate::led_backend::{Frame, LedBackend};
+
+/// D flip-flop controller for LED string.
+///
+/// Wraps GPIO control for a SN74LVC1G74 D flip-flop chip.
+/// Provides high-level methods for setting the Q output state.
+pub struct FlipFlop {
+    /// Active-low preset input (forces Q high when low)
+    fpre_n: Output<'static>,
+    /// Active-low clear input (forces Q low when low)
+    fclr_n: Output<'static>,
+    /// Data input (value to be clocked into Q)
+    fdata: Output<'static>,
+    /// Clock input (rising edge latches D to Q)
+    fclk: Output<'static>,
+    /// Last value latched into Q
+    q: bool,
+}
+
+impl FlipFlop {
+    /// Creates a new FlipFlop controller.
+    ///
+    /// # Arguments
+    ///
+    /// * `fpre_n` - Active-low preset GPIO
+    /// * `fclr_n` - Active-low clear GPIO
+    /// * `fdata` - Data input GPIO
+    /// * `fclk` - Clock input GPIO
+    pub fn new(
+        fpre_n: Output<'static>,
+        fclr_n: Output<'static>,
+        fdata: Output<'static>,
+        fclk: Output<'static>,
+    ) -> Self {
+        Self {
+            fpre_n,
+            fclr_n,
+            fdata,
+            fclk,
+            q: false,
+        }
+    }
+
+    /// Returns true if Q was last latched high (LED string ON).
+    pub fn is_on(&self) -> bool {
+        self.q
+    }
+
+    /// Releases asynchronous reset/preset controls.
+    ///
+    /// Sets both CLR_N and PRE_N high to deactivate them.
+    /// This allows normal clocked operation where D is latched to Q on CLK rising edge.
+    pub fn release_reset(&mut self) {
+        self.fclr_n.set_high();
+        self.fpre_n.set_high();
+    }
+
+    /// Clocks the flip-flop to set Q output high.
+    ///
+    /// Sets D input high, then pulses CLK to latch the value.
+    /// This turns the LED string ON.
+    pub fn clock_q_high(&mut self) {
+        self.fdata.set_high();
+        self.fclk.set_high();
+        self.fclk.set_low();
+        self.q = true;
+    }
+
+    /// Clocks the flip-flop to set Q output low.
+    ///
+    /// Sets D input low, then pulses CLK to latch the value.
+    /// This turns the LED string OFF.
+    pub fn clock_q_low(&mut self) {
+        self.fdata.set_low();
+        self.fclk.set_high();
+        self.fclk.set_low();
+        self.q = false;
+    }
+}
+
+/// Bank of `N` flip-flops, one per LED string.
+///
+/// Each string's LSTR node is also wired to a feedback input, which reads
+/// back the Q output. It is not read yet.
+pub struct FlipFlops<const N: usize> {
+    /// Flip-flops driving each LED string, in board description order
+    flops: [FlipFlop; N],
+    /// Feedback input on each string's LSTR node (not read yet)
+    _feedback: [Input<'static>; N],
+}
+
+impl<const N: usize> FlipFlops<N> {
+    /// Creates a new flip-flop bank.
+    ///
+    /// # Arguments
+    ///
+    /// * `flops` - FlipFlop controlling each LED string
+    /// * `feedback` - Feedback input on each string's LSTR node
+    pub fn new(flops: [FlipFlop; N], feedback: [Input<'static>; N]) -> Self {
+        Self {
+            flops,
+            _feedback: feedback,
+        }
+    }
+}
+
+impl<const N: usize> LedBackend for FlipFlops<N> {
+    const STRINGS: usize = N;
+
+    /// Explicitly clears every flip-flop to Q=LOW (LEDs OFF),
+    /// then releases reset controls for normal operation.
+    fn reset(&mut self) {
+        for flop in &mut self.flops {
+            // First, release any preset/clear to allow normal operation
+            flop.release_reset();
+
+            // Explicitly clock the flip-flop to LOW state (LEDs OFF)
+            flop.clock_q_low();
+        }
+    }
+
+    fn latched(&self) -> Frame {
+        let mut frame = Frame::OFF;
+        for (n, flop) in self.flops.iter().enumerate() {
+            if flop.is_on() {
+                frame.0 |= Frame::single(n).0;
+            }
+        }
+        frame
+    }
+
+    /// Only strings whose state changes are clocked.
+    fn apply(&mut self, frame: Frame) {
+        for (n, flop) in self.flops.iter_mut().enumerate() {
+            match (flop.is_on(), frame.is_on(n)) {
+                (false, true) => flop.clock_q_high(),
+                (true, false) => flop.clock_q_low(),
+                _ => {}
+            }
+        }
+    }
+}
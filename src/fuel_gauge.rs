@@ -0,0 +1,114 @@
+//! Coulomb-counting fuel gauge for the coin cell batteries.
+//!
+//! The ornament has no current sensor, but the firmware knows exactly which
+//! LED strings are lit and for how long. The fuel gauge integrates a modeled
+//! load current over time and charges it to whichever battery is active,
+//! giving a per-cell estimate of the charge remaining.
+//!
+//! # Load Model
+//!
+//! - **Idle**: ~3µA (MCU in STOP with RTC and PVD, load switch quiescent)
+//! - **LED string**: ~765µA (3 LEDs at ~255µA each)
+//!
+//! Charge is tracked in µA·s against the nominal CR2032 capacity. When the
+//! PVD reports that a cell has dropped below threshold the estimate for that
+//! cell is forced to empty, correcting for any modeling error.
+
+use crate::checksum::check_word;
+use crate::power_state::PowerState;
+
+/// Nominal coin cell capacity in µA·s (CR2032, 225 mAh).
+pub const CELL_CAPACITY_UAS: u32 = 225_000 * 3600;
+
+/// Modeled current draw with all LED strings off, in µA.
+pub const IDLE_CURRENT_UA: u32 = 3;
+
+/// Modeled current draw of one lit LED string, in µA.
+pub const STRING_CURRENT_UA: u32 = 765;
+
+/// Remaining charge at or below which a cell is considered low, in percent.
+pub const LOW_BATTERY_PERCENT: u8 = 15;
+
+/// Size of the serialized fuel gauge record in bytes.
+pub const RECORD_SIZE: usize = 16;
+
+/// Marks a valid fuel gauge record in EEPROM ("FUEL").
+const RECORD_MAGIC: u32 = 0x4655_454C;
+
+/// Estimated charge drawn from each battery slot.
+#[derive(Default)]
+pub struct FuelGauge {
+    /// Charge drawn in µA·s, indexed by [`PowerState::slot`]
+    drawn_uas: [u32; 2],
+}
+
+impl FuelGauge {
+    /// Charges modeled load current to a battery.
+    ///
+    /// # Arguments
+    ///
+    /// * `cell` - Battery that supplied the load
+    /// * `elapsed_ms` - Time the battery was active
+    /// * `string_on_ms` - Sum of on-time across all LED strings in that period
+    pub fn draw(&mut self, cell: &PowerState, elapsed_ms: u32, string_on_ms: u32) {
+        let charge_uams = u64::from(IDLE_CURRENT_UA) * u64::from(elapsed_ms)
+            + u64::from(STRING_CURRENT_UA) * u64::from(string_on_ms);
+        let charge_uas = u32::try_from(charge_uams / 1000).unwrap_or(u32::MAX);
+
+        let drawn = &mut self.drawn_uas[cell.slot()];
+        *drawn = drawn.saturating_add(charge_uas).min(CELL_CAPACITY_UAS);
+    }
+
+    /// Forces the estimate for a battery to empty.
+    ///
+    /// Called when the PVD shows the cell can no longer hold VDD above
+    /// threshold, regardless of what the model predicted.
+    pub fn mark_empty(&mut self, cell: &PowerState) {
+        self.drawn_uas[cell.slot()] = CELL_CAPACITY_UAS;
+    }
+
+    /// Resets the estimate for a battery to full after a cell replacement.
+    pub fn reset(&mut self, cell: &PowerState) {
+        self.drawn_uas[cell.slot()] = 0;
+    }
+
+    /// Returns the estimated charge remaining in a battery (0-100%).
+    pub fn percent_remaining(&self, cell: &PowerState) -> u8 {
+        let remaining = CELL_CAPACITY_UAS - self.drawn_uas[cell.slot()];
+        (u64::from(remaining) * 100 / u64::from(CELL_CAPACITY_UAS)) as u8
+    }
+
+    /// Serializes the fuel gauge for storage in EEPROM.
+    ///
+    /// Layout (little-endian): magic, main drawn, backup drawn, check word.
+    pub fn to_record(&self) -> [u8; RECORD_SIZE] {
+        let [main, backup] = self.drawn_uas;
+        let mut record = [0u8; RECORD_SIZE];
+        record[0..4].copy_from_slice(&RECORD_MAGIC.to_le_bytes());
+        record[4..8].copy_from_slice(&main.to_le_bytes());
+        record[8..12].copy_from_slice(&backup.to_le_bytes());
+        let check = check_word(RECORD_MAGIC, &record[..12]);
+        record[12..16].copy_from_slice(&check.to_le_bytes());
+        record
+    }
+
+    /// Restores a fuel gauge from an EEPROM record.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the record is blank, corrupted, or out of range
+    pub fn from_record(record: &[u8; RECORD_SIZE]) -> Option<Self> {
+        let u32_at =
+            |i: usize| u32::from_le_bytes([record[i], record[i + 1], record[i + 2], record[i + 3]]);
+        let (main, backup) = (u32_at(4), u32_at(8));
+
+        let valid = u32_at(0) == RECORD_MAGIC
+            && u32_at(12) == check_word(RECORD_MAGIC, &record[..12])
+            && main <= CELL_CAPACITY_UAS
+            && backup <= CELL_CAPACITY_UAS;
+
+        valid.then_some(Self {
+            drawn_uas: [main, backup],
+        })
+    }
+}
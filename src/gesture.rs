@@ -0,0 +1,187 @@
+//! Push-button gesture decoding.
+//!
+//! [`GestureDecoder`] is a pure state machine fed with the raw button level
+//! and a millisecond timestamp. It debounces the level itself and reports
+//! the next time it needs to be polled, so it has no hardware or timer
+//! dependencies; the host tests in `host-tests/tests/gesture.rs` drive it
+//! with simulated presses.
+//!
+//! # Timing
+//!
+//! A press shorter than [`LONG_PRESS_MS`] is reported as a short press once
+//! [`DOUBLE_PRESS_WINDOW_MS`] pass without a second press, or together with
+//! the second as a double press. A second press held into a long press
+//! reports the first as a short press as soon as it becomes long, and then
+//! the long or very long press on release.
+
+/// Time the raw level must be stable before it is accepted, in ms.
+pub const DEBOUNCE_MS: u64 = 20;
+
+/// Maximum gap between two short presses forming a double press, in ms.
+pub const DOUBLE_PRESS_WINDOW_MS: u64 = 400;
+
+/// Minimum hold time for a long press, in ms.
+pub const LONG_PRESS_MS: u64 = 1500;
+
+/// Minimum hold time for a very long press, in ms.
+pub const VERY_LONG_PRESS_MS: u64 = 5000;
+
+/// Button gesture recognized by [`GestureDecoder`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Gesture {
+    /// Single short press
+    Short,
+    /// Two short presses in quick succession
+    Double,
+    /// Press held for at least [`LONG_PRESS_MS`]
+    Long,
+    /// Press held for at least [`VERY_LONG_PRESS_MS`]
+    VeryLong,
+}
+
+/// Debounced press state of the gesture decoder.
+#[derive(Clone, Copy)]
+enum PressState {
+    /// Button released, no gesture in progress
+    Idle,
+    /// Button held down since `since`; `second` if this is the second press
+    /// of a possible double press
+    Down { since: u64, second: bool },
+    /// Short press released at `since`, waiting to see if a second follows
+    Up { since: u64 },
+}
+
+/// Debouncing gesture decoder for a single push-button.
+pub struct GestureDecoder {
+    /// Last raw level seen (true = pressed)
+    raw: bool,
+    /// Time of the last raw level change, in ms
+    raw_since: u64,
+    /// Debounced level (true = pressed)
+    stable: bool,
+    /// Gesture recognition state
+    state: PressState,
+}
+
+impl GestureDecoder {
+    /// Creates a decoder with the button released.
+    pub const fn new() -> Self {
+        Self {
+            raw: false,
+            raw_since: 0,
+            stable: false,
+            state: PressState::Idle,
+        }
+    }
+
+    /// Feeds the current raw button level to the decoder.
+    ///
+    /// Call on every edge and whenever [`Self::deadline`] expires.
+    ///
+    /// # Arguments
+    ///
+    /// * `pressed` - Raw button level (true = pressed)
+    /// * `now_ms` - Current time in milliseconds
+    ///
+    /// # Returns
+    ///
+    /// The gesture completed by this update, if any
+    pub fn update(&mut self, pressed: bool, now_ms: u64) -> Option<Gesture> {
+        if pressed != self.raw {
+            self.raw = pressed;
+            self.raw_since = now_ms;
+        }
+
+        if self.raw != self.stable && now_ms - self.raw_since >= DEBOUNCE_MS {
+            self.stable = self.raw;
+            let gesture = if self.stable {
+                self.on_press(self.raw_since)
+            } else {
+                self.on_release(self.raw_since)
+            };
+            if gesture.is_some() {
+                return gesture;
+            }
+        }
+
+        match self.state {
+            PressState::Up { since } if now_ms - since >= DOUBLE_PRESS_WINDOW_MS => {
+                self.state = PressState::Idle;
+                Some(Gesture::Short)
+            }
+            // A second press held into a long press is not a double press:
+            // report the first press now, the hold on release
+            PressState::Down {
+                since,
+                second: true,
+            } if now_ms - since >= LONG_PRESS_MS => {
+                self.state = PressState::Down {
+                    since,
+                    second: false,
+                };
+                Some(Gesture::Short)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the time at which the decoder next needs an update, if any.
+    ///
+    /// Edges always need an update as well; this only covers debounce
+    /// settling, the double press window expiring and a second press
+    /// becoming a long press.
+    pub fn deadline(&self) -> Option<u64> {
+        if self.raw != self.stable {
+            return Some(self.raw_since + DEBOUNCE_MS);
+        }
+
+        match self.state {
+            PressState::Up { since } => Some(since + DOUBLE_PRESS_WINDOW_MS),
+            PressState::Down {
+                since,
+                second: true,
+            } => Some(since + LONG_PRESS_MS),
+            _ => None,
+        }
+    }
+
+    /// Handles a debounced press at `at_ms`.
+    fn on_press(&mut self, at_ms: u64) -> Option<Gesture> {
+        let (second, pending_short) = match self.state {
+            PressState::Up { since } => {
+                let second = at_ms - since < DOUBLE_PRESS_WINDOW_MS;
+                (second, !second)
+            }
+            _ => (false, false),
+        };
+
+        self.state = PressState::Down {
+            since: at_ms,
+            second,
+        };
+
+        // A press after the window closed still owes the earlier short press
+        pending_short.then_some(Gesture::Short)
+    }
+
+    /// Handles a debounced release at `at_ms`.
+    fn on_release(&mut self, at_ms: u64) -> Option<Gesture> {
+        let PressState::Down { since, second } = self.state else {
+            return None;
+        };
+
+        let held_ms = at_ms - since;
+        self.state = PressState::Idle;
+
+        if held_ms >= VERY_LONG_PRESS_MS {
+            Some(Gesture::VeryLong)
+        } else if held_ms >= LONG_PRESS_MS {
+            Some(Gesture::Long)
+        } else if second {
+            Some(Gesture::Double)
+        } else {
+            self.state = PressState::Up { since: at_ms };
+            None
+        }
+    }
+}
@@ -5,23 +5,32 @@
 //!
 //! # Pin Assignments
 //!
-//! ## Power Control
-//! - **PB1**: MAIN_POWER_N - Active-low enable for main battery (Q1)
-//! - **PA8**: BACKUP_POWER_N - Active-low enable for backup battery (Q2)
+//! Board-specific pins are not hard-coded here. `build.rs` reads the board
+//! description selected by a `board-*` Cargo feature (or the
+//! `ORNAMENT_BOARD` environment variable) from `boards/<name>.toml`, checks
+//! that no pin is used twice or collides with the crystal or SWD pins
+//! listed below, and generates [`Peripherals::new`] from it. See
+//! `boards/rev-a.toml` for the pinout of the first PCB revision.
 //!
-//! ## Red LED String Control (LSTR1 via U2)
-//! - **PB3**: FCLK1 - Clock input for red flip-flop
-//! - **PA15**: FDATA1 - Data input for red flip-flop
-//! - **PB5**: FCLR1_N - Active-low clear for red flip-flop
-//! - **PB6**: FPRE1_N - Active-low preset for red flip-flop
-//! - **PB4**: LSTR1 - Feedback from red LED string (unused)
+//! A board description covers:
+//! - **power**: MAIN_POWER_N / BACKUP_POWER_N load switch enables
+//! - **string.\<name\>** (one per LED string, in order): FPRE_N, FCLR_N,
+//!   FDATA, FCLK flip-flop inputs and the LSTR string feedback input
+//! - **shift_register** (instead of string sections): SER, SRCLK, RCLK of a
+//!   74HC595 chain and the names of the strings on its outputs
+//! - **charlieplex** (instead of string sections): the charlieplexed pins
+//!   and the names of the LEDs between them, in scan order
+//! - **button** (optional): Push-button to GND, internal pull-up (EXTI)
+//! - **sync** (optional): Sync link to other ornaments, on a shared wire or
+//!   a phototransistor (EXTI)
+//! - **light_sensor** (optional): Cathode of a sensing LED on a pin of its
+//!   own, for day/night detection
+//! - **uart** (optional): Reserved TX/RX pins
 //!
-//! ## Green LED String Control (LSTR2 via U3)
-//! - **PB0**: FCLK2 - Clock input for green flip-flop
-//! - **PA7**: FDATA2 - Data input for green flip-flop
-//! - **PA5**: FCLR2_N - Active-low clear for green flip-flop
-//! - **PA4**: FPRE2_N - Active-low preset for green flip-flop
-//! - **PA6**: LSTR2 - Feedback from green LED string (unused)
+//! ## Internal Peripherals
+//! - **ADC1**: VDD measurement via VREFINT (no external pin)
+//! - **FLASH**: Data EEPROM for persistent state
+//! - **PWR**: Programmable Voltage Detector on EXTI line 16
 //!
 //! ## Low Power & RTC
 //! - **PC14**: OSC32_IN - 32.768 kHz crystal input
@@ -31,10 +40,19 @@
 //! - **PA13**: SWDIO
 //! - **PA14**: SWCLK
 
-use embassy_stm32::gpio::{Input, Level, Output, Pull, Speed};
+use embassy_stm32::exti::ExtiInput;
+use embassy_stm32::{adc, bind_interrupts, pac, peripherals, uid};
+use embassy_time::Instant;
 
+use crate::eeprom::Eeprom;
+use crate::event_log::ResetCause;
 use crate::power::PowerController;
-use crate::string_controller::{FlipFlop, StringController};
+use crate::{prng, pvd};
+
+bind_interrupts!(pub struct Irqs {
+    ADC1_COMP => adc::InterruptHandler<peripherals::ADC1>;
+    PVD => pvd::InterruptHandler;
+});
 
 /// Top-level peripheral container for the Christmas ornament.
 ///
@@ -43,63 +61,47 @@ use crate::string_controller::{FlipFlop, StringController};
 pub struct Peripherals {
     /// Power management controller (dual-battery system)
     pub pwr_ctrl: PowerController,
-    /// LED string pattern controller
-    pub str_ctrl: StringController,
+    /// LED pattern engine for the board's LED backend
+    pub led_ctrl: LedController,
+    /// Data EEPROM for persistent state
+    pub eeprom: Eeprom,
+    /// User push-button (active low), if the board has one
+    pub button: Option<ExtiInput<'static>>,
+    /// Ambient light sensor, if the board has one
+    pub light_sensor: AmbientSensor,
+    /// Charlieplexed LED pins, scanned by the charlieplex task
+    #[cfg(led_backend = "charlieplex")]
+    pub charlieplex_pins: [Flex<'static>; CHARLIEPLEX_PINS],
+    /// Link for synchronizing patterns with other ornaments
+    #[cfg(sync_link)]
+    pub sync: SyncLink,
 }
 
-impl Peripherals {
-    /// Initializes all peripherals from STM32 peripheral singleton.
-    ///
-    /// Consumes the embassy-stm32 Peripherals struct and creates
-    /// GPIO outputs/inputs for all hardware controllers.
-    ///
-    /// # Initial GPIO States
-    ///
-    /// Power control:
-    /// - PB1 (MAIN_POWER_N): Low (main battery ON)
-    /// - PA8 (BACKUP_POWER_N): High (backup battery OFF)
-    ///
-    /// Red flip-flop (U2):
-    /// - PB6 (FPRE1_N): High (preset inactive)
-    /// - PB5 (FCLR1_N): High (clear inactive)
-    /// - PA15 (FDATA1): Low
-    /// - PB3 (FCLK1): Low
-    ///
-    /// Green flip-flop (U3):
-    /// - PA4 (FPRE2_N): High (preset inactive)
-    /// - PA5 (FCLR2_N): High (clear inactive)
-    /// - PA7 (FDATA2): Low
-    /// - PB0 (FCLK2): Low
-    ///
-    /// # Arguments
-    ///
-    /// * `p` - STM32 peripheral singleton from embassy_stm32::init()
-    ///
-    /// # Returns
-    ///
-    /// Initialized Peripherals struct ready for use
-    pub fn new(p: embassy_stm32::Peripherals) -> Self {
-        Self {
-            pwr_ctrl: PowerController::new(
-                Output::new(p.PB1, Level::Low, Speed::Low),
-                Output::new(p.PA8, Level::High, Speed::Low),
-            ),
-            str_ctrl: StringController::new(
-                FlipFlop::new(
-                    Output::new(p.PB6, Level::High, Speed::Low),
-                    Output::new(p.PB5, Level::High, Speed::Low),
-                    Output::new(p.PA15, Level::Low, Speed::Low),
-                    Output::new(p.PB3, Level::Low, Speed::Low),
-                ),
-                FlipFlop::new(
-                    Output::new(p.PA4, Level::High, Speed::Low),
-                    Output::new(p.PA5, Level::High, Speed::Low),
-                    Output::new(p.PA7, Level::Low, Speed::Low),
-                    Output::new(p.PB0, Level::Low, Speed::Low),
-                ),
-                Input::new(p.PB4, Pull::None),
-                Input::new(p.PA6, Pull::None),
-            ),
-        }
+include!(concat!(env!("OUT_DIR"), "/board.rs"));
+
+/// Returns a seed that differs between devices and between power-ups.
+///
+/// Mixes the device's unique ID (constant per device), the RTC sub-second
+/// counter and the uptime (which depend on when the crystal started).
+pub fn hardware_seed() -> u32 {
+    let mut seed = 0;
+    let (words, _) = uid::uid().as_chunks::<4>();
+    for word in words {
+        seed = prng::mix(seed, u32::from_le_bytes(*word));
     }
+    seed = prng::mix(seed, u32::from(pac::RTC.ssr().read().ss()));
+    seed = prng::mix(seed, Instant::now().as_ticks() as u32);
+
+    #[cfg(feature = "debug-mode")]
+    defmt::info!("PRNG seed: {:#010x}", seed);
+
+    seed
+}
+
+/// Returns the cause of the latest reset and clears the reset flags.
+pub fn take_reset_cause() -> ResetCause {
+    let rcc = pac::RCC;
+    let flags = (rcc.csr().read().0 >> 24) as u8;
+    rcc.csr().modify(|w| w.set_rmvf(true));
+    ResetCause::from_flags(flags)
 }
@@ -0,0 +1,475 @@
+//! Wear-leveling key-value store on `embedded-storage`.
+//!
+//! Persistent state is written as fixed records at fixed offsets today (see
+//! [`crate::eeprom`]), so every update rewrites the same bytes. [`KvStore`]
+//! instead appends each update as a new record and spreads the writes over
+//! several pages of storage.
+//!
+//! # Layout
+//!
+//! The store occupies `pages` pages of `page_size` bytes. Each page starts
+//! with a 4-byte header, the page generation and a CRC-16 of it, followed
+//! by records:
+//!
+//! | Bytes | Contents                                           |
+//! |-------|----------------------------------------------------|
+//! | 0     | Key                                                |
+//! | 1     | Value length `n`                                   |
+//! | 2..   | Value (`n` bytes)                                  |
+//! | +2    | CRC-16 of page generation, key, length and value   |
+//!
+//! The page with the latest generation is active. A value is read from the
+//! last valid record for its key in the active page.
+//!
+//! # Wear Leveling
+//!
+//! Updates are appended to the active page. When a record no longer fits,
+//! the latest value of every key is copied into the next page in turn,
+//! which then becomes active. Each page is therefore rewritten once per
+//! `pages` page fills, and each byte only when the records reach it.
+//!
+//! # Atomic Updates
+//!
+//! Storage can lose power after any byte. Checks are written last and cover
+//! the page generation, so:
+//!
+//! - A torn record fails its check. Scanning stops there, the previous
+//!   value of the key stays in effect and the next record overwrites it.
+//! - Stale records left in a reused page fail the check against the new
+//!   generation.
+//! - A page being filled by compaction only becomes active once its header
+//!   is written, after the copied records and the new value. Until then the
+//!   old page stays active, with every previous value intact.
+//!
+//! A torn record or header passes its check by chance once in 65536 power
+//! failures.
+//!
+//! # Storage
+//!
+//! The store works on storage that can rewrite any byte on its own, like
+//! [`crate::eeprom::Eeprom`]: it relies on each write touching only the
+//! bytes written. That rules out NOR flash behind
+//! `embedded_storage::nor_flash::RmwNorFlashStorage`, which erases and
+//! rewrites a whole sector for every write, wearing it on each record and
+//! losing the sector's other records if power fails in between.
+//!
+//! The host tests run the store on simulated EEPROM that can lose power
+//! after any byte (see `host-tests/tests/kv_store.rs`).
+//!
+//! The firmware does not use the store yet: moving the fuel gauge and
+//! battery statistics onto it costs about 2 KB of flash, more than the
+//! RGB board has left.
+
+use core::marker::PhantomData;
+
+use embedded_storage::Storage;
+
+use crate::checksum::crc16;
+
+/// Size of a page header, in bytes.
+const HEADER_SIZE: u32 = 4;
+
+/// Record bytes besides the value: key, length and check word.
+const RECORD_OVERHEAD: u32 = 4;
+
+/// Largest value that can be stored, in bytes.
+pub const MAX_VALUE_SIZE: usize = 64;
+
+/// Value that can be kept in the store.
+pub trait Value: Sized {
+    /// Size of the serialized value, in bytes (at most [`MAX_VALUE_SIZE`])
+    const SIZE: usize;
+
+    /// Serializes the value into `bytes`, which is [`Self::SIZE`] long.
+    fn to_bytes(&self, bytes: &mut [u8]);
+
+    /// Restores a value from `bytes`, which is [`Self::SIZE`] long.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the bytes do not hold a valid value
+    fn from_bytes(bytes: &[u8]) -> Option<Self>;
+}
+
+impl Value for u32 {
+    const SIZE: usize = 4;
+
+    fn to_bytes(&self, bytes: &mut [u8]) {
+        bytes.copy_from_slice(&self.to_le_bytes());
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(u32::from_le_bytes(bytes.try_into().ok()?))
+    }
+}
+
+impl<const N: usize> Value for [u8; N] {
+    const SIZE: usize = N;
+
+    fn to_bytes(&self, bytes: &mut [u8]) {
+        bytes.copy_from_slice(self);
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        bytes.try_into().ok()
+    }
+}
+
+/// Key of a value of type `T`.
+///
+/// Keys are declared once as constants, so a value is always read back as
+/// the type it was stored as:
+///
+/// ```ignore
+/// const BOOT_COUNT: Key<u32> = Key::new(1);
+///
+/// let boots = store.fetch(&BOOT_COUNT)?.unwrap_or(0);
+/// store.store(&BOOT_COUNT, &(boots + 1))?;
+/// ```
+pub struct Key<T> {
+    /// Key byte in records
+    id: u8,
+    /// Type of the value
+    value: PhantomData<T>,
+}
+
+impl<T: Value> Key<T> {
+    /// Creates a key stored as `id`.
+    pub const fn new(id: u8) -> Self {
+        Self {
+            id,
+            value: PhantomData,
+        }
+    }
+}
+
+/// Error from a store operation.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Error<E> {
+    /// The storage failed
+    Storage(E),
+    /// The latest values of all keys do not fit in one page
+    Full,
+}
+
+/// Record found while scanning a page.
+#[derive(Clone, Copy)]
+struct RecordInfo {
+    /// Key of the record
+    key: u8,
+    /// Storage offset of the value
+    value_offset: u32,
+    /// Length of the value
+    len: u8,
+    /// Storage offset just past the record
+    end: u32,
+}
+
+/// Key-value store over `pages` pages of storage.
+pub struct KvStore<S> {
+    /// Storage holding the pages
+    storage: S,
+    /// Storage offset of the first page
+    base: u32,
+    /// Size of each page, in bytes
+    page_size: u32,
+    /// Number of pages
+    pages: u32,
+    /// Index of the active page
+    active: u32,
+    /// Generation of the active page
+    generation: u16,
+    /// Storage offset of the first free byte in the active page
+    free: u32,
+}
+
+impl<S: Storage> KvStore<S> {
+    /// Opens the store, recovering from any interrupted update.
+    ///
+    /// Blank or unrecognizable storage is formatted as an empty store.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Storage holding the pages
+    /// * `base` - Storage offset of the first page
+    /// * `page_size` - Size of each page, in bytes
+    /// * `pages` - Number of pages, at least 2
+    pub fn open(
+        storage: S,
+        base: u32,
+        page_size: u32,
+        pages: u32,
+    ) -> Result<Self, Error<S::Error>> {
+        let mut store = Self {
+            storage,
+            base,
+            page_size,
+            pages,
+            active: 0,
+            generation: 0,
+            free: 0,
+        };
+
+        let mut newest: Option<u16> = None;
+        for page in 0..pages {
+            if let Some(generation) = store.read_header(page)?
+                && newest.is_none_or(|newest| (generation.wrapping_sub(newest) as i16) > 0)
+            {
+                newest = Some(generation);
+                store.active = page;
+                store.generation = generation;
+            }
+        }
+
+        if newest.is_none() {
+            store.format()?;
+        }
+        store.free = store.scan(|_| ())?;
+        Ok(store)
+    }
+
+    /// Returns the latest value stored for a key.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the key was never stored, or its value no longer parses
+    pub fn fetch<T: Value>(&mut self, key: &Key<T>) -> Result<Option<T>, Error<S::Error>> {
+        let mut latest = None;
+        self.scan(|record| {
+            if record.key == key.id {
+                latest = Some(record);
+            }
+        })?;
+
+        let Some(record) = latest.filter(|record| usize::from(record.len) == T::SIZE) else {
+            return Ok(None);
+        };
+        let mut bytes = [0u8; MAX_VALUE_SIZE];
+        let bytes = &mut bytes[..T::SIZE];
+        self.storage
+            .read(record.value_offset, bytes)
+            .map_err(Error::Storage)?;
+        Ok(T::from_bytes(bytes))
+    }
+
+    /// Stores a value for a key, replacing the previous one atomically.
+    ///
+    /// Moves the store to the next page if the record does not fit in the
+    /// active one.
+    pub fn store<T: Value>(&mut self, key: &Key<T>, value: &T) -> Result<(), Error<S::Error>> {
+        let mut bytes = [0u8; MAX_VALUE_SIZE];
+        let bytes = &mut bytes[..T::SIZE];
+        value.to_bytes(bytes);
+
+        if self.free + RECORD_OVERHEAD + T::SIZE as u32 > self.page_end(self.active) {
+            return self.compact(key.id, bytes);
+        }
+        self.free = self.write_record(self.free, self.generation, key.id, bytes)?;
+        Ok(())
+    }
+
+    /// Returns the storage, closing the store.
+    pub fn release(self) -> S {
+        self.storage
+    }
+
+    /// Makes the first page active and empty.
+    ///
+    /// The page is cleared before its header is written, so records left
+    /// from a store whose headers were all lost do not reappear.
+    fn format(&mut self) -> Result<(), Error<S::Error>> {
+        let blank = [0u8; 16];
+        let mut offset = self.page_start(0) + HEADER_SIZE;
+        while offset < self.page_end(0) {
+            let len = (self.page_end(0) - offset).min(blank.len() as u32);
+            self.storage
+                .write(offset, &blank[..len as usize])
+                .map_err(Error::Storage)?;
+            offset += len;
+        }
+        self.write_header(0, 0)
+    }
+
+    /// Copies the latest value of every other key into the next page along
+    /// with the new value of `key`, then makes that page active.
+    ///
+    /// The new value goes in before the page header, so the update takes
+    /// effect together with the page switch.
+    fn compact(&mut self, key: u8, value: &[u8]) -> Result<(), Error<S::Error>> {
+        let target = (self.active + 1) % self.pages;
+        let generation = self.generation.wrapping_add(1);
+        let mut free = self.page_start(target) + HEADER_SIZE;
+
+        let mut offset = self.page_start(self.active) + HEADER_SIZE;
+        while let Some(record) = self.read_record(offset, self.generation)? {
+            offset = record.end;
+            if record.key == key || self.superseded(&record)? {
+                continue;
+            }
+
+            let mut bytes = [0u8; MAX_VALUE_SIZE];
+            let bytes = &mut bytes[..usize::from(record.len)];
+            self.storage
+                .read(record.value_offset, bytes)
+                .map_err(Error::Storage)?;
+            if free + RECORD_OVERHEAD + bytes.len() as u32 > self.page_end(target) {
+                return Err(Error::Full);
+            }
+            free = self.write_record(free, generation, record.key, bytes)?;
+        }
+
+        if free + RECORD_OVERHEAD + value.len() as u32 > self.page_end(target) {
+            return Err(Error::Full);
+        }
+        free = self.write_record(free, generation, key, value)?;
+
+        // The header goes last: until it is complete the old page is active
+        self.write_header(target, generation)?;
+        self.active = target;
+        self.generation = generation;
+        self.free = free;
+        Ok(())
+    }
+
+    /// Returns true if a later record in the active page has the same key.
+    fn superseded(&mut self, record: &RecordInfo) -> Result<bool, Error<S::Error>> {
+        let mut offset = record.end;
+        while let Some(later) = self.read_record(offset, self.generation)? {
+            if later.key == record.key {
+                return Ok(true);
+            }
+            offset = later.end;
+        }
+        Ok(false)
+    }
+
+    /// Calls `f` with each valid record of the active page, in order.
+    ///
+    /// # Returns
+    ///
+    /// The storage offset just past the last valid record
+    fn scan(&mut self, mut f: impl FnMut(RecordInfo)) -> Result<u32, Error<S::Error>> {
+        let mut offset = self.page_start(self.active) + HEADER_SIZE;
+        while let Some(record) = self.read_record(offset, self.generation)? {
+            f(record);
+            offset = record.end;
+        }
+        Ok(offset)
+    }
+
+    /// Reads the record at `offset` in the active page, if a valid one of
+    /// `generation` is there.
+    fn read_record(
+        &mut self,
+        offset: u32,
+        generation: u16,
+    ) -> Result<Option<RecordInfo>, Error<S::Error>> {
+        let page_end = self.page_end(self.active);
+        if offset + RECORD_OVERHEAD > page_end {
+            return Ok(None);
+        }
+
+        let mut head = [0u8; 2];
+        self.storage
+            .read(offset, &mut head)
+            .map_err(Error::Storage)?;
+        let [key, len] = head;
+        let end = offset + RECORD_OVERHEAD + u32::from(len);
+        if usize::from(len) > MAX_VALUE_SIZE || end > page_end {
+            return Ok(None);
+        }
+
+        let mut bytes = [0u8; MAX_VALUE_SIZE + 2];
+        let bytes = &mut bytes[..usize::from(len) + 2];
+        self.storage
+            .read(offset + 2, bytes)
+            .map_err(Error::Storage)?;
+        let (value, check) = bytes.split_at(usize::from(len));
+        if u16::from_le_bytes([check[0], check[1]]) != check_word(generation, &head, value) {
+            return Ok(None);
+        }
+
+        Ok(Some(RecordInfo {
+            key,
+            value_offset: offset + 2,
+            len,
+            end,
+        }))
+    }
+
+    /// Writes a record at `offset`, check word last.
+    ///
+    /// # Returns
+    ///
+    /// The storage offset just past the record
+    fn write_record(
+        &mut self,
+        offset: u32,
+        generation: u16,
+        key: u8,
+        value: &[u8],
+    ) -> Result<u32, Error<S::Error>> {
+        let head = [key, value.len() as u8];
+        let check = check_word(generation, &head, value);
+        self.storage.write(offset, &head).map_err(Error::Storage)?;
+        self.storage
+            .write(offset + 2, value)
+            .map_err(Error::Storage)?;
+        let end = offset + 2 + value.len() as u32;
+        self.storage
+            .write(end, &check.to_le_bytes())
+            .map_err(Error::Storage)?;
+        Ok(end + 2)
+    }
+
+    /// Reads a page header.
+    ///
+    /// # Returns
+    ///
+    /// The page generation, or `None` if the header is not valid
+    fn read_header(&mut self, page: u32) -> Result<Option<u16>, Error<S::Error>> {
+        let mut header = [0u8; HEADER_SIZE as usize];
+        self.storage
+            .read(self.page_start(page), &mut header)
+            .map_err(Error::Storage)?;
+        let generation = u16::from_le_bytes([header[0], header[1]]);
+        let check = u16::from_le_bytes([header[2], header[3]]);
+        Ok((check == header_check(generation)).then_some(generation))
+    }
+
+    /// Writes a page header, check word last.
+    fn write_header(&mut self, page: u32, generation: u16) -> Result<(), Error<S::Error>> {
+        let offset = self.page_start(page);
+        self.storage
+            .write(offset, &generation.to_le_bytes())
+            .map_err(Error::Storage)?;
+        self.storage
+            .write(offset + 2, &header_check(generation).to_le_bytes())
+            .map_err(Error::Storage)
+    }
+
+    /// Returns the storage offset of a page.
+    fn page_start(&self, page: u32) -> u32 {
+        self.base + page * self.page_size
+    }
+
+    /// Returns the storage offset just past a page.
+    fn page_end(&self, page: u32) -> u32 {
+        self.page_start(page) + self.page_size
+    }
+}
+
+/// Computes the CRC-16 of a record, seeded with the page generation.
+///
+/// Seeding with the generation makes records left over from a previous use
+/// of a page fail the check.
+fn check_word(generation: u16, head: &[u8], value: &[u8]) -> u16 {
+    crc16(crc16(!generation, head), value)
+}
+
+/// Computes the CRC-16 of a page header's generation bytes.
+///
+/// The CRC starts from 0xFFFF, so a blank, all-zero header fails the check.
+fn header_check(generation: u16) -> u16 {
+    crc16(0xFFFF, &generation.to_le_bytes())
+}
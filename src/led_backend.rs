@@ -0,0 +1,67 @@
+//! LED string frames and the backends that latch them.
+//!
+//! A [`Frame`] gives the state of every LED string as a bitmask. An
+//! [`LedBackend`] latches frames onto the strings in hardware: one flip-flop
+//! per string, a 74HC595 chain or a charlieplexed matrix, chosen by the
+//! board description. The pattern engine in [`crate::string_controller`]
+//! drives any of them.
+//!
+//! Frames and the trait have no hardware dependencies, so backends generic
+//! over their pins can be exercised on the host.
+
+/// Latched state of the LED strings as a bitmask.
+///
+/// Bit `n` set means string `n` is on, so up to 32 strings are supported.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub struct Frame(pub u32);
+
+impl Frame {
+    /// All strings off.
+    pub const OFF: Self = Self(0);
+
+    /// Creates a frame with only string `n` on.
+    pub const fn single(n: usize) -> Self {
+        Self(1 << n)
+    }
+
+    /// Creates a frame with the first `strings` strings on.
+    pub const fn all(strings: usize) -> Self {
+        Self(u32::MAX >> (32 - strings))
+    }
+
+    /// Returns true if string `n` is on in this frame.
+    pub const fn is_on(self, n: usize) -> bool {
+        self.0 & (1 << n) != 0
+    }
+}
+
+/// Hardware that latches a [`Frame`] onto the LED strings.
+///
+/// Implementations should hold the latched state without MCU involvement,
+/// so the LEDs keep running while the MCU sleeps in STOP mode. Backends
+/// that cannot (charlieplexing) must keep their wake-ups within a budget.
+/// The pattern engine drives any backend through this trait.
+#[cfg(not(led_backend = "rgb"))]
+pub trait LedBackend {
+    /// Number of LED strings driven, at most 32.
+    const STRINGS: usize;
+
+    /// Turns every string off and leaves the hardware ready for
+    /// [`Self::apply`].
+    fn reset(&mut self);
+
+    /// Returns the frame currently latched.
+    fn latched(&self) -> Frame;
+
+    /// Latches a frame onto the strings.
+    ///
+    /// Bits beyond the last string are ignored.
+    fn apply(&mut self, frame: Frame);
+
+    /// Returns the number of strings drawing current.
+    ///
+    /// Defaults to the strings lit in the latched frame.
+    fn lit_strings(&self) -> u32 {
+        self.latched().0.count_ones()
+    }
+}
@@ -0,0 +1,153 @@
+//! Day/night detection from LED decay times.
+//!
+//! An LED is also a (poor) photodiode. A [`LightSensor`] charges a sensing
+//! LED's capacitance, releases it and times how long its pin takes to read
+//! low (see [`crate::sensor_led`]). Photocurrent speeds up the decay, so a
+//! long decay means darkness.
+//!
+//! # Calibration
+//!
+//! The absolute decay time depends on the LEDs, the board and temperature,
+//! so [`DarkDetector`] calibrates itself: it tracks the shortest (brightest)
+//! and longest (darkest) decay seen, each drifting slowly back towards
+//! recent samples so the range follows the seasons. Until the two are at
+//! least [`MIN_CONTRAST`] apart the detector keeps reporting darkness, so a
+//! freshly powered ornament lights up.
+//!
+//! # Hysteresis
+//!
+//! Darkness is declared above [`DARK_PERCENT`] of the calibrated range and
+//! daylight below [`LIGHT_PERCENT`], and only after [`CONFIRM_SAMPLES`]
+//! samples in a row agree, so dusk, passing headlights and the ornament's
+//! own LEDs do not make it flicker on and off.
+//!
+//! The detector is pure: it is fed decay times and has no hardware or timer
+//! dependencies, so it is tested on the host with scripted decay times.
+
+/// Position in the calibrated range above which it is dark, in percent.
+pub const DARK_PERCENT: u32 = 60;
+
+/// Position in the calibrated range below which it is light, in percent.
+pub const LIGHT_PERCENT: u32 = 40;
+
+/// Samples in a row needed to change state.
+pub const CONFIRM_SAMPLES: u8 = 3;
+
+/// Smallest ratio between the darkest and brightest decay times for the
+/// calibration to be trusted.
+pub const MIN_CONTRAST: u32 = 2;
+
+/// Rate at which the calibrated extremes drift towards each sample, as a
+/// right shift (1/256 of the difference per sample).
+const CALIBRATION_DRIFT_SHIFT: u32 = 8;
+
+/// Source of decay times for a [`DarkDetector`].
+#[cfg(light_sensor)]
+pub trait LightSensor {
+    /// Measures one decay time, in microseconds.
+    fn decay_us(&mut self) -> u32;
+}
+
+/// Stand-in for boards without a light sensor; never measured.
+#[cfg(not(light_sensor))]
+pub struct NoLightSensor;
+
+/// Ambient light level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Ambient {
+    /// Daylight or room light: the LEDs stay off
+    Light,
+    /// Dark: the LEDs run their pattern
+    Dark,
+}
+
+/// Self-calibrating day/night detector with hysteresis.
+pub struct DarkDetector {
+    /// Shortest decay seen, drifting upwards (brightest)
+    brightest: u32,
+    /// Longest decay seen, drifting downwards (darkest)
+    darkest: u32,
+    /// Reported ambient light level
+    ambient: Ambient,
+    /// Samples in a row disagreeing with `ambient`
+    pending: u8,
+}
+
+impl DarkDetector {
+    /// Creates an uncalibrated detector reporting darkness.
+    pub const fn new() -> Self {
+        Self {
+            brightest: u32::MAX,
+            darkest: 0,
+            ambient: Ambient::Dark,
+            pending: 0,
+        }
+    }
+
+    /// Returns the reported ambient light level.
+    pub fn ambient(&self) -> Ambient {
+        self.ambient
+    }
+
+    /// Feeds a decay time sample.
+    ///
+    /// # Arguments
+    ///
+    /// * `decay_us` - Decay time of the sensing strings, in microseconds
+    ///
+    /// # Returns
+    ///
+    /// The new ambient light level if it changed with this sample
+    pub fn update(&mut self, decay_us: u32) -> Option<Ambient> {
+        self.calibrate(decay_us);
+
+        let wanted = self.classify(decay_us).unwrap_or(self.ambient);
+        if wanted == self.ambient {
+            self.pending = 0;
+            return None;
+        }
+
+        self.pending += 1;
+        if self.pending < CONFIRM_SAMPLES {
+            return None;
+        }
+
+        self.pending = 0;
+        self.ambient = wanted;
+        Some(wanted)
+    }
+
+    /// Widens the calibrated range to include `decay_us`, and drifts the
+    /// extremes towards it.
+    fn calibrate(&mut self, decay_us: u32) {
+        if decay_us < self.brightest {
+            self.brightest = decay_us;
+        } else {
+            self.brightest += (decay_us - self.brightest) >> CALIBRATION_DRIFT_SHIFT;
+        }
+
+        if decay_us > self.darkest {
+            self.darkest = decay_us;
+        } else {
+            self.darkest -= (self.darkest - decay_us) >> CALIBRATION_DRIFT_SHIFT;
+        }
+    }
+
+    /// Returns the light level a sample falls into, or None if it falls
+    /// in the hysteresis band or the calibration is not trusted yet.
+    fn classify(&self, decay_us: u32) -> Option<Ambient> {
+        if self.darkest < self.brightest.saturating_mul(MIN_CONTRAST) {
+            return None;
+        }
+
+        let span = u64::from(self.darkest - self.brightest);
+        let position = u64::from(decay_us - self.brightest) * 100;
+        if position > span * u64::from(DARK_PERCENT) {
+            Some(Ambient::Dark)
+        } else if position < span * u64::from(LIGHT_PERCENT) {
+            Some(Ambient::Light)
+        } else {
+            None
+        }
+    }
+}
@@ -3,7 +3,7 @@
 //! # Overview
 //!
 //! This firmware controls a low-power Christmas ornament featuring:
-//! - Two LED strings (red and green) alternating every 3 seconds
+//! - Two LED strings (red and green) with selectable patterns
 //! - Dual coin cell batteries with automatic failover
 //! - Ultra-low power operation using STM32L031G6 in STOP mode
 //! - Programmable Voltage Detector (PVD) for battery monitoring
@@ -22,27 +22,133 @@
 //! The system starts on the main battery. When PVD detects voltage below
 //! 2.7V, it triggers an interrupt that wakes the MCU from STOP mode and
 //! switches to the backup battery. This process continues to alternate
-//! between batteries as they deplete.
+//! between batteries as they deplete. Other battery selection policies
+//! (alternating on a schedule, keeping the backup for the warning, or a
+//! single cell) can be chosen at build time.
 //!
 //! # Low Power Operation
 //!
-//! - MSI oscillator at 66 kHz for minimal active current
+//! - MSI oscillator at 66 kHz for minimal active current, raised to 524 kHz
+//!   only for ADC measurements and EEPROM writes
 //! - Embassy executor automatically enters STOP mode when idle
-//! - RTC timer wakes MCU every 3 seconds to update LED pattern
+//! - RTC timer wakes MCU every second to update LED pattern
 //! - PVD interrupt wakes MCU when battery voltage changes
+//! - Fuel gauge update wakes MCU once a minute
+//! - Ambient light is sensed once a minute, on boards with a sensing LED
+//! - Push-button EXTI line wakes MCU on press and release
+//!
+//! # User Input
+//!
+//! A push-button cycles patterns (short press, or double press to go
+//! back), turns the LEDs off and on (long press), and requests a diagnostic
+//! report with an LED self-test (very long press).
+//!
+//! # Low Battery Warning
+//!
+//! With the default battery selection policy, while on the backup battery
+//! or when the active battery is estimated to be nearly empty, the red
+//! string double-blinks once a minute on top of the running pattern.
 //!
 //! # Module Organization
 //!
+//! - [`activity`] - Wake-up and active time accounting
+//! - [`activity_meter`] - Duty cycle statistics from timed active stretches
+//! - [`button`] - Push-button task
+//! - [`gesture`] - Push-button gesture decoding
 //! - [`power`] - Dual-battery management and PVD monitoring
-//! - [`string_controller`] - LED flip-flop control and pattern state machine
+//! - [`power_policy`] - Battery selection policies
+//! - [`power_state`] - Active battery slot
+//! - [`pvd`] - Programmable Voltage Detector driver
+//! - [`droop`] - Confirmation of PVD low-voltage events
+//! - [`clock`] - System clock boosts for bursty work
+//! - [`fuel_gauge`] - Modeled per-battery charge estimate
+//! - [`battery_stats`] - Lifetime battery statistics and replacement detection
+//! - [`checksum`] - Check words and CRCs protecting EEPROM records
+//! - [`vdd_monitor`] - Supply voltage measurement via VREFINT
+//! - [`diagnostics`] - Diagnostic reports over RTT
+//! - [`system_state`] - Shared state published between tasks
+//! - [`string_controller`] - LED task and pattern engine
+//! - [`pattern`] - LED display patterns
+//! - [`step_schedule`] - Drift-free timing of pattern steps
+//! - [`led_backend`] - LED string frames and the backend trait
+//! - `flip_flop` - LED backend with one D flip-flop per string
+//! - `shift_register` - LED backend with a 74HC595 chain
+//! - `charlieplex` / `charlieplex_scan` - LED backend, scan task and pin
+//!   tables for charlieplexed LEDs
+//! - `sync` / `sync_protocol` - Pattern synchronization between ornaments
+//!   and its framing and phase lock
+//! - `rgb_controller` / `rgb` - RGB pattern engine and frame renderer for
+//!   addressable LEDs
+//! - `ws2812` / `ws2812_encoder` - Driver and bit encoder for WS2812 LEDs
+//! - [`twinkle`] / [`prng`] - Random twinkle pattern and its PRNG
+//! - [`light_sensor`] / `sensor_led` - Day/night detection from LED decay times
+//! - `morse` / `morse_message` - Morse code message pattern and its EEPROM
+//!   record
+//! - `show` - Light show playback from a compiled light sequence
+//! - [`eeprom`] - Persistent storage in data EEPROM
+//! - [`event_log`] - Persistent event log ring in data EEPROM
+//! - [`kv_store`] - Wear-leveling key-value store on `embedded-storage`
 //! - [`hardware`] - Pin mappings and peripheral initialization
 
 #![no_std]
 #![no_main]
 
+mod activity;
+mod activity_meter;
+mod battery_stats;
+mod button;
+#[cfg(led_backend = "charlieplex")]
+mod charlieplex;
+#[cfg(led_backend = "charlieplex")]
+mod charlieplex_scan;
+mod checksum;
+mod clock;
+mod diagnostics;
+mod droop;
+mod eeprom;
+mod event_log;
+#[cfg(led_backend = "flip_flop")]
+mod flip_flop;
+mod fuel_gauge;
+mod gesture;
 mod hardware;
+#[expect(dead_code, reason = "Not used by the firmware yet")]
+mod kv_store;
+mod led_backend;
+mod light_sensor;
+#[cfg(not(led_backend = "rgb"))]
+mod morse;
+#[cfg(not(led_backend = "rgb"))]
+mod morse_message;
+mod pattern;
 mod power;
+mod power_policy;
+mod power_state;
+mod prng;
+mod pvd;
+#[cfg(led_backend = "rgb")]
+mod rgb;
+#[cfg(led_backend = "rgb")]
+mod rgb_controller;
+#[cfg(light_sensor)]
+mod sensor_led;
+#[cfg(led_backend = "shift_register")]
+mod shift_register;
+#[cfg(not(led_backend = "rgb"))]
+mod show;
+mod step_schedule;
 mod string_controller;
+#[cfg(sync_link)]
+mod sync;
+#[cfg(sync_link)]
+mod sync_protocol;
+mod system_state;
+mod twinkle;
+mod vdd_monitor;
+#[cfg(led_backend = "rgb")]
+mod ws2812;
+#[cfg(led_backend = "rgb")]
+mod ws2812_encoder;
 
 use embassy_executor::Spawner;
 use embassy_stm32::{
@@ -53,20 +159,18 @@ use embassy_stm32::{
 use embassy_time::Timer;
 use {defmt_rtt as _, panic_probe as _};
 
+use button::button_task;
 use hardware::Peripherals;
-use power::{power_monitor_task, setup_pvd};
-
-/// LED pattern cycle time in seconds.
-///
-/// The LED strings alternate between red and green at this interval.
-/// Each string is ON for this duration, then OFF for this duration.
-const LED_CYCLE_SECS: u64 = 1;
+use power::power_monitor_task;
+use string_controller::{LED_COMMANDS, LedCommand, LedEngine, led_task};
 
 /// Creates a low-power clock configuration for STM32L031.
 ///
 /// # Clock Settings
 ///
-/// - **MSI**: 66 kHz in normal mode, 2.097 MHz in debug mode (for reliable debugging)
+/// - **MSI**: 66 kHz in normal mode, 2.097 MHz in debug mode (for reliable debugging),
+///   4.194 MHz on RGB boards (the WS2812 bit stream needs a 2 MHz SPI clock).
+///   In normal mode [`clock::boost`] raises it temporarily for bursty work.
 /// - **System clock**: MSI (no PLL)
 /// - **LSE**: 32.768 kHz external crystal for RTC
 /// - **Voltage scale**: Range 1 (1.8V core for low power)
@@ -80,9 +184,11 @@ const LED_CYCLE_SECS: u64 = 1;
 /// Configured RCC settings for embassy-stm32 initialization
 fn create_low_power_config() -> embassy_stm32::rcc::Config {
     embassy_stm32::rcc::Config {
-        #[cfg(feature = "debug-mode")]
+        #[cfg(led_backend = "rgb")]
+        msi: Some(embassy_stm32::rcc::MSIRange::RANGE4M),
+        #[cfg(all(feature = "debug-mode", not(led_backend = "rgb")))]
         msi: Some(embassy_stm32::rcc::MSIRange::RANGE2M),
-        #[cfg(not(feature = "debug-mode"))]
+        #[cfg(all(not(feature = "debug-mode"), not(led_backend = "rgb")))]
         msi: Some(embassy_stm32::rcc::MSIRange::RANGE66K),
         hsi: false,
         hse: None,
@@ -110,24 +216,26 @@ fn create_low_power_config() -> embassy_stm32::rcc::Config {
 ///
 /// 1. Configure clocks for low power operation (66 kHz MSI)
 /// 2. Initialize STM32 peripherals
-/// 3. Setup PVD for battery voltage monitoring
-/// 4. Initialize GPIO and controllers
-/// 5. Activate main battery and reset LED state
-/// 6. Spawn background task for power monitoring
-/// 7. Enter main loop alternating LED pattern
-///
-/// # Main Loop
+/// 3. Initialize GPIO and controllers, including the PVD for battery
+///    voltage monitoring
+/// 4. Activate main battery and reset LED state
+/// 5. Spawn background tasks for power monitoring, LED control and the
+///    push-button
+/// 6. Run the LED self-test so every string is seen working at power-on
 ///
-/// The main loop cycles through LED states at intervals defined by LED_CYCLE_SECS:
-/// - GreenOff → Red ON → Red OFF → Green ON → (repeat)
-///
-/// Between state changes, the MCU enters STOP mode automatically,
-/// waking only when the RTC timer expires or PVD triggers.
+/// Once initialization is complete, `main` returns and the executor keeps
+/// running the spawned tasks. Between task wake-ups, the MCU enters STOP
+/// mode automatically, waking only when an RTC timer expires or PVD
+/// triggers.
 ///
 /// # Spawned Tasks
 ///
-/// - **power_monitor_task**: Handles battery switching on PVD events
-#[embassy_executor::main]
+/// - **power_monitor_task**: Handles battery switching on PVD events and
+///   keeps the battery fuel gauge and statistics up to date
+/// - **led_task**: Steps the LED pattern and executes LED commands
+/// - **button_task**: Decodes push-button gestures into LED commands and
+///   diagnostic requests
+#[embassy_executor::main(executor = "crate::activity::Executor")]
 async fn main(spawner: Spawner) {
     let mut config = Config::default();
     config.rcc = create_low_power_config();
@@ -144,11 +252,6 @@ async fn main(spawner: Spawner) {
 
     Timer::after_secs(3).await;
 
-    #[cfg(feature = "debug-mode")]
-    defmt::info!("Setting up PVD...");
-
-    setup_pvd();
-
     #[cfg(feature = "debug-mode")]
     defmt::info!("Initializing peripherals...");
 
@@ -162,27 +265,41 @@ async fn main(spawner: Spawner) {
     #[cfg(feature = "debug-mode")]
     defmt::info!("Resetting LED controllers...");
 
-    peripherals.str_ctrl.reset();
+    peripherals.led_ctrl.reset();
+
+    #[cfg(not(led_backend = "rgb"))]
+    peripherals
+        .led_ctrl
+        .set_message(morse_message::load_configured(&mut peripherals.eeprom));
 
     #[cfg(feature = "debug-mode")]
     defmt::info!("Spawning power monitor task...");
 
     spawner
-        .spawn(power_monitor_task(peripherals.pwr_ctrl))
+        .spawn(power_monitor_task(peripherals.pwr_ctrl, peripherals.eeprom))
         .unwrap();
 
     #[cfg(feature = "debug-mode")]
-    defmt::info!("Entering main LED cycle loop...");
+    defmt::info!("Spawning LED task...");
 
-    loop {
-        #[cfg(feature = "debug-mode")]
-        defmt::info!("Activating next string...");
+    spawner
+        .spawn(led_task(peripherals.led_ctrl, peripherals.light_sensor))
+        .unwrap();
 
-        peripherals.str_ctrl.activate_next_string();
+    #[cfg(led_backend = "charlieplex")]
+    spawner
+        .spawn(charlieplex::charlieplex_task(peripherals.charlieplex_pins))
+        .unwrap();
 
+    #[cfg(sync_link)]
+    spawner.spawn(sync::sync_task(peripherals.sync)).unwrap();
+
+    if let Some(button) = peripherals.button {
         #[cfg(feature = "debug-mode")]
-        defmt::info!("Sleeping for {} seconds", LED_CYCLE_SECS);
+        defmt::info!("Spawning button task...");
 
-        Timer::after_secs(LED_CYCLE_SECS).await;
+        spawner.spawn(button_task(button)).unwrap();
     }
+
+    LED_COMMANDS.send(LedCommand::SelfTest).await;
 }
@@ -0,0 +1,322 @@
+//! Morse code message playback.
+//!
+//! A short message (a name, "MERRY XMAS") is played in Morse on the first
+//! LED string, with the second string lit during the gaps between words as
+//! a word marker. The message and its speed are stored in EEPROM.
+//!
+//! # Timing
+//!
+//! Standard Morse timing in units of one dit, with `1200 / wpm` ms per unit
+//! (the PARIS convention):
+//!
+//! | Element          | Units | Strings          |
+//! |------------------|-------|------------------|
+//! | Dit              | 1     | First on         |
+//! | Dah              | 3     | First on         |
+//! | Gap in a letter  | 1     | Off              |
+//! | Gap after letter | 3     | Off              |
+//! | Gap after word   | 7     | Second on        |
+//! | End of message   | 14    | Second on        |
+//!
+//! [`Cursor`] turns the message into one [`Step`] per unit, which the
+//! pattern engine shows for one unit each.
+//!
+//! # Encoding
+//!
+//! Letters are case-insensitive. Characters without a Morse code are
+//! treated as spaces. Each code is packed into a byte, first symbol in bit 0
+//! (0 = dit, 1 = dah), with a 1 bit above the last symbol as a terminator.
+//!
+//! # EEPROM Record
+//!
+//! A [`Message`] is kept in EEPROM as a [`RECORD_SIZE`] byte record, read
+//! and written by `morse_message`.
+
+use crate::checksum::check_word;
+
+/// Longest message stored, in characters.
+pub const MAX_MESSAGE_LEN: usize = 32;
+
+/// Message played without a valid EEPROM record.
+pub const DEFAULT_MESSAGE: &[u8] = b"MERRY XMAS";
+
+/// Speed of the default message, in words per minute.
+pub const DEFAULT_WPM: u8 = 6;
+
+/// Slowest speed accepted, in words per minute.
+pub const MIN_WPM: u8 = 2;
+
+/// Fastest speed accepted, in words per minute.
+pub const MAX_WPM: u8 = 20;
+
+/// Gap after a letter, in units.
+const LETTER_GAP_UNITS: u8 = 3;
+
+/// Gap after a word, in units.
+const WORD_GAP_UNITS: u8 = 7;
+
+/// Gap after the whole message before it repeats, in units.
+const MESSAGE_GAP_UNITS: u8 = 14;
+
+/// Size of the serialized message record in bytes.
+pub const RECORD_SIZE: usize = 44;
+
+/// Marks a valid message record in EEPROM ("MRSE").
+const RECORD_MAGIC: u32 = 0x4D52_5345;
+
+/// Packs a code written with `.` and `-` (see the module documentation).
+const fn pack(code: &str) -> u8 {
+    let symbols = code.as_bytes();
+    assert!(symbols.len() < 8, "Morse code too long to pack");
+
+    let mut packed = 1 << symbols.len();
+    let mut i = 0;
+    while i < symbols.len() {
+        if symbols[i] == b'-' {
+            packed |= 1 << i;
+        }
+        i += 1;
+    }
+    packed
+}
+
+/// Codes of the letters A to Z.
+const LETTERS: [u8; 26] = [
+    pack(".-"),   // A
+    pack("-..."), // B
+    pack("-.-."), // C
+    pack("-.."),  // D
+    pack("."),    // E
+    pack("..-."), // F
+    pack("--."),  // G
+    pack("...."), // H
+    pack(".."),   // I
+    pack(".---"), // J
+    pack("-.-"),  // K
+    pack(".-.."), // L
+    pack("--"),   // M
+    pack("-."),   // N
+    pack("---"),  // O
+    pack(".--."), // P
+    pack("--.-"), // Q
+    pack(".-."),  // R
+    pack("..."),  // S
+    pack("-"),    // T
+    pack("..-"),  // U
+    pack("...-"), // V
+    pack(".--"),  // W
+    pack("-..-"), // X
+    pack("-.--"), // Y
+    pack("--.."), // Z
+];
+
+/// Codes of the digits 0 to 9.
+const DIGITS: [u8; 10] = [
+    pack("-----"), // 0
+    pack(".----"), // 1
+    pack("..---"), // 2
+    pack("...--"), // 3
+    pack("....-"), // 4
+    pack("....."), // 5
+    pack("-...."), // 6
+    pack("--..."), // 7
+    pack("---.."), // 8
+    pack("----."), // 9
+];
+
+/// Codes of the punctuation marks.
+const PUNCTUATION: [(u8, u8); 10] = [
+    (b'.', pack(".-.-.-")),
+    (b',', pack("--..--")),
+    (b'?', pack("..--..")),
+    (b'\'', pack(".----.")),
+    (b'!', pack("-.-.--")),
+    (b'/', pack("-..-.")),
+    (b'&', pack(".-...")),
+    (b'=', pack("-...-")),
+    (b'+', pack(".-.-.")),
+    (b'-', pack("-....-")),
+];
+
+/// Returns the packed Morse code of an ASCII character, if it has one.
+pub fn code(c: u8) -> Option<u8> {
+    match c.to_ascii_uppercase() {
+        c @ b'A'..=b'Z' => Some(LETTERS[usize::from(c - b'A')]),
+        c @ b'0'..=b'9' => Some(DIGITS[usize::from(c - b'0')]),
+        c => PUNCTUATION
+            .iter()
+            .find(|(mark, _)| *mark == c)
+            .map(|(_, code)| *code),
+    }
+}
+
+/// State of the LED strings for one Morse unit.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum Step {
+    /// Part of a dit or dah: first string on
+    Mark,
+    /// Gap within a letter or between letters: all off
+    Space,
+    /// Gap between words or before the message repeats: second string on
+    WordGap,
+}
+
+/// Position in a message being played, one unit at a time.
+pub struct Cursor {
+    /// Index of the character being played
+    index: usize,
+    /// Symbols of the character still to play, packed as by [`code`]
+    code: u8,
+    /// Element being played
+    step: Step,
+    /// Units of the element still to play
+    left: u8,
+}
+
+impl Cursor {
+    /// Creates a cursor at the start of a message.
+    pub const fn new() -> Self {
+        Self {
+            index: 0,
+            code: 1,
+            step: Step::Space,
+            left: 0,
+        }
+    }
+
+    /// Returns the next unit of the message, repeating it endlessly.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Message being played, the same on every call
+    pub fn next(&mut self, text: &[u8]) -> Step {
+        while self.left == 0 {
+            self.load(text);
+        }
+        self.left -= 1;
+        self.step
+    }
+
+    /// Moves on to the next element of the message.
+    fn load(&mut self, text: &[u8]) {
+        if self.step == Step::Mark {
+            if self.code > 1 {
+                self.set(Step::Space, 1);
+                return;
+            }
+
+            self.index += 1;
+            match text.get(self.index) {
+                None => self.set(Step::WordGap, MESSAGE_GAP_UNITS),
+                Some(&c) if code(c).is_none() => self.set(Step::WordGap, WORD_GAP_UNITS),
+                Some(_) => self.set(Step::Space, LETTER_GAP_UNITS),
+            }
+            return;
+        }
+
+        if self.code <= 1 {
+            let next = (0..text.len())
+                .map(|offset| (self.index + offset) % text.len())
+                .find_map(|index| code(text[index]).map(|code| (index, code)));
+            let Some((index, code)) = next else {
+                // Nothing to play: a lit word marker
+                self.set(Step::WordGap, MESSAGE_GAP_UNITS);
+                return;
+            };
+            self.index = index;
+            self.code = code;
+        }
+
+        let dah = self.code & 1 != 0;
+        self.code >>= 1;
+        self.set(Step::Mark, if dah { 3 } else { 1 });
+    }
+
+    /// Starts playing an element.
+    fn set(&mut self, step: Step, units: u8) {
+        self.step = step;
+        self.left = units;
+    }
+}
+
+/// Message and speed of the Morse pattern.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Message {
+    /// ASCII text, `len` bytes used
+    text: [u8; MAX_MESSAGE_LEN],
+    /// Length of the text
+    len: u8,
+    /// Speed in words per minute
+    wpm: u8,
+}
+
+impl Message {
+    /// Creates a message, truncating the text to [`MAX_MESSAGE_LEN`] and
+    /// clamping the speed to [`MIN_WPM`]..=[`MAX_WPM`].
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - ASCII text of the message
+    /// * `wpm` - Speed in words per minute
+    pub fn new(text: &[u8], wpm: u8) -> Self {
+        let len = text.len().min(MAX_MESSAGE_LEN);
+        let mut buffer = [0; MAX_MESSAGE_LEN];
+        buffer[..len].copy_from_slice(&text[..len]);
+        Self {
+            text: buffer,
+            len: len as u8,
+            wpm: wpm.clamp(MIN_WPM, MAX_WPM),
+        }
+    }
+
+    /// Returns the text of the message.
+    pub fn text(&self) -> &[u8] {
+        &self.text[..usize::from(self.len)]
+    }
+
+    /// Returns the duration of one Morse unit, in milliseconds.
+    pub fn unit_ms(&self) -> u32 {
+        1200 / u32::from(self.wpm)
+    }
+
+    /// Serializes the message for storage in EEPROM.
+    ///
+    /// Layout: magic (little-endian), speed, length, two reserved bytes,
+    /// text padded with zeros, then check word.
+    pub fn to_record(&self) -> [u8; RECORD_SIZE] {
+        let mut record = [0u8; RECORD_SIZE];
+        record[0..4].copy_from_slice(&RECORD_MAGIC.to_le_bytes());
+        record[4] = self.wpm;
+        record[5] = self.len;
+        record[8..40].copy_from_slice(&self.text);
+        let check = check_word(RECORD_MAGIC, &record[..40]);
+        record[40..44].copy_from_slice(&check.to_le_bytes());
+        record
+    }
+
+    /// Restores a message from an EEPROM record.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the record is blank or corrupted
+    pub fn from_record(record: &[u8; RECORD_SIZE]) -> Option<Self> {
+        let u32_at =
+            |i: usize| u32::from_le_bytes([record[i], record[i + 1], record[i + 2], record[i + 3]]);
+
+        if u32_at(0) != RECORD_MAGIC || u32_at(40) != check_word(RECORD_MAGIC, &record[..40]) {
+            return None;
+        }
+
+        let len = usize::from(record[5]);
+        if len > MAX_MESSAGE_LEN {
+            return None;
+        }
+        Some(Self::new(&record[8..8 + len], record[4]))
+    }
+}
+
+impl Default for Message {
+    fn default() -> Self {
+        Self::new(DEFAULT_MESSAGE, DEFAULT_WPM)
+    }
+}
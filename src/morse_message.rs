@@ -0,0 +1,85 @@
+//! Morse message record in EEPROM.
+//!
+//! The message played by the Message pattern (see [`crate::morse`]) is a
+//! [`RECORD_SIZE`] byte record at [`MESSAGE_OFFSET`], read at boot with
+//! [`load_configured`]. Without a valid record the ornament plays
+//! [`morse::DEFAULT_MESSAGE`](crate::morse::DEFAULT_MESSAGE).
+//!
+//! # Configuration
+//!
+//! There is no configuration protocol on the ornament, so the message is
+//! set when the firmware is built: `build.rs` turns the `ORNAMENT_MESSAGE`
+//! and `ORNAMENT_MESSAGE_WPM` environment variables into
+//! [`CONFIGURED_MESSAGE`]. At boot a configured message that differs from
+//! the stored one is written to EEPROM, where it stays when later firmware
+//! built without a message is flashed.
+
+use embedded_storage::{ReadStorage, Storage};
+
+use crate::clock;
+use crate::eeprom::MESSAGE_OFFSET;
+use crate::morse::{Message, RECORD_SIZE};
+
+include!(concat!(env!("OUT_DIR"), "/message.rs"));
+
+/// Reads the stored message.
+///
+/// # Arguments
+///
+/// * `storage` - EEPROM holding the message record
+///
+/// # Returns
+///
+/// The stored message, or the default one if there is no valid record
+pub fn load<S: ReadStorage>(storage: &mut S) -> Message {
+    let mut record = [0u8; RECORD_SIZE];
+    if storage.read(MESSAGE_OFFSET, &mut record).is_ok()
+        && let Some(message) = Message::from_record(&record)
+    {
+        return message;
+    }
+
+    #[cfg(feature = "debug-mode")]
+    defmt::warn!("No valid message record, playing the default message");
+
+    Message::default()
+}
+
+/// Reads the stored message, first storing [`CONFIGURED_MESSAGE`] if it is
+/// set and differs from the stored one.
+///
+/// # Arguments
+///
+/// * `storage` - EEPROM holding the message record
+///
+/// # Returns
+///
+/// The message to play
+pub fn load_configured<S: Storage>(storage: &mut S) -> Message {
+    let Some((text, wpm)) = CONFIGURED_MESSAGE else {
+        return load(storage);
+    };
+
+    let configured = Message::new(text, wpm);
+    if load(storage) != configured {
+        #[cfg(feature = "debug-mode")]
+        defmt::info!("Storing the configured message");
+
+        let _boost = clock::boost();
+        if store(storage, &configured).is_err() {
+            #[cfg(feature = "debug-mode")]
+            defmt::error!("Failed to store the configured message");
+        }
+    }
+    configured
+}
+
+/// Stores a message, to be played from the next boot.
+///
+/// # Arguments
+///
+/// * `storage` - EEPROM holding the message record
+/// * `message` - Text and speed to store
+pub fn store<S: Storage>(storage: &mut S, message: &Message) -> Result<(), S::Error> {
+    storage.write(MESSAGE_OFFSET, &message.to_record())
+}
@@ -0,0 +1,109 @@
+//! LED display patterns.
+//!
+//! A [`Pattern`] is the user-facing pattern selection, shared by every LED
+//! engine. The fixed patterns are sequences of [`Frame`]s, defined for any
+//! number of strings; the engines generate the frames of the others
+//! themselves.
+//!
+//! Patterns have no hardware or timer dependencies, so they can be
+//! exercised on the host.
+
+use crate::led_backend::Frame;
+
+/// LED display pattern.
+///
+/// Patterns are defined for any number of strings, so the same pattern
+/// runs on every board variant.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, defmt::Format)]
+pub enum Pattern {
+    /// Each string in turn with a dark gap between (original ornament
+    /// pattern: red on, both off, green on, both off)
+    #[default]
+    Alternate,
+    /// Each string in turn with no dark gap
+    Swap,
+    /// All strings blink together
+    Together,
+    /// Each string switches on and off at random, within duty bounds (see
+    /// [`crate::twinkle`])
+    Twinkle,
+    /// The stored message in Morse code on the first string, with the
+    /// second string lit between words (see [`crate::morse`])
+    #[cfg(not(led_backend = "rgb"))]
+    Message,
+    /// The light show compiled into the firmware, from its start (see
+    /// [`crate::show`])
+    #[cfg(not(led_backend = "rgb"))]
+    Show,
+}
+
+impl Pattern {
+    /// Returns the number of frames in one cycle of the pattern.
+    ///
+    /// Twinkle, Message and Show are not fixed sequences and count as a
+    /// single frame; the engines generate their frames themselves.
+    ///
+    /// # Arguments
+    ///
+    /// * `strings` - Number of LED strings on the board
+    pub fn len(&self, strings: usize) -> usize {
+        match self {
+            Pattern::Alternate => 2 * strings,
+            Pattern::Swap => strings,
+            Pattern::Together => 2,
+            Pattern::Twinkle => 1,
+            #[cfg(not(led_backend = "rgb"))]
+            Pattern::Message | Pattern::Show => 1,
+        }
+    }
+
+    /// Returns frame `step` of the pattern (all off for Twinkle, Message
+    /// and Show).
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - Frame index, less than [`Self::len`]
+    /// * `strings` - Number of LED strings on the board
+    pub fn frame(&self, step: usize, strings: usize) -> Frame {
+        match self {
+            Pattern::Alternate if step.is_multiple_of(2) => Frame::single(step / 2),
+            Pattern::Swap => Frame::single(step),
+            Pattern::Together if step == 0 => Frame::all(strings),
+            _ => Frame::OFF,
+        }
+    }
+
+    /// Returns the pattern after this one, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            Pattern::Alternate => Pattern::Swap,
+            Pattern::Swap => Pattern::Together,
+            Pattern::Together => Pattern::Twinkle,
+            #[cfg(led_backend = "rgb")]
+            Pattern::Twinkle => Pattern::Alternate,
+            #[cfg(not(led_backend = "rgb"))]
+            Pattern::Twinkle => Pattern::Message,
+            #[cfg(not(led_backend = "rgb"))]
+            Pattern::Message => Pattern::Show,
+            #[cfg(not(led_backend = "rgb"))]
+            Pattern::Show => Pattern::Alternate,
+        }
+    }
+
+    /// Returns the pattern before this one, wrapping around.
+    pub fn previous(self) -> Self {
+        match self {
+            #[cfg(led_backend = "rgb")]
+            Pattern::Alternate => Pattern::Twinkle,
+            #[cfg(not(led_backend = "rgb"))]
+            Pattern::Alternate => Pattern::Show,
+            Pattern::Swap => Pattern::Alternate,
+            Pattern::Together => Pattern::Swap,
+            Pattern::Twinkle => Pattern::Together,
+            #[cfg(not(led_backend = "rgb"))]
+            Pattern::Message => Pattern::Twinkle,
+            #[cfg(not(led_backend = "rgb"))]
+            Pattern::Show => Pattern::Message,
+        }
+    }
+}
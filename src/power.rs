@@ -16,34 +16,92 @@
 //! # PVD Operation
 //!
 //! The PVD monitors VDD and triggers EXTI line 16 when voltage crosses the
-//! threshold (2.7V). This wakes the MCU from STOP mode and signals the
-//! power monitor task to switch power sources.
-
-use embassy_stm32::{gpio::Output, pac};
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
-use pac::interrupt;
-
-/// EXTI line number for PVD interrupt (fixed at line 16 on STM32)
-const PVD_EXTI_LINE: usize = 16;
-
-/// IMR register index for EXTI line 16 (lines 0-31 are in IMR1)
-const IMR1_REG_IDX: usize = 0;
-
-/// Power source state for tracking active battery
-#[derive(Default)]
-pub enum PowerState {
-    /// Main battery (BT1) is active
-    #[default]
-    MainPower,
-    /// Backup battery (BT2) is active
-    BackupPower,
-}
+//! threshold (2.7V). This wakes the MCU from STOP mode, and the power
+//! monitor task, waiting on the [`crate::pvd`] driver, switches power
+//! sources.
+//!
+//! # Fuel Gauge
+//!
+//! The power monitor task also wakes periodically to charge the modeled
+//! load to the active battery (see [`crate::fuel_gauge`]). The LED task
+//! reports how many strings are lit through [`set_led_load`], and the
+//! estimate is persisted to EEPROM so it survives a reset.
+//!
+//! # Battery Replacement
+//!
+//! On each periodic wake VDD is measured through the ADC (see
+//! [`crate::vdd_monitor`]). A jump in VDD on the active slot indicates a
+//! fresh cell, which resets that slot's fuel gauge estimate and is counted
+//! in the lifetime statistics (see [`crate::battery_stats`]).
+//!
+//! # Battery Selection
+//!
+//! What happens when a cell fails, and whether the cells are also switched
+//! on a schedule, is up to the configured [`crate::power_policy`]. By
+//! default the main cell runs until it fails, then the backup.
+//!
+//! # Event Log
+//!
+//! The power monitor task owns the EEPROM, so it writes the events queued
+//! by every task to the event log (see [`crate::event_log`]) after each
+//! wake, including its own: boots, battery switches, rejected droops,
+//! storage faults and running out of batteries.
+//!
+//! # Low Battery Warning
+//!
+//! When the policy requires it (by default while running on the backup
+//! battery, or when the fuel gauge estimates the active battery is nearly
+//! empty), the power monitor task raises the low battery warning in the
+//! shared [`crate::system_state`]. The LED loop plays a warning overlay on
+//! top of the running pattern until the warning is cleared.
+
+use core::cell::Cell;
+
+use embassy_futures::select::{Either3, select3};
+use embassy_stm32::{Peri, gpio::Output, peripherals::PWR};
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_storage::{ReadStorage, Storage};
+
+use crate::activity;
+use crate::activity_meter::Task;
+use crate::battery_stats::{self, BatteryStats};
+use crate::clock;
+use crate::diagnostics;
+use crate::droop::{self, DroopFilter, Reading, Verdict};
+use crate::eeprom::{BATTERY_STATS_OFFSET, EVENT_LOG_OFFSET, Eeprom, FUEL_GAUGE_OFFSET};
+use crate::event_log::{self, EventLog, Kind};
+use crate::fuel_gauge::{self, FuelGauge};
+use crate::hardware::{self, BatteryPolicy, Irqs};
+use crate::power_policy::{Action, PowerPolicy};
+use crate::power_state::PowerState;
+use crate::pvd::{self, Pvd, VoltageEvent};
+use crate::string_controller::{self, LED_COMMANDS, LedCommand, Overlay};
+use crate::system_state::{self, Fault};
+use crate::vdd_monitor::VddMonitor;
+
+/// PVD threshold and edges: VDD falling below 2.7V triggers a battery
+/// switch, VDD recovering is only logged.
+const PVD_CONFIG: pvd::Config = pvd::Config {
+    level: pvd::Level::V2_7,
+    edges: pvd::Edges::Both,
+};
 
-/// Controller for dual-battery power management with automatic failover.
+/// Interval between fuel gauge updates when no PVD event occurs.
+const FUEL_GAUGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Interval between fuel gauge writes to EEPROM.
+///
+/// Hourly writes keep EEPROM wear far below its rated endurance over the
+/// lifetime of a pair of coin cells.
+const FUEL_GAUGE_PERSIST_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Controller for dual-battery power management.
 ///
 /// Controls two active-low load switch enable signals to select between
-/// main and backup batteries. Ensures make-before-break switching to
-/// prevent power interruption during transitions.
+/// main and backup batteries, as decided by the configured
+/// [`PowerPolicy`]. Ensures make-before-break switching to prevent power
+/// interruption during transitions.
 pub struct PowerController {
     /// Active-low enable for main battery load switch (Q1)
     main_power_n: Output<'static>,
@@ -51,6 +109,20 @@ pub struct PowerController {
     backup_power_n: Output<'static>,
     /// Current active power source
     state: PowerState,
+    /// Estimated charge drawn from each battery
+    fuel_gauge: FuelGauge,
+    /// Lifetime usage statistics per battery slot
+    stats: BatteryStats,
+    /// Supply voltage measurement for replacement detection
+    vdd_monitor: VddMonitor,
+    /// Voltage detector signalling a failing battery
+    pvd: Pvd<'static>,
+    /// Confirmation of PVD low-voltage events
+    droops: DroopFilter,
+    /// Rejected droops already recorded in the event log
+    droops_logged: u32,
+    /// When to switch batteries and warn the user
+    policy: BatteryPolicy,
 }
 
 impl PowerController {
@@ -60,11 +132,27 @@ impl PowerController {
     ///
     /// * `main_power_n` - Active-low control for main battery load switch (PB1)
     /// * `backup_power_n` - Active-low control for backup battery load switch (PA8)
-    pub fn new(main_power_n: Output<'static>, backup_power_n: Output<'static>) -> Self {
+    /// * `vdd_monitor` - Supply voltage measurement for replacement detection
+    /// * `pwr` - Power controller peripheral, for the voltage detector
+    /// * `policy` - Battery selection policy
+    pub fn new(
+        main_power_n: Output<'static>,
+        backup_power_n: Output<'static>,
+        vdd_monitor: VddMonitor,
+        pwr: Peri<'static, PWR>,
+        policy: BatteryPolicy,
+    ) -> Self {
         Self {
             main_power_n,
             backup_power_n,
             state: PowerState::default(),
+            fuel_gauge: FuelGauge::default(),
+            stats: BatteryStats::default(),
+            vdd_monitor,
+            pvd: Pvd::new(pwr, Irqs, PVD_CONFIG),
+            droops: DroopFilter::new(),
+            droops_logged: 0,
+            policy,
         }
     }
 
@@ -75,33 +163,272 @@ impl PowerController {
     pub fn init_main_power(&mut self) {
         self.main_power_n.set_low();
         self.backup_power_n.set_high();
+
+        #[cfg(feature = "debug-mode")]
+        defmt::info!("PVD initialized, VDD is {}", self.pvd.state());
+    }
+
+    /// Checks that a PVD low-voltage event is not a transient droop.
+    ///
+    /// Re-reads the detector after a settle time and, if no LED string is
+    /// lit, measures VDD (see [`crate::droop`]).
+    ///
+    /// # Returns
+    ///
+    /// True if VDD stayed low, false if the event was rejected
+    pub async fn confirm_low_voltage(&mut self) -> bool {
+        self.droops.begin();
+
+        let mut verdict = Verdict::Pending;
+        while verdict == Verdict::Pending {
+            Timer::after(droop::SETTLE_TIME).await;
+            verdict = self
+                .droops
+                .update(Reading::PvdLow(self.pvd.state() == VoltageEvent::Low));
+        }
+
+        if verdict == Verdict::Confirmed && lit_strings() == 0 {
+            let vdd_mv = self.vdd_monitor.measure_mv().await;
+            verdict = self.droops.update(Reading::VddMv(vdd_mv));
+        }
+
+        #[cfg(feature = "debug-mode")]
+        if verdict == Verdict::Rejected {
+            defmt::info!(
+                "Transient droop rejected ({} so far)",
+                self.droops.rejected()
+            );
+        }
+
+        verdict == Verdict::Confirmed
+    }
+
+    /// Handles a confirmed low-voltage event.
+    ///
+    /// The active cell can no longer hold VDD, whatever the model says, so
+    /// it is marked empty before the policy is asked what to do.
+    ///
+    /// # Returns
+    ///
+    /// True if the policy wants to switch to the other battery
+    pub fn battery_failed(&mut self) -> bool {
+        self.fuel_gauge.mark_empty(&self.state);
+        let switch = self.policy.on_low_voltage(self.state) == Action::Switch;
+        if !switch {
+            event_log::record(Kind::Shutdown, 0);
+        }
+        switch
     }
 
-    /// Handles power source transitions based on voltage detector status.
+    /// Switches to the other battery.
+    ///
+    /// The LEDs are turned off for the changeover (see
+    /// [`string_controller::blackout`]), so the new cell does not take
+    /// their inrush while both load switches change state. VDD is then
+    /// checked through the PVD and the ADC; if it is still low, the new cell
+    /// is failing too and is marked empty as well.
+    pub async fn switch_battery(&mut self) {
+        let blackout = string_controller::blackout().await;
+
+        #[cfg(feature = "debug-mode")]
+        if blackout.is_none() {
+            defmt::warn!("LEDs not turned off in time, switching under load");
+        }
+
+        self.state = match &self.state {
+            PowerState::MainPower => {
+                self.switch_to_backup();
+                PowerState::BackupPower
+            }
+            PowerState::BackupPower => {
+                self.switch_to_main();
+                PowerState::MainPower
+            }
+        };
+        self.stats.record_switch(&self.state);
+        event_log::record(Kind::BatterySwitch, self.state.slot() as u8);
+
+        Timer::after(droop::SETTLE_TIME).await;
+        let vdd_mv = self.vdd_monitor.measure_mv().await;
+        let recovered = self.pvd.state() == VoltageEvent::Restored || vdd_mv >= droop::RECOVERED_MV;
+
+        #[cfg(feature = "debug-mode")]
+        defmt::warn!(
+            "Switched to slot {}: VDD {} mV, recovered: {}",
+            self.state.slot(),
+            vdd_mv,
+            recovered
+        );
+
+        if !recovered {
+            self.fuel_gauge.mark_empty(&self.state);
+        }
+
+        drop(blackout);
+    }
+
+    /// Charges elapsed time and modeled load current to the active battery.
+    ///
+    /// # Arguments
     ///
-    /// Called from the PVD interrupt handler via the power monitor task.
-    /// Toggles between main and backup power when voltage drops are detected.
+    /// * `elapsed_ms` - Time since the previous update
+    /// * `string_on_ms` - LED string on-time reported since the previous update
+    pub fn account(&mut self, elapsed_ms: u32, string_on_ms: u32) {
+        self.fuel_gauge.draw(&self.state, elapsed_ms, string_on_ms);
+        self.stats.record_active(&self.state, elapsed_ms);
+
+        #[cfg(feature = "debug-mode")]
+        defmt::info!(
+            "Fuel gauge: main={}% backup={}%",
+            self.fuel_gauge.percent_remaining(&PowerState::MainPower),
+            self.fuel_gauge.percent_remaining(&PowerState::BackupPower)
+        );
+    }
+
+    /// Asks the policy whether to switch batteries on a periodic update.
     ///
     /// # Arguments
     ///
-    /// * `low_voltage` - True if PVD detected voltage below threshold
-    pub fn power_transition(&mut self, low_voltage: bool) {
-        if low_voltage {
-            self.state = match &self.state {
-                PowerState::MainPower => {
-                    #[cfg(feature = "debug-mode")]
-                    defmt::warn!("Switching from MAIN battery to BACKUP battery");
-                    self.switch_to_backup();
-                    PowerState::BackupPower
-                }
-                PowerState::BackupPower => {
-                    #[cfg(feature = "debug-mode")]
-                    defmt::warn!("Switching from BACKUP battery to MAIN battery");
-                    self.switch_to_main();
-                    PowerState::MainPower
-                }
+    /// * `elapsed_ms` - Time since the previous update
+    ///
+    /// # Returns
+    ///
+    /// True if the policy wants to switch to the other battery
+    pub fn switch_due(&mut self, elapsed_ms: u32) -> bool {
+        let percent = self.battery_percent();
+        self.policy.on_update(self.state, elapsed_ms, percent) == Action::Switch
+    }
+
+    /// Returns the estimated charge remaining per battery slot, in percent.
+    fn battery_percent(&self) -> [u8; 2] {
+        [
+            self.fuel_gauge.percent_remaining(&PowerState::MainPower),
+            self.fuel_gauge.percent_remaining(&PowerState::BackupPower),
+        ]
+    }
+
+    /// Measures VDD and checks whether the active cell was replaced.
+    ///
+    /// A fresh cell resets the fuel gauge estimate for its slot.
+    ///
+    /// # Returns
+    ///
+    /// True if a replacement was detected
+    pub async fn check_for_fresh_cell(&mut self) -> bool {
+        let vdd_mv = self.vdd_monitor.measure_mv().await;
+
+        #[cfg(feature = "debug-mode")]
+        defmt::info!("VDD: {} mV", vdd_mv);
+
+        let replaced = self.stats.record_vdd(&self.state, vdd_mv);
+        if replaced {
+            #[cfg(feature = "debug-mode")]
+            defmt::warn!(
+                "Fresh cell detected in slot {} (>{} mV jump)",
+                self.state.slot(),
+                battery_stats::FRESH_CELL_JUMP_MV
+            );
+
+            self.fuel_gauge.reset(&self.state);
+        }
+
+        replaced
+    }
+
+    /// Restores the fuel gauge and battery statistics persisted in EEPROM.
+    ///
+    /// Leaves both cells at full and statistics at zero if no valid record
+    /// is found, which is the case on first boot with fresh batteries.
+    pub fn restore(&mut self, eeprom: &mut Eeprom) {
+        let mut gauge_record = [0u8; fuel_gauge::RECORD_SIZE];
+        if eeprom.read(FUEL_GAUGE_OFFSET, &mut gauge_record).is_ok() {
+            if let Some(gauge) = FuelGauge::from_record(&gauge_record) {
+                self.fuel_gauge = gauge;
+            } else {
+                #[cfg(feature = "debug-mode")]
+                defmt::warn!("No valid fuel gauge record, assuming full batteries");
             }
         }
+
+        let mut stats_record = [0u8; battery_stats::RECORD_SIZE];
+        if eeprom.read(BATTERY_STATS_OFFSET, &mut stats_record).is_ok() {
+            if let Some(stats) = BatteryStats::from_record(&stats_record) {
+                self.stats = stats;
+            } else {
+                #[cfg(feature = "debug-mode")]
+                defmt::warn!("No valid battery statistics record, starting from zero");
+            }
+        }
+    }
+
+    /// Writes the fuel gauge and battery statistics to EEPROM, with the
+    /// system clock boosted.
+    ///
+    /// Droops rejected since the previous write are recorded in the event
+    /// log as one event, so a weak cell glitching on every LED step does not
+    /// wear out the log.
+    pub fn persist(&mut self, eeprom: &mut Eeprom) {
+        let droops = self.droops.rejected() - self.droops_logged;
+        if droops > 0 {
+            event_log::record(Kind::Droop, droops.min(u8::MAX.into()) as u8);
+            self.droops_logged += droops;
+        }
+
+        let _boost = clock::boost();
+        let result = eeprom
+            .write(FUEL_GAUGE_OFFSET, &self.fuel_gauge.to_record())
+            .and_then(|()| eeprom.write(BATTERY_STATS_OFFSET, &self.stats.to_record()));
+
+        if let Err(_e) = result {
+            #[cfg(feature = "debug-mode")]
+            defmt::error!("Failed to persist battery state: {}", _e);
+        }
+
+        let fault = result.is_err().then_some(Fault::Storage);
+        if let Some(fault) = fault {
+            event_log::record(Kind::Fault, fault as u8);
+        }
+        system_state::update(|state| state.fault = fault);
+    }
+
+    /// Publishes the active battery and fuel gauge estimates to the shared
+    /// system state.
+    pub fn publish_state(&self) {
+        let power = self.state;
+        let battery_percent = self.battery_percent();
+        let low_battery_warning = self.policy.warning_required(power, battery_percent);
+
+        system_state::update(|state| {
+            state.power = power;
+            state.battery_percent = battery_percent;
+            state.low_battery_warning = low_battery_warning;
+        });
+    }
+
+    /// Limits the LEDs to the low battery warning if the policy requires it.
+    ///
+    /// The pattern is turned off and the warning overlay played on every
+    /// wake, so a button press cannot keep the LEDs lit from a cell kept
+    /// in reserve.
+    pub fn restrict_leds(&self) {
+        if self.policy.warning_only(self.state) {
+            let _ = LED_COMMANDS.try_send(LedCommand::AllOff);
+            let _ = LED_COMMANDS.try_send(LedCommand::Overlay(Overlay::LowBattery));
+        }
+    }
+
+    /// Reports battery estimates, statistics, MCU activity and the event
+    /// log on the diagnostic channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - Event log to list
+    /// * `eeprom` - EEPROM holding the event log
+    pub fn report_diagnostics(&self, events: &EventLog, eeprom: &mut Eeprom) {
+        diagnostics::report_batteries(&self.state, &self.fuel_gauge, &self.stats);
+        diagnostics::report_droops(self.droops.rejected());
+        diagnostics::report_activity();
+        diagnostics::report_events(events, eeprom);
     }
 
     /// Switches from main to backup battery using make-before-break.
@@ -121,126 +448,175 @@ impl PowerController {
     }
 }
 
-/// Static signal for communicating PVD events from interrupt to async task.
-///
-/// The PVD interrupt handler signals voltage status (true = low voltage)
-/// to the power monitor task waiting on this signal.
-static PVD_SIGNAL: Signal<CriticalSectionRawMutex, bool> = Signal::new();
-
-/// PVD interrupt handler (EXTI line 16).
-///
-/// Triggered when VDD crosses the configured threshold (2.7V).
-/// Clears the interrupt flag and signals the power monitor task
-/// with the current voltage status.
-///
-/// # Safety
-///
-/// This interrupt handler accesses PAC registers directly.
-#[interrupt]
-fn PVD() {
-    let exti = pac::EXTI;
-    let pwr = pac::PWR;
-
-    // Clear pending interrupt on EXTI line 16
-    exti.pr(IMR1_REG_IDX)
-        .modify(|w| w.set_line(PVD_EXTI_LINE, true));
-
-    // Check if voltage is below or above the threshold
-    let voltage_low = pwr.csr().read().pvdo();
+/// LED load reported by the LED task.
+#[derive(Clone, Copy)]
+struct LedLoad {
+    /// Number of LED strings currently lit
+    lit_strings: u32,
+    /// When `lit_strings` last changed or was last accounted
+    since: Instant,
+    /// String on-time accumulated since the last fuel gauge update, in ms
+    ///
+    /// Summed across strings, so two strings lit for one second add 2000.
+    string_on_ms: u32,
+}
 
-    #[cfg(feature = "debug-mode")]
-    {
-        if voltage_low {
-            defmt::warn!("PVD: Voltage dropped below 2.7V threshold!");
-        } else {
-            defmt::info!("PVD: Voltage returned above 2.7V threshold");
-        }
+impl LedLoad {
+    /// Adds on-time of the currently lit strings up to `now`.
+    fn accumulate(&mut self, now: Instant) {
+        let elapsed_ms = (now - self.since).as_millis() as u32;
+        self.string_on_ms = self
+            .string_on_ms
+            .saturating_add(self.lit_strings * elapsed_ms);
+        self.since = now;
     }
-
-    PVD_SIGNAL.signal(voltage_low);
 }
 
-/// Configures the Programmable Voltage Detector (PVD) and EXTI interrupt.
-///
-/// Sets up PVD to monitor VDD at 2.7V threshold and trigger EXTI line 16
-/// on both rising and falling edges. This allows the system to detect
-/// battery voltage drops and wake from STOP mode.
-///
-/// # Configuration
+/// Current LED load, shared between the LED task and the power monitor task.
+static LED_LOAD: Mutex<CriticalSectionRawMutex, Cell<LedLoad>> = Mutex::new(Cell::new(LedLoad {
+    lit_strings: 0,
+    since: Instant::from_ticks(0),
+    string_on_ms: 0,
+}));
+
+/// Reports LED load to the fuel gauge.
 ///
-/// - PVD threshold: 2.7V (adjustable via `Pls` enum)
-/// - EXTI line 16: Rising and falling edge triggers
-/// - NVIC: PVD interrupt unmasked
+/// Called by the LED task whenever the latched LED state may have changed.
+/// On-time is accounted exactly, however long the state is held.
 ///
-/// # Safety
+/// # Arguments
 ///
-/// Directly accesses PAC registers and unmasks NVIC interrupt.
-pub fn setup_pvd() {
-    // The PAC must be used to configure the PVD
-    let pwr = pac::PWR;
-    let exti = pac::EXTI;
-
-    // Enable the PWR clock
-    pac::RCC.apb1enr().modify(|w| w.set_pwren(true));
-
-    // Configure PVD level (2.7V threshold)
-    pwr.cr().modify(|w| w.set_pls(pac::pwr::vals::Pls::V2_7));
-    pwr.cr().modify(|w| w.set_pvde(true));
-
-    // Enable EXTI line 16 for PVD
-    exti.imr(IMR1_REG_IDX)
-        .modify(|w| w.set_line(PVD_EXTI_LINE, true));
-    exti.rtsr(IMR1_REG_IDX)
-        .modify(|w| w.set_line(PVD_EXTI_LINE, true));
-    exti.ftsr(IMR1_REG_IDX)
-        .modify(|w| w.set_line(PVD_EXTI_LINE, true));
-
-    // Enable PVD interrupt in the NVIC
-    unsafe {
-        cortex_m::peripheral::NVIC::unmask(embassy_stm32::interrupt::PVD);
-    };
+/// * `lit_strings` - Number of LED strings lit from now on
+pub fn set_led_load(lit_strings: u32) {
+    LED_LOAD.lock(|load| {
+        let mut value = load.get();
+        value.accumulate(Instant::now());
+        value.lit_strings = lit_strings;
+        load.set(value);
+    });
+}
 
-    #[cfg(feature = "debug-mode")]
-    {
-        // Read current voltage status
-        let voltage_low = pwr.csr().read().pvdo();
-        if voltage_low {
-            defmt::warn!("PVD initialized: Current voltage is BELOW 2.7V threshold");
-        } else {
-            defmt::info!("PVD initialized: Current voltage is ABOVE 2.7V threshold");
-        }
+/// Returns the number of LED strings lit.
+fn lit_strings() -> u32 {
+    LED_LOAD.lock(|load| load.get().lit_strings)
+}
+
+/// Returns LED string on-time since the previous call, in ms.
+fn take_string_on_ms() -> u32 {
+    LED_LOAD.lock(|load| {
+        let mut value = load.get();
+        value.accumulate(Instant::now());
+        let string_on_ms = core::mem::take(&mut value.string_on_ms);
+        load.set(value);
+        string_on_ms
+    })
+}
+
+/// Writes queued events to the event log, with the system clock boosted.
+fn write_events(events: &mut EventLog, eeprom: &mut Eeprom) {
+    while let Ok(entry) = event_log::EVENTS.try_receive() {
+        let _boost = clock::boost();
+        events.append(eeprom, entry).ok();
     }
 }
 
 /// Async task for monitoring power and handling battery switching.
 ///
-/// Waits for PVD interrupt signals and transitions between main and
-/// backup batteries based on voltage detector status. Runs continuously
-/// in the background.
+/// Waits for PVD events and transitions between main and
+/// backup batteries based on voltage detector status. Between PVD events
+/// it wakes every [`FUEL_GAUGE_INTERVAL`] to update the fuel gauge and check
+/// for a replaced cell. Battery state is persisted hourly, after every
+/// battery switch, and after every replacement. The battery state is
+/// published to the shared system state and queued events are written to
+/// the event log after every wake, and a diagnostic report is produced on
+/// request. Runs continuously in the background.
 ///
 /// # Arguments
 ///
 /// * `pwr_ctrl` - PowerController instance (takes ownership)
+/// * `eeprom` - EEPROM driver used to persist battery state (takes ownership)
 ///
 /// # Example
 ///
 /// ```no_run
-/// spawner.spawn(power_monitor_task(peripherals.pwr_ctrl)).unwrap();
+/// spawner.spawn(power_monitor_task(peripherals.pwr_ctrl, peripherals.eeprom)).unwrap();
 /// ```
 #[embassy_executor::task]
-pub async fn power_monitor_task(mut pwr_ctrl: PowerController) {
+pub async fn power_monitor_task(pwr_ctrl: PowerController, eeprom: Eeprom) {
+    activity::tracked(Task::Power, run_power_monitor(pwr_ctrl, eeprom)).await
+}
+
+/// Body of [`power_monitor_task`].
+async fn run_power_monitor(mut pwr_ctrl: PowerController, mut eeprom: Eeprom) {
     #[cfg(feature = "debug-mode")]
     defmt::info!("Power monitor task started, waiting for PVD events...");
 
+    pwr_ctrl.restore(&mut eeprom);
+    let mut events = EventLog::recover(&mut eeprom, EVENT_LOG_OFFSET);
+    event_log::record(Kind::Boot, hardware::take_reset_cause() as u8);
+
+    // Catch a cell swapped while the ornament was unpowered
+    if pwr_ctrl.check_for_fresh_cell().await {
+        pwr_ctrl.persist(&mut eeprom);
+    }
+
+    write_events(&mut events, &mut eeprom);
+    pwr_ctrl.report_diagnostics(&events, &mut eeprom);
+    pwr_ctrl.publish_state();
+
+    let mut last_update = Instant::now();
+    let mut last_persist = last_update;
+
     loop {
-        let voltage_low = PVD_SIGNAL.wait().await;
+        let event = select3(
+            pwr_ctrl.pvd.wait_for_change(),
+            Timer::after(FUEL_GAUGE_INTERVAL),
+            diagnostics::REPORT_REQUEST.wait(),
+        )
+        .await;
 
-        #[cfg(feature = "debug-mode")]
-        defmt::info!(
-            "Power monitor received PVD signal: voltage_low={}",
-            voltage_low
-        );
+        // Charge the elapsed period to the battery that supplied it,
+        // before any switch below changes the active battery.
+        let now = Instant::now();
+        let elapsed_ms = (now - last_update).as_millis() as u32;
+        pwr_ctrl.account(elapsed_ms, take_string_on_ms());
+        last_update = now;
+
+        let mut persist = now - last_persist >= FUEL_GAUGE_PERSIST_INTERVAL;
 
-        pwr_ctrl.power_transition(voltage_low);
+        let mut failed = false;
+        if let Either3::First(voltage) = event {
+            #[cfg(feature = "debug-mode")]
+            defmt::info!("Power monitor received PVD event: {}", voltage);
+
+            failed = voltage == VoltageEvent::Low && pwr_ctrl.confirm_low_voltage().await;
+        }
+
+        let switch = if failed {
+            persist = true;
+            pwr_ctrl.battery_failed()
+        } else {
+            pwr_ctrl.switch_due(elapsed_ms)
+        };
+        if switch {
+            pwr_ctrl.switch_battery().await;
+            persist = true;
+        }
+
+        // After a switch this is the first reading from the newly active slot
+        persist |= pwr_ctrl.check_for_fresh_cell().await;
+
+        if persist {
+            pwr_ctrl.persist(&mut eeprom);
+            last_persist = now;
+        }
+
+        pwr_ctrl.publish_state();
+        pwr_ctrl.restrict_leds();
+        write_events(&mut events, &mut eeprom);
+
+        if let Either3::Third(()) = event {
+            pwr_ctrl.report_diagnostics(&events, &mut eeprom);
+        }
     }
 }
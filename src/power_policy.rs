@@ -0,0 +1,172 @@
+//! Battery selection policies.
+//!
+//! The ornament has two coin cell slots, but not every deployment wants to
+//! use them the same way. A [`PowerPolicy`] decides when the power monitor
+//! task switches between them and how the user is warned:
+//!
+//! - [`Failover`]: run the main cell until it fails, then the backup; the
+//!   warning is raised while on backup. This is the default.
+//! - [`Balance`]: alternate the cells every few hours of use, so both age
+//!   together and can be replaced as a pair.
+//! - [`Reserve`]: keep the backup cell for the low battery warning alone.
+//!   Once the main cell fails the pattern is turned off and only the
+//!   warning is shown.
+//! - [`SingleCell`]: the backup slot is empty. Never switch; a failing cell
+//!   only raises the warning.
+//!
+//! # Configuration
+//!
+//! The policy is chosen at build time with the `ORNAMENT_POWER_POLICY`
+//! environment variable (`failover`, `balance:<hours>`, `reserve` or
+//! `single`). `build.rs` exports it as the `power_policy` cfg and generates
+//! the `hardware::BatteryPolicy` alias for the selected type, so only that
+//! policy is compiled in.
+//!
+//! Policies are pure state machines fed with elapsed time and fuel gauge
+//! estimates, so they can be exercised on the host.
+
+use crate::fuel_gauge::LOW_BATTERY_PERCENT;
+use crate::power_state::PowerState;
+
+/// What the power monitor task should do with the active battery.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Action {
+    /// Keep running on the active battery
+    Stay,
+    /// Switch to the other battery
+    Switch,
+}
+
+/// Strategy for selecting the battery that powers the ornament.
+pub trait PowerPolicy {
+    /// Decides what to do once the active battery is confirmed failing.
+    ///
+    /// The failing battery has already been marked empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `active` - Battery that failed
+    fn on_low_voltage(&mut self, active: PowerState) -> Action;
+
+    /// Decides whether to switch on a periodic fuel gauge update.
+    ///
+    /// # Arguments
+    ///
+    /// * `active` - Battery that supplied the elapsed period
+    /// * `elapsed_ms` - Time since the previous update
+    /// * `percent` - Estimated charge remaining per battery slot
+    fn on_update(&mut self, _active: PowerState, _elapsed_ms: u32, _percent: [u8; 2]) -> Action {
+        Action::Stay
+    }
+
+    /// Returns true if the user should be warned about the batteries.
+    ///
+    /// By default the warning is raised while on the backup battery, or
+    /// when the active battery is nearly empty.
+    fn warning_required(&self, active: PowerState, percent: [u8; 2]) -> bool {
+        active == PowerState::BackupPower || percent[active.slot()] <= LOW_BATTERY_PERCENT
+    }
+
+    /// Returns true if the LEDs may only show the low battery warning.
+    fn warning_only(&self, _active: PowerState) -> bool {
+        false
+    }
+}
+
+/// Runs the main battery until it fails, then the backup.
+#[cfg_attr(
+    not(power_policy = "failover"),
+    expect(dead_code, reason = "Not the configured policy")
+)]
+pub struct Failover;
+
+impl PowerPolicy for Failover {
+    fn on_low_voltage(&mut self, _active: PowerState) -> Action {
+        Action::Switch
+    }
+}
+
+/// Alternates the batteries after a fixed time of use each.
+///
+/// A cell that failed or is estimated nearly empty is not switched to, so
+/// once one cell is spent the other is run on its own.
+pub struct Balance {
+    /// Time each battery is used before switching, in ms
+    period_ms: u32,
+    /// Time the active battery has been used since the last switch, in ms
+    used_ms: u32,
+}
+
+#[cfg_attr(
+    not(power_policy = "balance"),
+    expect(dead_code, reason = "Not the configured policy")
+)]
+impl Balance {
+    /// Creates a policy switching every `hours` of use.
+    pub const fn new(hours: u32) -> Self {
+        Self {
+            period_ms: hours * 3_600_000,
+            used_ms: 0,
+        }
+    }
+}
+
+impl PowerPolicy for Balance {
+    fn on_low_voltage(&mut self, _active: PowerState) -> Action {
+        self.used_ms = 0;
+        Action::Switch
+    }
+
+    fn on_update(&mut self, active: PowerState, elapsed_ms: u32, percent: [u8; 2]) -> Action {
+        self.used_ms = self.used_ms.saturating_add(elapsed_ms);
+        let other = percent[1 - active.slot()];
+        if self.used_ms < self.period_ms || other <= LOW_BATTERY_PERCENT {
+            return Action::Stay;
+        }
+
+        self.used_ms = 0;
+        Action::Switch
+    }
+
+    /// Running on backup is routine, so the warning is only raised once
+    /// either cell is nearly empty.
+    fn warning_required(&self, _active: PowerState, percent: [u8; 2]) -> bool {
+        percent.iter().any(|&p| p <= LOW_BATTERY_PERCENT)
+    }
+}
+
+/// Keeps the backup battery for the low battery warning.
+#[cfg_attr(
+    not(power_policy = "reserve"),
+    expect(dead_code, reason = "Not the configured policy")
+)]
+pub struct Reserve;
+
+impl PowerPolicy for Reserve {
+    /// Switches from a failing main battery only: once the backup fails
+    /// too there is nothing left to switch to.
+    fn on_low_voltage(&mut self, active: PowerState) -> Action {
+        match active {
+            PowerState::MainPower => Action::Switch,
+            PowerState::BackupPower => Action::Stay,
+        }
+    }
+
+    fn warning_only(&self, active: PowerState) -> bool {
+        active == PowerState::BackupPower
+    }
+}
+
+/// Runs a single battery in the main slot, with the backup slot empty.
+#[cfg_attr(
+    not(power_policy = "single"),
+    expect(dead_code, reason = "Not the configured policy")
+)]
+pub struct SingleCell;
+
+impl PowerPolicy for SingleCell {
+    /// Never switches: enabling the empty slot would gain nothing.
+    fn on_low_voltage(&mut self, _active: PowerState) -> Action {
+        Action::Stay
+    }
+}
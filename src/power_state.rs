@@ -0,0 +1,25 @@
+//! Battery slot selection shared between tasks.
+//!
+//! [`PowerState`] names the battery powering the ornament. The power
+//! monitor task switches it (see [`crate::power`]), and the fuel gauge,
+//! statistics and policies index their per-cell figures by its slot.
+
+/// Power source state for tracking active battery
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, defmt::Format)]
+pub enum PowerState {
+    /// Main battery (BT1) is active
+    #[default]
+    MainPower,
+    /// Backup battery (BT2) is active
+    BackupPower,
+}
+
+impl PowerState {
+    /// Returns the battery slot index (0 = main, 1 = backup).
+    pub fn slot(&self) -> usize {
+        match self {
+            PowerState::MainPower => 0,
+            PowerState::BackupPower => 1,
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! Pseudo-random number generation.
+//!
+//! The MCU has no hardware RNG, so randomized patterns use a small
+//! xorshift32 generator. It is not suitable for anything but visual effects.
+//!
+//! # Seeding
+//!
+//! On the ornament the generator is seeded with
+//! [`hardware_seed`](crate::hardware::hardware_seed), which mixes the
+//! device's 96-bit unique ID with the RTC sub-second counter and the
+//! uptime, so ornaments hung side by side do not twinkle in step. For
+//! reproducible sequences (e.g. when checking a pattern on the host) seed it
+//! with a fixed value through [`Xorshift32::new`] instead.
+
+/// State used in place of a zero seed, which xorshift cannot leave.
+const NONZERO_SEED: u32 = 0x2545_f491;
+
+/// Xorshift32 pseudo-random number generator.
+pub struct Xorshift32 {
+    /// Generator state, never zero
+    state: u32,
+}
+
+impl Xorshift32 {
+    /// Creates a generator from a seed.
+    ///
+    /// The same seed always gives the same sequence. A zero seed is
+    /// replaced by a fixed non-zero one.
+    pub const fn new(seed: u32) -> Self {
+        Self {
+            state: if seed == 0 { NONZERO_SEED } else { seed },
+        }
+    }
+
+    /// Returns the next pseudo-random value.
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// Returns a pseudo-random value between `min` and `max`, inclusive.
+    pub fn range(&mut self, min: u32, max: u32) -> u32 {
+        match (max - min).checked_add(1) {
+            Some(span) => min + self.next_u32() % span,
+            None => self.next_u32(),
+        }
+    }
+}
+
+/// Mixes `value` into a hash with the murmur3 32-bit finalizer.
+pub const fn mix(hash: u32, value: u32) -> u32 {
+    let mut h = hash ^ value;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+    h
+}
@@ -0,0 +1,262 @@
+//! Programmable Voltage Detector (PVD) driver for STM32L0.
+//!
+//! The PVD compares VDD against a selectable threshold and drives EXTI
+//! line 16, which wakes the MCU from STOP mode. embassy-stm32 has no driver
+//! for it, so this module provides one in the style of its drivers: [`Pvd`]
+//! owns the `PWR` peripheral, [`InterruptHandler`] is bound to the `PVD`
+//! interrupt with `bind_interrupts!`, and [`Pvd::wait_for_change`] waits
+//! for the next threshold crossing.
+//!
+//! # Example
+//!
+//! ```no_run
+//! bind_interrupts!(struct Irqs {
+//!     PVD => pvd::InterruptHandler;
+//! });
+//!
+//! let mut pvd = Pvd::new(p.PWR, Irqs, pvd::Config::default());
+//! loop {
+//!     match pvd.wait_for_change().await {
+//!         VoltageEvent::Low => { /* VDD fell below the threshold */ }
+//!         VoltageEvent::Restored => { /* VDD rose above it again */ }
+//!     }
+//! }
+//! ```
+//!
+//! # Events
+//!
+//! Only the latest crossing is kept: if VDD crosses the threshold several
+//! times before the waiting task runs, [`Pvd::wait_for_change`] returns the
+//! last one. The comparator has about 100 mV of hysteresis.
+//!
+//! All `unsafe` code (the interrupt handler and unmasking it in the NVIC) is
+//! in this module. Only one [`Pvd`] can exist, as it owns `PWR`.
+
+use embassy_stm32::interrupt::typelevel::{Binding, Handler, Interrupt, PVD};
+use embassy_stm32::pac::{self, pwr::vals::Pls};
+use embassy_stm32::{Peri, peripherals::PWR};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+
+/// EXTI line the PVD output is connected to.
+const EXTI_LINE: usize = 16;
+
+/// EXTI register bank holding lines 0 to 31.
+const EXTI_BANK: usize = 0;
+
+/// Latest threshold crossing, signalled from the interrupt handler.
+static EVENT: Signal<CriticalSectionRawMutex, VoltageEvent> = Signal::new();
+
+/// Threshold VDD is compared against.
+#[expect(dead_code, reason = "The ornament only switches at 2.7V")]
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Level {
+    /// 1.9 V
+    V1_9,
+    /// 2.1 V
+    V2_1,
+    /// 2.3 V
+    V2_3,
+    /// 2.5 V
+    V2_5,
+    /// 2.7 V
+    V2_7,
+    /// 2.9 V
+    V2_9,
+    /// 3.1 V
+    V3_1,
+    /// Voltage on the PVD_IN pin (PB7) compared against VREFINT
+    External,
+}
+
+impl Level {
+    /// Returns the register value selecting this level.
+    fn pls(self) -> Pls {
+        match self {
+            Level::V1_9 => Pls::V1_9,
+            Level::V2_1 => Pls::V2_1,
+            Level::V2_3 => Pls::V2_3,
+            Level::V2_5 => Pls::V2_5,
+            Level::V2_7 => Pls::V2_7,
+            Level::V2_9 => Pls::V2_9,
+            Level::V3_1 => Pls::V3_1,
+            Level::External => Pls::EXTERNAL,
+        }
+    }
+}
+
+/// Threshold crossings that raise an event.
+#[expect(dead_code, reason = "The ornament watches both edges")]
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Edges {
+    /// VDD falling below the threshold
+    Falling,
+    /// VDD rising above the threshold
+    Rising,
+    /// Both directions
+    Both,
+}
+
+/// PVD configuration.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Config {
+    /// Threshold VDD is compared against
+    pub level: Level,
+    /// Crossings that raise an event
+    pub edges: Edges,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            level: Level::V2_7,
+            edges: Edges::Both,
+        }
+    }
+}
+
+/// Threshold crossing reported by the PVD.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum VoltageEvent {
+    /// VDD fell below the threshold
+    Low,
+    /// VDD rose above the threshold
+    Restored,
+}
+
+/// PVD interrupt handler, to bind to the `PVD` interrupt.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl Handler<PVD> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        pac::EXTI
+            .pr(EXTI_BANK)
+            .write(|w| w.set_line(EXTI_LINE, true));
+        EVENT.signal(current_state());
+    }
+}
+
+/// Returns which side of the threshold VDD is on.
+fn current_state() -> VoltageEvent {
+    // PVDO is set while VDD is below the threshold
+    if pac::PWR.csr().read().pvdo() {
+        VoltageEvent::Low
+    } else {
+        VoltageEvent::Restored
+    }
+}
+
+/// Programmable Voltage Detector.
+///
+/// Dropping the driver disables the detector and its interrupt.
+pub struct Pvd<'d> {
+    /// Power controller holding the PVD registers
+    _pwr: Peri<'d, PWR>,
+    /// True while the detector is enabled
+    enabled: bool,
+}
+
+impl<'d> Pvd<'d> {
+    /// Configures and enables the detector.
+    ///
+    /// # Arguments
+    ///
+    /// * `pwr` - Power controller peripheral
+    /// * `_irq` - Binding of the `PVD` interrupt to [`InterruptHandler`]
+    /// * `config` - Threshold and edges to detect
+    pub fn new(
+        pwr: Peri<'d, PWR>,
+        _irq: impl Binding<PVD, InterruptHandler> + 'd,
+        config: Config,
+    ) -> Self {
+        pac::RCC.apb1enr().modify(|w| w.set_pwren(true));
+
+        let mut pvd = Self {
+            _pwr: pwr,
+            enabled: false,
+        };
+        pvd.set_config(config);
+        pvd.enable();
+        pvd
+    }
+
+    /// Changes the threshold and edges.
+    ///
+    /// The detector is briefly disabled, so no event is raised for the
+    /// switch itself.
+    pub fn set_config(&mut self, config: Config) {
+        let enabled = self.enabled;
+        self.disable();
+
+        pac::PWR.cr().modify(|w| w.set_pls(config.level.pls()));
+
+        // The EXTI line follows PVDO, which rises as VDD falls
+        let exti = pac::EXTI;
+        exti.ftsr(EXTI_BANK).modify(|w| {
+            w.set_line(
+                EXTI_LINE,
+                matches!(config.edges, Edges::Rising | Edges::Both),
+            )
+        });
+        exti.rtsr(EXTI_BANK).modify(|w| {
+            w.set_line(
+                EXTI_LINE,
+                matches!(config.edges, Edges::Falling | Edges::Both),
+            )
+        });
+
+        if enabled {
+            self.enable();
+        }
+    }
+
+    /// Starts the detector and unmasks its interrupt.
+    ///
+    /// Events from before the detector was enabled are discarded.
+    pub fn enable(&mut self) {
+        pac::PWR.cr().modify(|w| w.set_pvde(true));
+
+        let exti = pac::EXTI;
+        exti.pr(EXTI_BANK).write(|w| w.set_line(EXTI_LINE, true));
+        EVENT.reset();
+        exti.imr(EXTI_BANK).modify(|w| w.set_line(EXTI_LINE, true));
+
+        PVD::unpend();
+        // Safety: the handler only touches registers owned by this driver
+        // and a signal made for interrupt context
+        unsafe { PVD::enable() };
+        self.enabled = true;
+    }
+
+    /// Stops the detector, masking its interrupt.
+    pub fn disable(&mut self) {
+        PVD::disable();
+
+        let exti = pac::EXTI;
+        exti.imr(EXTI_BANK).modify(|w| w.set_line(EXTI_LINE, false));
+        exti.pr(EXTI_BANK).write(|w| w.set_line(EXTI_LINE, true));
+        pac::PWR.cr().modify(|w| w.set_pvde(false));
+        self.enabled = false;
+    }
+
+    /// Returns which side of the threshold VDD is on now.
+    pub fn state(&self) -> VoltageEvent {
+        current_state()
+    }
+
+    /// Waits for VDD to cross the threshold in a configured direction.
+    ///
+    /// # Returns
+    ///
+    /// The side of the threshold VDD was on right after the latest crossing
+    pub async fn wait_for_change(&mut self) -> VoltageEvent {
+        EVENT.wait().await
+    }
+}
+
+impl Drop for Pvd<'_> {
+    fn drop(&mut self) {
+        self.disable();
+    }
+}
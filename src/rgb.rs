@@ -0,0 +1,263 @@
+//! Addressable RGB LED color model and frame renderer.
+//!
+//! Used on board variants with a short chain of WS2812/SK6812 LEDs instead
+//! of on/off strings. [`Renderer`] renders each step of an [`RgbPattern`]
+//! into a frame buffer of [`Rgb`] colors, which
+//! [`RgbController`](crate::rgb_controller::RgbController) sends to the
+//! LEDs.
+//!
+//! # Patterns
+//!
+//! | RGB pattern | Effect                                               |
+//! |-------------|------------------------------------------------------|
+//! | Chase       | Alternate, in palette colors, fading between steps   |
+//! | Gradient    | The palette as a gradient, rotating along the chain  |
+//! | Sparkle     | Random LEDs flash in palette colors and fade out     |
+//! | Twinkle     | LEDs light in palette colors at random and fade out  |
+//!
+//! Switching pattern cross-fades from the last rendered frame over
+//! [`FADE_STEPS`] steps.
+//!
+//! # Rendering
+//!
+//! [`Renderer`] is pure: it has no hardware or timer dependencies and the
+//! frames it produces depend only on the pattern, the step count and the
+//! PRNG seed it was created with, so rendered frames are checked against
+//! golden buffers on the host (`host-tests/tests/rgb.rs`).
+//!
+//! # Brightness
+//!
+//! An RGB LED at full white draws up to 60 mA, far more than a coin cell
+//! can supply, so frames are rendered at full brightness and every color is
+//! scaled to [`MAX_BRIGHTNESS`] before being sent.
+
+use crate::pattern::Pattern;
+use crate::prng::Xorshift32;
+use crate::twinkle::{Twinkle, TwinkleConfig};
+
+/// Number of rendered frames each step of the chase pattern is held for.
+const CHASE_HOLD_STEPS: u32 = 20;
+
+/// Number of rendered frames a fade between two frames takes.
+pub const FADE_STEPS: u32 = 5;
+
+/// Gradient rotation per rendered frame, in 1/256ths of the chain.
+const GRADIENT_SPEED: u32 = 2;
+
+/// Chance per rendered frame that a new sparkle starts, in 1/256ths.
+const SPARKLE_CHANCE: u32 = 48;
+
+/// Brightness kept per rendered frame by fading sparkles and twinkles, in 1/256ths.
+const SPARKLE_DECAY: u8 = 200;
+
+/// Brightness cap applied to every color sent to the LEDs, in 1/256ths.
+pub const MAX_BRIGHTNESS: u8 = 24;
+
+/// Twinkle bounds, in rendered frames: on for 0.2-0.6 s, off for 0.6-3 s.
+const RGB_TWINKLE: TwinkleConfig = TwinkleConfig::new((4, 12), (12, 60));
+
+/// 24-bit color.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, defmt::Format)]
+pub struct Rgb {
+    /// Red channel
+    pub r: u8,
+    /// Green channel
+    pub g: u8,
+    /// Blue channel
+    pub b: u8,
+}
+
+impl Rgb {
+    /// All channels off.
+    pub const BLACK: Self = Self::new(0, 0, 0);
+
+    /// Creates a color from its channels.
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Returns true if every channel is off.
+    pub const fn is_black(self) -> bool {
+        self.r == 0 && self.g == 0 && self.b == 0
+    }
+
+    /// Scales every channel by `level`/256.
+    pub const fn scale(self, level: u8) -> Self {
+        const fn channel(c: u8, level: u8) -> u8 {
+            ((c as u16 * level as u16) >> 8) as u8
+        }
+
+        Self::new(
+            channel(self.r, level),
+            channel(self.g, level),
+            channel(self.b, level),
+        )
+    }
+
+    /// Returns the color `t`/256 of the way from `self` to `to`.
+    pub const fn lerp(self, to: Self, t: u8) -> Self {
+        const fn channel(a: u8, b: u8, t: u8) -> u8 {
+            (a as i32 + (((b as i32 - a as i32) * t as i32) >> 8)) as u8
+        }
+
+        Self::new(
+            channel(self.r, to.r, t),
+            channel(self.g, to.g, t),
+            channel(self.b, to.b, t),
+        )
+    }
+}
+
+/// Colors the RGB patterns are drawn from.
+pub const PALETTE: [Rgb; 4] = [
+    Rgb::new(255, 0, 0),     // Red
+    Rgb::new(0, 255, 0),     // Green
+    Rgb::new(255, 160, 0),   // Gold
+    Rgb::new(255, 200, 120), // Warm white
+];
+
+/// Returns the palette as a gradient, sampled at `phase`/256 of the way
+/// around (the gradient wraps from the last color back to the first).
+pub fn gradient(phase: u8) -> Rgb {
+    let scaled = u32::from(phase) * PALETTE.len() as u32;
+    let index = (scaled >> 8) as usize;
+    let t = (scaled & 0xff) as u8;
+    PALETTE[index].lerp(PALETTE[(index + 1) % PALETTE.len()], t)
+}
+
+/// Pattern rendered on addressable RGB LEDs.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum RgbPattern {
+    /// The string pattern with each LED in its palette color
+    Chase,
+    /// The palette as a gradient rotating along the chain
+    Gradient,
+    /// Random LEDs flash in palette colors and fade out
+    Sparkle,
+    /// Each LED lights in its palette color at random times and fades out
+    Twinkle,
+}
+
+/// Renders [`RgbPattern`]s for a chain of `N` LEDs into a frame buffer.
+pub struct Renderer<const N: usize> {
+    /// Pattern being rendered
+    pattern: RgbPattern,
+    /// Frames rendered since the pattern was set
+    step: u32,
+    /// Last rendered frame
+    frame: [Rgb; N],
+    /// Frame shown when the pattern was set, faded out over [`FADE_STEPS`]
+    from: [Rgb; N],
+    /// Random numbers for the sparkle and twinkle patterns
+    rng: Xorshift32,
+    /// Random schedule of each LED for the twinkle pattern
+    twinkle: Twinkle<N>,
+}
+
+impl<const N: usize> Renderer<N> {
+    /// Creates a renderer showing the default pattern from a black frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - PRNG seed; the same seed renders the same frames
+    pub const fn new(seed: u32) -> Self {
+        Self {
+            pattern: RgbPattern::Chase,
+            step: 0,
+            frame: [Rgb::BLACK; N],
+            from: [Rgb::BLACK; N],
+            rng: Xorshift32::new(seed),
+            twinkle: Twinkle::new(RGB_TWINKLE),
+        }
+    }
+
+    /// Switches pattern, cross-fading from the last rendered frame.
+    pub fn set_pattern(&mut self, pattern: RgbPattern) {
+        self.pattern = pattern;
+        self.step = 0;
+        self.from = self.frame;
+        if pattern == RgbPattern::Twinkle {
+            self.twinkle.restart(&mut self.rng);
+        }
+    }
+
+    /// Renders the next frame.
+    ///
+    /// # Returns
+    ///
+    /// The rendered frame, at full brightness
+    pub fn render(&mut self) -> &[Rgb; N] {
+        let mut frame = [Rgb::BLACK; N];
+
+        match self.pattern {
+            RgbPattern::Chase => {
+                let hold = self.step % CHASE_HOLD_STEPS;
+                let index = (self.step / CHASE_HOLD_STEPS) as usize;
+                let len = Pattern::Alternate.len(N);
+                let current = Self::chase_frame(index % len);
+                let previous = Self::chase_frame((index + len - 1) % len);
+                let t = Self::fade_level(hold);
+                for (n, led) in frame.iter_mut().enumerate() {
+                    *led = previous[n].lerp(current[n], t);
+                }
+            }
+            RgbPattern::Gradient => {
+                let offset = self.step.wrapping_mul(GRADIENT_SPEED);
+                for (n, led) in frame.iter_mut().enumerate() {
+                    let position = (n * 256 / N) as u32;
+                    *led = gradient(position.wrapping_add(offset) as u8);
+                }
+            }
+            RgbPattern::Sparkle => {
+                for (n, led) in frame.iter_mut().enumerate() {
+                    *led = self.frame[n].scale(SPARKLE_DECAY);
+                }
+                let roll = self.rng.next_u32();
+                if roll & 0xff < SPARKLE_CHANCE {
+                    let n = (roll >> 8) as usize % N;
+                    frame[n] = PALETTE[(roll >> 16) as usize % PALETTE.len()];
+                }
+            }
+            RgbPattern::Twinkle => {
+                let lit = self.twinkle.step(&mut self.rng);
+                for (n, led) in frame.iter_mut().enumerate() {
+                    *led = if lit.is_on(n) {
+                        PALETTE[n % PALETTE.len()]
+                    } else {
+                        self.frame[n].scale(SPARKLE_DECAY)
+                    };
+                }
+            }
+        }
+
+        if self.step < FADE_STEPS {
+            let t = Self::fade_level(self.step);
+            for (n, led) in frame.iter_mut().enumerate() {
+                *led = self.from[n].lerp(*led, t);
+            }
+        }
+
+        self.step = self.step.wrapping_add(1);
+        self.frame = frame;
+        &self.frame
+    }
+
+    /// Returns the string pattern frame at `index` with each lit LED in its
+    /// palette color.
+    fn chase_frame(index: usize) -> [Rgb; N] {
+        let bits = Pattern::Alternate.frame(index, N);
+        let mut frame = [Rgb::BLACK; N];
+        for (n, led) in frame.iter_mut().enumerate() {
+            if bits.is_on(n) {
+                *led = PALETTE[n % PALETTE.len()];
+            }
+        }
+        frame
+    }
+
+    /// Returns how far a fade has progressed after `step` frames, in
+    /// 1/256ths (255 once complete).
+    fn fade_level(step: u32) -> u8 {
+        (step.min(FADE_STEPS) * 255 / FADE_STEPS) as u8
+    }
+}
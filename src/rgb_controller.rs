@@ -0,0 +1,191 @@
+//! Addressable RGB LED pattern engine.
+//!
+//! [`RgbController`] is the [`LedEngine`] of board variants with a short
+//! chain of WS2812/SK6812 LEDs instead of on/off strings. It renders frames
+//! with a [`Renderer`] (see [`crate::rgb`]) and sends them through the
+//! [`Ws2812`] driver.
+//!
+//! # Patterns
+//!
+//! The user-facing [`Pattern`] selection is mapped onto an [`RgbPattern`]:
+//!
+//! | Pattern   | RGB pattern |
+//! |-----------|-------------|
+//! | Alternate | Chase       |
+//! | Swap      | Gradient    |
+//! | Together  | Sparkle     |
+//! | Twinkle   | Twinkle     |
+//!
+//! # Power
+//!
+//! A WS2812 draws about 1 mA even when dark. The LED supply is power-gated
+//! whenever every LED is black (see [`crate::ws2812`]), and all colors are
+//! scaled to [`MAX_BRIGHTNESS`] before being sent.
+
+use embassy_time::Timer;
+
+use crate::hardware::hardware_seed;
+use crate::pattern::Pattern;
+use crate::rgb::{MAX_BRIGHTNESS, PALETTE, Renderer, Rgb, RgbPattern};
+use crate::string_controller::{
+    LedEngine, OVERLAY_BLINK_OFF_MS, OVERLAY_BLINK_ON_MS, Overlay, SELF_TEST_STEP_MS,
+};
+use crate::ws2812::Ws2812;
+
+/// Time between rendered frames, in milliseconds.
+const RGB_STEP_MS: u32 = 50;
+
+impl From<Pattern> for RgbPattern {
+    fn from(pattern: Pattern) -> Self {
+        match pattern {
+            Pattern::Alternate => RgbPattern::Chase,
+            Pattern::Swap => RgbPattern::Gradient,
+            Pattern::Together => RgbPattern::Sparkle,
+            Pattern::Twinkle => RgbPattern::Twinkle,
+        }
+    }
+}
+
+/// Pattern engine for a chain of `N` addressable RGB LEDs.
+pub struct RgbController<const N: usize> {
+    /// Frame renderer
+    renderer: Renderer<N>,
+    /// Pattern selected, before mapping to its [`RgbPattern`]
+    pattern: Pattern,
+    /// LED chain driver
+    strip: Ws2812,
+    /// Frame currently shown, at full brightness
+    shown: [Rgb; N],
+}
+
+impl<const N: usize> RgbController<N> {
+    /// Creates a new RgbController.
+    ///
+    /// # Arguments
+    ///
+    /// * `strip` - Driver for the LED chain
+    pub fn new(strip: Ws2812) -> Self {
+        Self {
+            renderer: Renderer::new(hardware_seed()),
+            pattern: Pattern::default(),
+            strip,
+            shown: [Rgb::BLACK; N],
+        }
+    }
+
+    /// Sends a frame to the LEDs, capped to [`MAX_BRIGHTNESS`].
+    async fn show(&mut self, frame: [Rgb; N]) {
+        self.shown = frame;
+        self.strip
+            .show(&frame.map(|led| led.scale(MAX_BRIGHTNESS)))
+            .await;
+    }
+
+    /// Shows every LED in the same color.
+    async fn fill(&mut self, color: Rgb) {
+        self.show([color; N]).await;
+    }
+}
+
+impl<const N: usize> LedEngine for RgbController<N> {
+    const STEP_MS: u32 = RGB_STEP_MS;
+
+    /// Powers the LED chain down and restarts the pattern.
+    fn reset(&mut self) {
+        #[cfg(feature = "debug-mode")]
+        defmt::info!("Resetting {} RGB LEDs...", N);
+
+        self.strip.power_off();
+        self.shown = [Rgb::BLACK; N];
+        self.renderer = Renderer::new(hardware_seed());
+        self.pattern = Pattern::default();
+    }
+
+    /// Counts each LED that is not black as one lit string.
+    fn lit_strings(&self) -> u32 {
+        self.shown.iter().filter(|led| !led.is_black()).count() as u32
+    }
+
+    fn pattern(&self) -> Pattern {
+        self.pattern
+    }
+
+    fn set_pattern(&mut self, pattern: Pattern) {
+        self.pattern = pattern;
+        let pattern = RgbPattern::from(pattern);
+
+        #[cfg(feature = "debug-mode")]
+        defmt::info!("RGB pattern: {}", pattern);
+
+        self.renderer.set_pattern(pattern);
+    }
+
+    async fn advance(&mut self) {
+        let frame = *self.renderer.render();
+        self.show(frame).await;
+    }
+
+    async fn all_off(&mut self) {
+        self.fill(Rgb::BLACK).await;
+    }
+
+    /// Every LED double-blinks red, then the frame shown before is restored.
+    async fn play_overlay(&mut self, overlay: &Overlay) {
+        let saved = self.shown;
+
+        self.fill(Rgb::BLACK).await;
+        Timer::after_millis(OVERLAY_BLINK_OFF_MS).await;
+
+        match overlay {
+            Overlay::LowBattery => {
+                #[cfg(feature = "debug-mode")]
+                defmt::info!("Overlay: low battery double-blink");
+
+                for _ in 0..2 {
+                    self.fill(PALETTE[0]).await;
+                    Timer::after_millis(OVERLAY_BLINK_ON_MS).await;
+                    self.fill(Rgb::BLACK).await;
+                    Timer::after_millis(OVERLAY_BLINK_OFF_MS).await;
+                }
+            }
+        }
+
+        self.show(saved).await;
+    }
+
+    async fn blackout(&mut self, until: impl Future<Output = ()>) {
+        let saved = self.shown;
+
+        self.fill(Rgb::BLACK).await;
+        until.await;
+        self.show(saved).await;
+    }
+
+    /// Lights each LED in white, then every LED in red, green and blue to
+    /// check each color channel.
+    async fn self_test(&mut self) {
+        #[cfg(feature = "debug-mode")]
+        defmt::info!("RGB self-test");
+
+        let saved = self.shown;
+        let white = Rgb::new(255, 255, 255);
+
+        for n in 0..N {
+            let mut frame = [Rgb::BLACK; N];
+            frame[n] = white;
+            self.show(frame).await;
+            Timer::after_millis(SELF_TEST_STEP_MS).await;
+        }
+
+        for color in [
+            Rgb::new(255, 0, 0),
+            Rgb::new(0, 255, 0),
+            Rgb::new(0, 0, 255),
+        ] {
+            self.fill(color).await;
+            Timer::after_millis(SELF_TEST_STEP_MS).await;
+        }
+
+        self.show(saved).await;
+    }
+}
@@ -0,0 +1,60 @@
+//! Ambient light sensing with a reverse-biased LED.
+//!
+//! An LED is also a (poor) photodiode. On boards with a `[light_sensor]`
+//! section a dedicated sensing LED sits on its own GPIO, cathode on the pin
+//! and anode to GND, so nothing but the MCU ever drives its node and the
+//! LED never lights.
+//!
+//! # Measurement
+//!
+//! The pin drives the cathode high for a moment, reverse-biasing the LED
+//! and charging its junction capacitance, and is then turned into an
+//! input. Photocurrent discharges the capacitance, so the time until the
+//! pin reads low shortens as more light falls on the LED (see
+//! [`crate::light_sensor`]). Between measurements the pin is left in analog
+//! mode, which draws no input current.
+//!
+//! The ornament's own LEDs would light the sensor, so the LED task turns
+//! them off for the measurement, at most [`MAX_DECAY_US`] once a minute.
+
+use embassy_stm32::gpio::{Flex, Pull, Speed};
+use embassy_time::{Duration, Instant};
+
+use crate::light_sensor::LightSensor;
+
+/// Longest decay time measured, in microseconds; darker is clipped.
+pub const MAX_DECAY_US: u64 = 20_000;
+
+/// Reverse-biased LED on its own GPIO, used as a light sensor.
+pub struct SensorLed {
+    /// GPIO on the sensing LED's cathode
+    pin: Flex<'static>,
+}
+
+impl SensorLed {
+    /// Creates a sensing LED driver.
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - GPIO on the sensing LED's cathode
+    pub fn new(mut pin: Flex<'static>) -> Self {
+        pin.set_as_analog();
+        Self { pin }
+    }
+}
+
+impl LightSensor for SensorLed {
+    fn decay_us(&mut self) -> u32 {
+        self.pin.set_high();
+        self.pin.set_as_output(Speed::Low);
+        self.pin.set_as_input(Pull::None);
+
+        let start = Instant::now();
+        let timeout = Duration::from_micros(MAX_DECAY_US);
+        while self.pin.is_high() && start.elapsed() < timeout {}
+        let decay = start.elapsed().as_micros().min(MAX_DECAY_US);
+
+        self.pin.set_as_analog();
+        decay as u32
+    }
+}
@@ -0,0 +1,115 @@
+//! Shift-register LED backend.
+//!
+//! Drives LED strings from a chain of 74HC595 (or 74LV595) serial-in,
+//! parallel-out shift registers using only three GPIOs, however many
+//! strings there are. The registers' output latches hold their state while
+//! the MCU sleeps in STOP mode.
+//!
+//! # Hardware Design
+//!
+//! - SER: Serial data into the first register; each register's QH' feeds
+//!   SER of the next
+//! - SRCLK: Shift clock, shared by the chain. Rising edge shifts SER in
+//! - RCLK: Latch clock, shared by the chain. Rising edge copies the shift
+//!   stages to the QA..QH outputs
+//! - SRCLR_N: Tied high (shift stages are cleared by shifting zeros)
+//! - OE_N: Tied low (outputs always enabled)
+//!
+//! String 0 is wired to QA of the first register, string 7 to QH, string 8
+//! to QA of the second register, and so on.
+//!
+//! # Bit Order and Latch Timing
+//!
+//! A frame is shifted out last string first (see [`shift_order`]), so after
+//! [`LedBackend::STRINGS`] shift clocks string 0 sits in the QA stage of the
+//! first register. Only then is RCLK pulsed. The outputs change once, on
+//! that rising edge, so the strings never show the partially shifted frame.
+//! At the MCU clock speeds used here every GPIO write lasts many times the
+//! 74HC595's minimum pulse widths and setup times.
+//!
+//! The driver is generic over its pins, so the host tests in
+//! `host-tests/tests/shift_register.rs` run it against a simulated chain.
+
+use core::convert::Infallible;
+
+use embedded_hal::digital::v2::OutputPin;
+
+use crate::led_backend::{Frame, LedBackend};
+
+/// Returns the serial data bits for a frame, in shift order.
+///
+/// The last string is shifted first, so that once all bits are clocked in
+/// string 0 sits on QA of the first register in the chain.
+///
+/// # Arguments
+///
+/// * `frame` - Frame to shift out
+/// * `strings` - Number of strings on the chain
+pub fn shift_order(frame: Frame, strings: usize) -> impl Iterator<Item = bool> {
+    (0..strings).rev().map(move |n| frame.is_on(n))
+}
+
+/// Chain of 74HC595 shift registers driving `N` LED strings.
+pub struct ShiftRegister<const N: usize, P> {
+    /// Serial data input of the first register
+    ser: P,
+    /// Shift clock (rising edge shifts SER in)
+    srclk: P,
+    /// Latch clock (rising edge updates the outputs)
+    rclk: P,
+    /// Last frame latched onto the outputs
+    latched: Frame,
+}
+
+impl<const N: usize, P: OutputPin<Error = Infallible>> ShiftRegister<N, P> {
+    /// Creates a new shift register chain driver.
+    ///
+    /// # Arguments
+    ///
+    /// * `ser` - Serial data GPIO
+    /// * `srclk` - Shift clock GPIO, initially low
+    /// * `rclk` - Latch clock GPIO, initially low
+    pub fn new(ser: P, srclk: P, rclk: P) -> Self {
+        Self {
+            ser,
+            srclk,
+            rclk,
+            latched: Frame::OFF,
+        }
+    }
+
+    /// Shifts a frame into the chain and latches it onto the outputs.
+    fn shift_out(&mut self, frame: Frame) {
+        for bit in shift_order(frame, N) {
+            let Ok(()) = self.ser.set_state(bit.into());
+            let Ok(()) = self.srclk.set_high();
+            let Ok(()) = self.srclk.set_low();
+        }
+
+        let Ok(()) = self.rclk.set_high();
+        let Ok(()) = self.rclk.set_low();
+        self.latched = frame;
+    }
+}
+
+impl<const N: usize, P: OutputPin<Error = Infallible>> LedBackend for ShiftRegister<N, P> {
+    const STRINGS: usize = N;
+
+    /// The output latches power up in an undefined state, so zeros are
+    /// shifted through the whole chain and latched.
+    fn reset(&mut self) {
+        self.shift_out(Frame::OFF);
+    }
+
+    fn latched(&self) -> Frame {
+        self.latched
+    }
+
+    /// The chain is only shifted if the frame differs from the latched one.
+    fn apply(&mut self, frame: Frame) {
+        let frame = Frame(frame.0 & Frame::all(N).0);
+        if frame != self.latched {
+            self.shift_out(frame);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Light show playback from a compact light sequence.
+//!
+//! A light show is a list of timed string-state changes ("cues") made to
+//! go with a song: start the song and select the Show pattern on the same
+//! beat, and the strings follow the music. The cue sheet is written as CSV
+//! and compiled into the firmware by `build.rs` (see `shows/demo.csv`).
+//!
+//! # Format
+//!
+//! A light sequence is a 4-byte header followed by the cues:
+//!
+//! | Bytes | Contents                                               |
+//! |-------|--------------------------------------------------------|
+//! | 3     | [`HEADER`]: magic `LS` and format version 1            |
+//! | 1     | Frame width W: bytes per cue bitmask, 1 to 4           |
+//! | 2 + W | Each cue: delay in ms since the previous cue (u16 LE), |
+//! |       | then the strings lit from then on (bitmask, LE)        |
+//!
+//! The first cue has no delay and the last turns every string off at the
+//! end of the show, which then starts over. Cues are read straight from
+//! flash, and each step waits exactly until the next cue, so timing is as
+//! fine as the RTC tick rather than a fixed pattern step.
+
+use crate::led_backend::Frame;
+
+/// Magic and format version starting a light sequence.
+pub const HEADER: [u8; 3] = *b"LS\x01";
+
+/// Light sequence compiled from the cue sheet by `build.rs`.
+pub const SHOW: Sequence =
+    match Sequence::new(include_bytes!(concat!(env!("OUT_DIR"), "/show.bin"))) {
+        Some(sequence) => sequence,
+        None => panic!("invalid light sequence"),
+    };
+
+/// State change of the LED strings at a point in the show.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Cue {
+    /// Time since the previous cue, in milliseconds
+    pub delay_ms: u16,
+    /// Strings lit from this cue on
+    pub frame: Frame,
+}
+
+/// Light sequence stored in flash.
+#[derive(Clone, Copy)]
+pub struct Sequence<'a> {
+    /// Encoded cues, after the header
+    cues: &'a [u8],
+    /// Bytes per cue bitmask
+    width: usize,
+}
+
+impl<'a> Sequence<'a> {
+    /// Checks a light sequence's header and length.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Encoded light sequence, header included
+    ///
+    /// # Returns
+    ///
+    /// `None` if the header is wrong or there are no whole cues
+    pub const fn new(bytes: &'a [u8]) -> Option<Self> {
+        let Some((header, cues)) = bytes.split_at_checked(4) else {
+            return None;
+        };
+        if header[0] != HEADER[0] || header[1] != HEADER[1] || header[2] != HEADER[2] {
+            return None;
+        }
+
+        let width = header[3] as usize;
+        if width == 0 || width > 4 || cues.is_empty() || !cues.len().is_multiple_of(2 + width) {
+            return None;
+        }
+        Some(Self { cues, width })
+    }
+
+    /// Returns the number of cues.
+    pub const fn cue_count(&self) -> usize {
+        self.cues.len() / (2 + self.width)
+    }
+
+    /// Returns cue `index`, which must be less than [`Self::cue_count`].
+    pub fn cue(&self, index: usize) -> Cue {
+        let size = 2 + self.width;
+        let cue = &self.cues[index * size..][..size];
+
+        let mut mask = [0; 4];
+        mask[..self.width].copy_from_slice(&cue[2..]);
+        Cue {
+            delay_ms: u16::from_le_bytes([cue[0], cue[1]]),
+            frame: Frame(u32::from_le_bytes(mask)),
+        }
+    }
+}
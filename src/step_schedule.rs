@@ -0,0 +1,55 @@
+//! Drift-free timing of pattern steps.
+//!
+//! The LED task waits for each step on a [`StepSchedule`]. The schedule is
+//! pure instant arithmetic, so the host tests in
+//! `host-tests/tests/step_schedule.rs` run a day of steps over virtual time
+//! at the firmware's 32.768 kHz tick rate and check that none of it drifts.
+
+use embassy_time::{Duration, Instant};
+
+/// Absolute deadlines of pattern steps.
+///
+/// Steps are timed from an anchor instant by the sum of their durations in
+/// milliseconds, rather than by waiting a step duration after each step,
+/// so the time spent latching frames never adds up as drift, and step
+/// durations that are not a whole number of timer ticks do not accumulate
+/// rounding errors.
+pub struct StepSchedule {
+    /// Time of the first step
+    anchor: Instant,
+    /// Time from the anchor to the next step, in milliseconds
+    elapsed_ms: u64,
+}
+
+impl StepSchedule {
+    /// Creates a schedule with its next step at `at`.
+    pub const fn starting_at(at: Instant) -> Self {
+        Self {
+            anchor: at,
+            elapsed_ms: 0,
+        }
+    }
+
+    /// Returns the time of the next step.
+    pub fn deadline(&self) -> Instant {
+        self.anchor + Duration::from_millis(self.elapsed_ms)
+    }
+
+    /// Schedules the step after the next one.
+    ///
+    /// If that step is already overdue, because the task was held up (by an
+    /// overlay, for instance) for longer than a step, the schedule restarts
+    /// at `now` so the steps missed are skipped rather than shown in a
+    /// burst.
+    ///
+    /// # Arguments
+    ///
+    /// * `step_ms` - Duration of the step just shown, in milliseconds
+    /// * `now` - Current time
+    pub fn advance(&mut self, step_ms: u32, now: Instant) {
+        self.elapsed_ms += u64::from(step_ms);
+        if self.deadline() < now {
+            *self = Self::starting_at(now);
+        }
+    }
+}
@@ -1,210 +1,739 @@
 //! LED string controller module for Christmas ornament.
 //!
-//! Controls two independent LED strings (red and green) using D flip-flops.
-//! Each flip-flop (SN74LVC1G74) drives a string of 3 LEDs through its Q output.
+//! Drives independent LED strings through an [`LedBackend`], which latches
+//! each [`Frame`] in hardware so the LEDs keep running while the MCU sleeps
+//! in STOP mode (see [`crate::led_backend`]). The backend and number of
+//! strings are set by the board description (see [`crate::hardware`]):
 //!
-//! # Hardware Design
+//! - `flip_flop`: one D flip-flop per string (rev-a board)
+//! - `shift_register`: a 74HC595 shift register chain
+//! - `charlieplex`: LEDs charlieplexed on MCU pins, scanned by a task
 //!
-//! - U2 (SN74LVC1G74): Controls LSTR1 (3x RED LEDs: LED1, LED3, LED5)
-//! - U3 (SN74LVC1G74): Controls LSTR2 (3x GREEN LEDs: LED6, LED2, LED4)
+//! Boards with addressable RGB LEDs use the `rgb_controller` pattern engine
+//! instead, which renders colored frames from the same [`Pattern`]
+//! selection.
 //!
-//! Each flip-flop has:
-//! - D (data input): Sets the value to be clocked in
-//! - CLK (clock): Rising edge latches D input to Q output
-//! - PRE_N (preset, active-low): Asynchronously sets Q high
-//! - CLR_N (clear, active-low): Asynchronously sets Q low
-//! - Q (output): Drives LED string (high = LEDs ON)
+//! # Patterns
 //!
-//! LEDs are driven with 4.7kΩ resistors for ~255µA current at 3.0V.
-
-use embassy_stm32::gpio::{Input, Output};
-
-/// D flip-flop controller for LED string.
-///
-/// Wraps GPIO control for a SN74LVC1G74 D flip-flop chip.
-/// Provides high-level methods for setting the Q output state.
-pub struct FlipFlop {
-    /// Active-low preset input (forces Q high when low)
-    fpre_n: Output<'static>,
-    /// Active-low clear input (forces Q low when low)
-    fclr_n: Output<'static>,
-    /// Data input (value to be clocked into Q)
-    fdata: Output<'static>,
-    /// Clock input (rising edge latches D to Q)
-    fclk: Output<'static>,
+//! A [`Pattern`] (see [`crate::pattern`]) is a sequence of [`Frame`]s, each
+//! a bitmask giving the state of every string. Patterns are defined for any
+//! number of strings, so a two-string board is just one configuration.
+//!
+//! On the LED strings the Message pattern plays a short text in Morse code
+//! (see [`crate::morse`]), stepping once per Morse unit instead of every
+//! [`LED_CYCLE_MS`], and the Show pattern plays a light show compiled in at
+//! build time (see [`crate::show`]), stepping at each cue.
+//!
+//! # Overlays
+//!
+//! Short warning animations (see [`Overlay`]) can be played on top of the
+//! running pattern. The pattern's latched state is saved, the overlay is
+//! blinked out, and the saved state is restored so the pattern continues
+//! where it left off.
+//!
+//! # LED Task
+//!
+//! [`led_task`] owns the board's [`LedEngine`] ([`StringController`] or
+//! `RgbController`) and steps the pattern. Other tasks control the LEDs by
+//! sending [`LedCommand`]s on [`LED_COMMANDS`] rather than sharing the
+//! controller.
+//!
+//! # Blackouts
+//!
+//! The power task turns the LEDs off while it switches batteries, so the
+//! new cell does not take the strings' inrush during the changeover. It
+//! holds a [`Blackout`] from [`blackout`]: the LED task saves the frame
+//! shown, turns every LED off and waits, without stepping the pattern,
+//! until the guard is dropped, then restores the frame.
+
+use embassy_futures::select::{Either3, select3};
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel, signal::Signal,
+};
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+
+use crate::activity;
+use crate::activity_meter::Task;
+use crate::event_log::{self, Kind};
+use crate::hardware::{AmbientSensor, LedController};
+#[cfg(not(led_backend = "rgb"))]
+use crate::hardware::{STRING_COUNT, hardware_seed};
+#[cfg(not(led_backend = "rgb"))]
+use crate::led_backend::{Frame, LedBackend};
+#[cfg(light_sensor)]
+use crate::light_sensor::LightSensor;
+use crate::light_sensor::{Ambient, DarkDetector};
+#[cfg(not(led_backend = "rgb"))]
+use crate::morse::{self, Cursor, Message};
+use crate::pattern::Pattern;
+use crate::power::set_led_load;
+#[cfg(not(led_backend = "rgb"))]
+use crate::prng::Xorshift32;
+#[cfg(not(led_backend = "rgb"))]
+use crate::show::SHOW;
+use crate::step_schedule::StepSchedule;
+#[cfg(sync_link)]
+use crate::sync::{self, SyncPoint};
+#[cfg(sync_link)]
+use crate::sync_protocol::{self, SyncFrame};
+use crate::system_state::{self, SYSTEM_STATE};
+#[cfg(not(led_backend = "rgb"))]
+use crate::twinkle::{Twinkle, TwinkleConfig};
+
+/// On-time of each blink in an overlay, in milliseconds.
+pub const OVERLAY_BLINK_ON_MS: u64 = 80;
+
+/// Off-time between blinks in an overlay, in milliseconds.
+pub const OVERLAY_BLINK_OFF_MS: u64 = 160;
+
+/// Time each frame of the self-test is shown, in milliseconds.
+pub const SELF_TEST_STEP_MS: u64 = 500;
+
+/// Pattern step time of the LED strings, in milliseconds.
+///
+/// Each frame of the pattern is held for this duration before the next
+/// frame is latched.
+#[cfg(not(led_backend = "rgb"))]
+const LED_CYCLE_MS: u32 = 1000;
+
+/// Twinkle bounds of the LED strings, in [`LED_CYCLE_MS`] steps: on for 1-3 s,
+/// off for 3-8 s.
+#[cfg(not(led_backend = "rgb"))]
+const STRING_TWINKLE: TwinkleConfig = TwinkleConfig::new((1, 3), (3, 8));
+
+/// Interval between ambient light measurements, on boards that sense light.
+#[cfg(light_sensor)]
+const LIGHT_SENSE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Interval between low battery warning overlays while the warning is raised.
+const LOW_BATTERY_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Number of LED commands that can be queued before senders wait.
+const LED_COMMAND_QUEUE_LEN: usize = 4;
+
+/// Warning animation played on top of the running pattern.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Overlay {
+    /// Quick double-blink of the first string (battery low or on backup)
+    LowBattery,
+}
+
+/// Commands accepted by [`led_task`].
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+#[expect(
+    dead_code,
+    reason = "Pause and Resume are not sent by any subsystem yet"
+)]
+pub enum LedCommand {
+    /// Switch to the pattern after the one shown (see [`Pattern::next`]),
+    /// restarting it from its first frame
+    NextPattern,
+    /// Switch to the pattern before the one shown (see [`Pattern::previous`]),
+    /// restarting it from its first frame
+    PreviousPattern,
+    /// AllOff if the pattern is running, otherwise Resume
+    ToggleLeds,
+    /// Stop stepping the pattern, holding the current frame
+    Pause,
+    /// Continue stepping the pattern after Pause or AllOff
+    Resume,
+    /// Play an overlay immediately
+    Overlay(Overlay),
+    /// Turn all strings off and stop stepping the pattern
+    AllOff,
+    /// Light each string in turn, then restore the current frame
+    SelfTest,
+    /// Turn every LED off until the [`Blackout`] guard is dropped, then
+    /// restore the current frame (see [`blackout`])
+    Blackout,
+    /// Follow the sync leader's pattern and step timing
+    #[cfg(sync_link)]
+    Sync(SyncPoint),
+    /// Flash a sync frame on the LEDs (optical sync leader)
+    #[cfg(sync_link)]
+    SyncFlash(SyncFrame),
+}
+
+/// Command queue for [`led_task`].
+pub static LED_COMMANDS: Channel<CriticalSectionRawMutex, LedCommand, LED_COMMAND_QUEUE_LEN> =
+    Channel::new();
+
+/// Raised by [`led_task`] once the LEDs are off for a [`Blackout`].
+static BLACKOUT_STARTED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Raised when a [`Blackout`] ends, letting [`led_task`] restore the LEDs.
+static BLACKOUT_ENDED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Longest wait for [`led_task`] to turn the LEDs off for a [`Blackout`].
+///
+/// The LED task may be busy playing an overlay or self-test, and a battery
+/// switch must not wait for it.
+const BLACKOUT_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Keeps every LED off while held.
+///
+/// Dropping the guard restores the frame shown before.
+pub struct Blackout {
+    _private: (),
+}
+
+/// Turns every LED off until the returned guard is dropped.
+///
+/// # Returns
+///
+/// The guard, once the LEDs are off, or None if the LED task did not turn
+/// them off within [`BLACKOUT_TIMEOUT`]
+pub async fn blackout() -> Option<Blackout> {
+    BLACKOUT_STARTED.reset();
+    LED_COMMANDS.try_send(LedCommand::Blackout).ok()?;
+
+    if with_timeout(BLACKOUT_TIMEOUT, BLACKOUT_STARTED.wait())
+        .await
+        .is_err()
+    {
+        // The request is queued: let it end as soon as the LED task gets to it
+        BLACKOUT_ENDED.signal(());
+        return None;
+    }
+    Some(Blackout { _private: () })
+}
+
+impl Drop for Blackout {
+    fn drop(&mut self) {
+        BLACKOUT_ENDED.signal(());
+    }
 }
 
-impl FlipFlop {
-    /// Creates a new FlipFlop controller.
+/// Pattern engine driven by [`led_task`].
+///
+/// Implemented by [`StringController`] for on/off LED strings and by
+/// `RgbController` for addressable RGB LEDs, so the same task and commands
+/// drive every board variant.
+pub trait LedEngine {
+    /// Time between pattern steps, in milliseconds.
+    const STEP_MS: u32;
+
+    /// Returns the time until the next step in milliseconds:
+    /// [`Self::STEP_MS`], unless the engine follows a sync leader stepping
+    /// at a slightly different rate or plays a pattern with its own timing.
+    fn step_ms(&self) -> u32 {
+        Self::STEP_MS
+    }
+
+    /// Turns every LED off and resets the pattern to its first step.
+    fn reset(&mut self);
+
+    /// Returns the current LED load in lit strings, for the fuel gauge.
+    fn lit_strings(&self) -> u32;
+
+    /// Returns the pattern being displayed.
+    fn pattern(&self) -> Pattern;
+
+    /// Switches to a new pattern, starting from its first step.
+    fn set_pattern(&mut self, pattern: Pattern);
+
+    /// Shows the next step of the pattern.
+    ///
+    /// Call this every [`Self::step_ms`] to run the display pattern.
+    async fn advance(&mut self);
+
+    /// Turns every LED off, leaving the pattern where it is.
+    async fn all_off(&mut self);
+
+    /// Plays a warning overlay on top of the running pattern.
     ///
     /// # Arguments
     ///
-    /// * `fpre_n` - Active-low preset GPIO
-    /// * `fclr_n` - Active-low clear GPIO
-    /// * `fdata` - Data input GPIO
-    /// * `fclk` - Clock input GPIO
-    pub fn new(
-        fpre_n: Output<'static>,
-        fclr_n: Output<'static>,
-        fdata: Output<'static>,
-        fclk: Output<'static>,
-    ) -> Self {
-        Self {
-            fpre_n,
-            fclr_n,
-            fdata,
-            fclk,
-        }
-    }
+    /// * `overlay` - Overlay animation to play
+    async fn play_overlay(&mut self, overlay: &Overlay);
 
-    /// Releases asynchronous reset/preset controls.
+    /// Turns every LED off until `until` completes, then restores the frame
+    /// shown before, leaving the pattern where it is.
     ///
-    /// Sets both CLR_N and PRE_N high to deactivate them.
-    /// This allows normal clocked operation where D is latched to Q on CLK rising edge.
-    pub fn release_reset(&mut self) {
-        self.fclr_n.set_high();
-        self.fpre_n.set_high();
-    }
-
-    /// Clocks the flip-flop to set Q output high.
+    /// # Arguments
     ///
-    /// Sets D input high, then pulses CLK to latch the value.
-    /// This turns the LED string ON.
-    pub fn clock_q_high(&mut self) {
-        self.fdata.set_high();
-        self.fclk.set_high();
-        self.fclk.set_low();
-    }
+    /// * `until` - Future completing when the LEDs may light again
+    async fn blackout(&mut self, until: impl Future<Output = ()>);
 
-    /// Clocks the flip-flop to set Q output low.
+    /// Lights each LED on its own, then all together, then restores the
+    /// running pattern.
     ///
-    /// Sets D input low, then pulses CLK to latch the value.
-    /// This turns the LED string OFF.
-    pub fn clock_q_low(&mut self) {
-        self.fdata.set_low();
-        self.fclk.set_high();
-        self.fclk.set_low();
-    }
+    /// Lets a user confirm every LED works without waiting for the pattern
+    /// to reach it.
+    async fn self_test(&mut self);
 }
 
-/// State machine for alternating LED string activation.
-///
-/// Cycles through four states to create an alternating red/green pattern:
-/// GreenOff → Red → RedOff → Green → (repeat)
-#[derive(Default)]
-enum ActiveString {
-    /// Green string is off, transitioning to red
-    #[default]
-    GreenOff,
-    /// Red string is active
-    Red,
-    /// Red string is off, transitioning to green
-    RedOff,
-    /// Green string is active
-    Green,
+/// Controller for managing LED strings with a selectable pattern.
+///
+/// Steps through the frames of a [`Pattern`] on any [`LedBackend`]. String
+/// 0 is used for warning overlays.
+#[cfg(not(led_backend = "rgb"))]
+pub struct StringController<B: LedBackend> {
+    /// Hardware latching the LED strings
+    backend: B,
+    /// Pattern being displayed
+    pattern: Pattern,
+    /// Index of the next frame of the pattern to latch
+    step: usize,
+    /// Sync leader's step period in milliseconds, while following one
+    #[cfg(sync_link)]
+    step_ms: Option<u32>,
+    /// Random schedule of each string for the Twinkle pattern
+    twinkle: Twinkle<STRING_COUNT>,
+    /// Random numbers for the Twinkle pattern
+    rng: Xorshift32,
+    /// Text and speed of the Message pattern
+    message: Message,
+    /// Position in the message
+    cursor: Cursor,
+    /// Index of the next cue of the light show
+    cue: usize,
 }
 
-/// Controller for managing two LED strings with alternating pattern.
-///
-/// Coordinates two flip-flops to create an alternating red/green display
-/// pattern. Each string can also be sensed via feedback inputs (unused
-/// in current implementation).
-pub struct StringController {
-    /// Flip-flop controlling red LED string (LSTR1)
-    red_flop: FlipFlop,
-    /// Flip-flop controlling green LED string (LSTR2)
-    green_flop: FlipFlop,
-    /// Feedback input from red string (PB4, currently unused)
-    _red_string: Input<'static>,
-    /// Feedback input from green string (PA6, currently unused)
-    _green_string: Input<'static>,
-    /// Current state in the alternating pattern
-    active_string: ActiveString,
-}
+#[cfg(not(led_backend = "rgb"))]
+impl<B: LedBackend> StringController<B> {
+    /// Number of strings, checked at compile time to fit in a [`Frame`].
+    const STRINGS: usize = {
+        assert!(
+            B::STRINGS > 0 && B::STRINGS <= 32,
+            "1 to 32 LED strings are supported"
+        );
+        B::STRINGS
+    };
 
-impl StringController {
     /// Creates a new StringController.
     ///
     /// # Arguments
     ///
-    /// * `red_flop` - FlipFlop controlling red LED string
-    /// * `green_flop` - FlipFlop controlling green LED string
-    /// * `red_string` - Feedback input from red string (reserved for future use)
-    /// * `green_string` - Feedback input from green string (reserved for future use)
-    pub fn new(
-        red_flop: FlipFlop,
-        green_flop: FlipFlop,
-        red_string: Input<'static>,
-        green_string: Input<'static>,
-    ) -> Self {
+    /// * `backend` - Hardware latching the LED strings
+    pub fn new(backend: B) -> Self {
         Self {
-            red_flop,
-            green_flop,
-            _red_string: red_string,
-            _green_string: green_string,
-            active_string: ActiveString::default(),
+            backend,
+            pattern: Pattern::default(),
+            step: 0,
+            #[cfg(sync_link)]
+            step_ms: None,
+            twinkle: Twinkle::new(STRING_TWINKLE),
+            rng: Xorshift32::new(hardware_seed()),
+            message: Message::default(),
+            cursor: Cursor::new(),
+            cue: 0,
         }
     }
 
-    /// Resets both LED strings to initial state.
+    /// Replaces the message played by the Message pattern, restarting it.
     ///
-    /// Explicitly clears both flip-flops to Q=LOW (LEDs OFF),
-    /// then releases reset controls for normal operation.
-    pub fn reset(&mut self) {
+    /// # Arguments
+    ///
+    /// * `message` - Text and speed, usually loaded with
+    ///   [`crate::morse_message::load_configured`]
+    pub fn set_message(&mut self, message: Message) {
+        self.message = message;
+        self.cursor = Cursor::new();
+    }
+
+    /// Returns the frame currently latched by the backend.
+    pub fn latched(&self) -> Frame {
+        self.backend.latched()
+    }
+
+    /// Latches a frame onto the strings.
+    pub fn apply(&mut self, frame: Frame) {
+        self.backend.apply(frame);
+    }
+
+    /// Returns the pattern and the step last shown, for the sync leader to
+    /// broadcast, or None while playing the message or the light show
+    /// (see [`crate::sync`]).
+    #[cfg(sync_link)]
+    pub fn sync_frame(&self) -> Option<SyncFrame> {
+        if matches!(self.pattern, Pattern::Message | Pattern::Show) {
+            return None;
+        }
+        let len = self.pattern.len(Self::STRINGS);
+        Some(SyncFrame {
+            pattern: self.pattern,
+            step: ((self.step + len - 1) % len) as u8,
+        })
+    }
+
+    /// Jumps to the sync leader's pattern and step and adopts its step
+    /// period.
+    ///
+    /// The frame the leader shows next is latched at the next step.
+    #[cfg(sync_link)]
+    pub fn follow(&mut self, point: &SyncPoint) {
+        if point.frame.pattern != self.pattern {
+            self.set_pattern(point.frame.pattern);
+        }
+        let len = self.pattern.len(Self::STRINGS);
+        self.step = (usize::from(point.frame.step) + 1) % len;
+        // Rounded to whole milliseconds; each frame re-anchors the phase
+        self.step_ms = Some((point.period.as_micros() + 500) as u32 / 1000);
+    }
+
+    /// Flashes a sync frame on all strings for optical followers, then
+    /// restores the latched frame.
+    #[cfg(sync_link)]
+    pub async fn flash_sync_frame(&mut self, frame: SyncFrame) {
+        let saved = self.latched();
+
+        self.apply(Frame::OFF);
+        Timer::after_millis(sync_protocol::GAP_MS).await;
+        for width in sync_protocol::pulses(frame) {
+            self.apply(Frame::all(Self::STRINGS));
+            Timer::after_millis(width).await;
+            self.apply(Frame::OFF);
+            Timer::after_millis(sync_protocol::GAP_MS).await;
+        }
+
+        self.apply(saved);
+    }
+}
+
+#[cfg(not(led_backend = "rgb"))]
+impl<B: LedBackend> LedEngine for StringController<B> {
+    const STEP_MS: u32 = LED_CYCLE_MS;
+
+    fn step_ms(&self) -> u32 {
+        match self.pattern {
+            Pattern::Message => return self.message.unit_ms(),
+            Pattern::Show => return SHOW.cue(self.cue).delay_ms.into(),
+            _ => {}
+        }
+        #[cfg(sync_link)]
+        if let Some(step_ms) = self.step_ms {
+            return step_ms;
+        }
+        LED_CYCLE_MS
+    }
+
+    /// Resets all LED strings to initial state (LEDs OFF).
+    fn reset(&mut self) {
         #[cfg(feature = "debug-mode")]
-        defmt::info!("Resetting LED strings...");
+        defmt::info!("Resetting {} LED strings...", Self::STRINGS);
 
-        // First, release any preset/clear to allow normal operation
-        self.red_flop.release_reset();
-        self.green_flop.release_reset();
+        self.backend.reset();
 
-        // Explicitly clock both flip-flops to LOW state (LEDs OFF)
-        self.red_flop.clock_q_low();
-        self.green_flop.clock_q_low();
+        #[cfg(feature = "debug-mode")]
+        defmt::info!("All LED strings initialized to OFF");
 
+        self.step = 0;
+    }
+
+    fn lit_strings(&self) -> u32 {
+        self.backend.lit_strings()
+    }
+
+    fn pattern(&self) -> Pattern {
+        self.pattern
+    }
+
+    fn set_pattern(&mut self, pattern: Pattern) {
         #[cfg(feature = "debug-mode")]
-        defmt::info!("Both LED strings initialized to OFF");
+        defmt::info!("Pattern: {}", pattern);
 
-        self.active_string = ActiveString::default();
+        self.pattern = pattern;
+        self.step = 0;
+        match pattern {
+            Pattern::Twinkle => self.twinkle.restart(&mut self.rng),
+            Pattern::Message => self.cursor = Cursor::new(),
+            Pattern::Show => self.cue = 0,
+            _ => {}
+        }
     }
 
-    /// Advances to the next state in the LED pattern.
-    ///
-    /// Implements the state machine:
-    /// ```text
-    /// GreenOff → Red (turn red ON)
-    /// Red      → RedOff (turn red OFF)
-    /// RedOff   → Green (turn green ON)
-    /// Green    → GreenOff (turn green OFF)
-    /// ```
-    ///
-    /// Call this periodically to create the alternating red/green display pattern.
-    pub fn activate_next_string(&mut self) {
-        self.active_string = match self.active_string {
-            ActiveString::GreenOff => {
-                #[cfg(feature = "debug-mode")]
-                defmt::info!("State: GreenOff -> Red (turning red ON)");
-                self.red_flop.clock_q_high();
-                ActiveString::Red
+    /// Latches the next frame of the pattern.
+    async fn advance(&mut self) {
+        let len = self.pattern.len(Self::STRINGS);
+        let frame = match self.pattern {
+            Pattern::Twinkle => self.twinkle.step(&mut self.rng),
+            Pattern::Message => match self.cursor.next(self.message.text()) {
+                morse::Step::Mark => Frame::single(0),
+                morse::Step::Space => Frame::OFF,
+                morse::Step::WordGap if Self::STRINGS > 1 => Frame::single(1),
+                morse::Step::WordGap => Frame::OFF,
+            },
+            Pattern::Show => {
+                let frame = SHOW.cue(self.cue).frame;
+                self.cue = (self.cue + 1) % SHOW.cue_count();
+                frame
             }
-            ActiveString::Red => {
+            pattern => pattern.frame(self.step % len, Self::STRINGS),
+        };
+
+        #[cfg(feature = "debug-mode")]
+        defmt::info!("Pattern step {}: {}", self.step, frame);
+
+        self.apply(frame);
+        self.step = (self.step + 1) % len;
+    }
+
+    async fn all_off(&mut self) {
+        self.apply(Frame::OFF);
+    }
+
+    /// All strings are turned off for the duration of the overlay and then
+    /// restored to their latched state, leaving the pattern state machine
+    /// untouched.
+    async fn play_overlay(&mut self, overlay: &Overlay) {
+        let saved = self.latched();
+
+        self.apply(Frame::OFF);
+        Timer::after_millis(OVERLAY_BLINK_OFF_MS).await;
+
+        match overlay {
+            Overlay::LowBattery => {
                 #[cfg(feature = "debug-mode")]
-                defmt::info!("State: Red -> RedOff (turning red OFF)");
-                self.red_flop.clock_q_low();
-                ActiveString::RedOff
+                defmt::info!("Overlay: low battery double-blink");
+
+                for _ in 0..2 {
+                    self.apply(Frame::single(0));
+                    Timer::after_millis(OVERLAY_BLINK_ON_MS).await;
+                    self.apply(Frame::OFF);
+                    Timer::after_millis(OVERLAY_BLINK_OFF_MS).await;
+                }
+            }
+        }
+
+        self.apply(saved);
+    }
+
+    async fn blackout(&mut self, until: impl Future<Output = ()>) {
+        let saved = self.latched();
+
+        self.apply(Frame::OFF);
+        until.await;
+        self.apply(saved);
+    }
+
+    async fn self_test(&mut self) {
+        #[cfg(feature = "debug-mode")]
+        defmt::info!("LED self-test");
+
+        let saved = self.latched();
+
+        for frame in (0..Self::STRINGS)
+            .map(Frame::single)
+            .chain([Frame::all(Self::STRINGS)])
+        {
+            self.apply(frame);
+            Timer::after_millis(SELF_TEST_STEP_MS).await;
+        }
+
+        self.apply(saved);
+    }
+}
+
+/// Async task that owns the LEDs and steps the display pattern.
+///
+/// Shows the next step of the pattern after [`LedEngine::step_ms`] of the
+/// board's engine, on a drift-free [`StepSchedule`]. The deadline for the
+/// next step is kept across commands, so commands arriving mid-step do not
+/// restart the step. While paused or turned off the task waits on
+/// [`LED_COMMANDS`] alone and causes no wake-ups. Plays the low battery
+/// warning overlay every [`LOW_BATTERY_WARNING_INTERVAL`] while the shared
+/// system state has the warning raised, and publishes the selected pattern
+/// there along with whether it is playing.
+///
+/// On boards with a light sensor, ambient light is measured every
+/// [`LIGHT_SENSE_INTERVAL`] with the LEDs briefly turned off, and the
+/// pattern only runs while a [`DarkDetector`] reports darkness; in daylight
+/// the LEDs are turned off.
+///
+/// # Arguments
+///
+/// * `led_ctrl` - LED engine for the board's LEDs (takes ownership)
+/// * `light_sensor` - The board's light sensor, if any (takes ownership)
+///
+/// # Example
+///
+/// ```no_run
+/// spawner.spawn(led_task(peripherals.led_ctrl, peripherals.light_sensor)).unwrap();
+/// LED_COMMANDS.send(LedCommand::SelfTest).await;
+/// ```
+#[embassy_executor::task]
+pub async fn led_task(led_ctrl: LedController, light_sensor: AmbientSensor) {
+    activity::tracked(Task::Led, run_leds(led_ctrl, light_sensor)).await
+}
+
+/// Body of [`led_task`].
+async fn run_leds(mut led_ctrl: LedController, mut light_sensor: AmbientSensor) {
+    #[cfg(feature = "debug-mode")]
+    defmt::info!("LED task started");
+
+    let mut system_state = SYSTEM_STATE.receiver().unwrap();
+    let mut low_battery_warning = false;
+    let mut next_warning = Instant::now();
+    let mut running = true;
+    let mut schedule = StepSchedule::starting_at(Instant::now());
+    let mut detector = DarkDetector::new();
+    #[cfg(light_sensor)]
+    let mut next_sense = Instant::now();
+
+    loop {
+        let dark = detector.ambient() == Ambient::Dark;
+        let step_due = async {
+            if running && dark {
+                Timer::at(schedule.deadline()).await
+            } else {
+                core::future::pending().await
             }
-            ActiveString::RedOff => {
+        };
+        #[cfg(light_sensor)]
+        let sense_due = Timer::at(next_sense);
+        #[cfg(not(light_sensor))]
+        let sense_due = core::future::pending::<()>();
+
+        match select3(LED_COMMANDS.receive(), step_due, sense_due).await {
+            Either3::First(command) => {
                 #[cfg(feature = "debug-mode")]
-                defmt::info!("State: RedOff -> Green (turning green ON)");
-                self.green_flop.clock_q_high();
-                ActiveString::Green
+                defmt::info!("LED command: {}", command);
+
+                match command {
+                    // Relative to the pattern shown, even after a sync leader
+                    // changed it
+                    LedCommand::NextPattern | LedCommand::PreviousPattern => {
+                        let pattern = if command == LedCommand::NextPattern {
+                            led_ctrl.pattern().next()
+                        } else {
+                            led_ctrl.pattern().previous()
+                        };
+                        event_log::record(Kind::Pattern, pattern as u8);
+                        led_ctrl.set_pattern(pattern);
+                        running = true;
+                        schedule = StepSchedule::starting_at(Instant::now());
+                    }
+                    LedCommand::ToggleLeds if running => {
+                        event_log::record(Kind::LedsOff, 0);
+                        led_ctrl.all_off().await;
+                        running = false;
+                    }
+                    LedCommand::ToggleLeds => {
+                        event_log::record(Kind::Pattern, led_ctrl.pattern() as u8);
+                        running = true;
+                        schedule = StepSchedule::starting_at(Instant::now());
+                    }
+                    LedCommand::Pause => running = false,
+                    LedCommand::Resume => {
+                        if !running {
+                            running = true;
+                            schedule = StepSchedule::starting_at(Instant::now());
+                        }
+                    }
+                    LedCommand::Overlay(overlay) => led_ctrl.play_overlay(&overlay).await,
+                    LedCommand::AllOff => {
+                        led_ctrl.all_off().await;
+                        running = false;
+                    }
+                    LedCommand::SelfTest => led_ctrl.self_test().await,
+                    LedCommand::Blackout => {
+                        led_ctrl
+                            .blackout(async {
+                                set_led_load(0);
+                                BLACKOUT_STARTED.signal(());
+                                BLACKOUT_ENDED.wait().await;
+                            })
+                            .await
+                    }
+                    #[cfg(sync_link)]
+                    LedCommand::Sync(point) => {
+                        led_ctrl.follow(&point);
+                        schedule = StepSchedule::starting_at(point.next_step);
+                    }
+                    #[cfg(sync_link)]
+                    LedCommand::SyncFlash(frame) => led_ctrl.flash_sync_frame(frame).await,
+                }
+            }
+            Either3::Second(()) => {
+                // The state also changes with this task's own updates below
+                if let Some(state) = system_state.try_changed()
+                    && state.low_battery_warning != low_battery_warning
+                {
+                    #[cfg(feature = "debug-mode")]
+                    defmt::info!("Low battery warning: {}", state.low_battery_warning);
+
+                    low_battery_warning = state.low_battery_warning;
+                    next_warning = Instant::now();
+                }
+
+                if low_battery_warning && Instant::now() >= next_warning {
+                    led_ctrl.play_overlay(&Overlay::LowBattery).await;
+                    next_warning = Instant::now() + LOW_BATTERY_WARNING_INTERVAL;
+                }
+
+                led_ctrl.advance().await;
+                schedule.advance(led_ctrl.step_ms(), Instant::now());
+
+                #[cfg(sync_link)]
+                if cfg!(feature = "sync-leader")
+                    && let Some(frame) = led_ctrl.sync_frame()
+                {
+                    sync::SYNC_TICKS.signal(frame);
+                }
             }
-            ActiveString::Green => {
+            Either3::Third(()) => {
+                let Some(decay_us) = measure_light(&mut led_ctrl, &mut light_sensor).await else {
+                    continue;
+                };
+                #[cfg(light_sensor)]
+                {
+                    next_sense = Instant::now() + LIGHT_SENSE_INTERVAL;
+                }
+
                 #[cfg(feature = "debug-mode")]
-                defmt::info!("State: Green -> GreenOff (turning green OFF)");
-                self.green_flop.clock_q_low();
-                ActiveString::GreenOff
+                defmt::info!("Light sense decay: {}us", decay_us);
+
+                match detector.update(decay_us) {
+                    Some(Ambient::Light) => led_ctrl.all_off().await,
+                    Some(Ambient::Dark) => schedule = StepSchedule::starting_at(Instant::now()),
+                    None => {}
+                }
             }
         }
+
+        set_led_load(led_ctrl.lit_strings());
+        let pattern = led_ctrl.pattern();
+        let leds_on = running && detector.ambient() == Ambient::Dark;
+        system_state::update(|state| {
+            state.pattern = pattern;
+            state.leds_on = leds_on;
+        });
     }
 }
+
+/// Measures ambient light with the LEDs turned off.
+///
+/// # Arguments
+///
+/// * `led_ctrl` - LED engine, blacked out during the measurement
+/// * `light_sensor` - The board's light sensor
+///
+/// # Returns
+///
+/// A decay time for [`DarkDetector`]
+#[cfg(light_sensor)]
+async fn measure_light(
+    led_ctrl: &mut LedController,
+    light_sensor: &mut AmbientSensor,
+) -> Option<u32> {
+    let mut decay_us = 0;
+    led_ctrl
+        .blackout(async { decay_us = light_sensor.decay_us() })
+        .await;
+    Some(decay_us)
+}
+
+/// Stand-in for [`measure_light`] on boards without a light sensor, whose
+/// LED task never senses light and stays in the dark state.
+///
+/// # Returns
+///
+/// None
+#[cfg(not(light_sensor))]
+async fn measure_light(
+    _led_ctrl: &mut LedController,
+    _light_sensor: &mut AmbientSensor,
+) -> Option<u32> {
+    None
+}
@@ -0,0 +1,136 @@
+//! Pattern synchronization between ornaments.
+//!
+//! Ornaments on the same tree share a sync link so they step their patterns
+//! together. One ornament, built with the `sync-leader` feature, is the
+//! leader: after every pattern step it broadcasts a [`SyncFrame`] with its
+//! pattern and step. Every other ornament follows: it decodes the frames,
+//! locks its step timing to the leader's with a [`PhaseLock`] and jumps to
+//! the leader's pattern and step.
+//!
+//! # Links
+//!
+//! The board description's `[sync]` section selects the link:
+//!
+//! - `wire`: a single line shared by all ornaments, pulled up on each
+//!   follower. The leader drives it open-drain, so a pulse is the line held
+//!   low.
+//! - `optical`: the leader flashes all its LEDs; each follower has a
+//!   phototransistor pulling its sync input low while lit.
+//!
+//! Either way a follower sees each pulse as the input held low, on an EXTI
+//! line so it sleeps in STOP mode between edges.
+//!
+//! # Protocol
+//!
+//! The frames and the phase lock are defined in [`crate::sync_protocol`].
+//! The leader sends no frames while it plays its Morse message or light
+//! show: the steps are too short to carry a frame each, and followers may
+//! carry different messages and shows. Followers keep their own pattern
+//! until the leader moves on.
+
+use embassy_stm32::exti::ExtiInput;
+#[cfg(sync_link = "wire")]
+use embassy_stm32::gpio::OutputOpenDrain;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+#[cfg(sync_link = "wire")]
+use embassy_time::Timer;
+use embassy_time::{Duration, Instant};
+
+use crate::activity;
+use crate::activity_meter::Task;
+use crate::hardware::LedController;
+use crate::string_controller::{LED_COMMANDS, LedCommand, LedEngine};
+use crate::sync_protocol::{FrameDecoder, PhaseLock, SyncFrame};
+#[cfg(sync_link = "wire")]
+use crate::sync_protocol::{GAP_MS, pulses};
+
+/// Follower position from a received frame, sent to the LED task.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct SyncPoint {
+    /// Leader's pattern and the step it has just shown
+    pub frame: SyncFrame,
+    /// Time of the next step
+    pub next_step: Instant,
+    /// Leader's step period, in local time
+    pub period: Duration,
+}
+
+/// Frames for the leader to broadcast, signalled by the LED task after
+/// every step (except while playing the Morse message or light show).
+pub static SYNC_TICKS: Signal<CriticalSectionRawMutex, SyncFrame> = Signal::new();
+
+/// This ornament's end of the sync link.
+pub enum SyncLink {
+    /// Leader driving the shared wire, open-drain and idle high
+    #[cfg(sync_link = "wire")]
+    WireLeader(OutputOpenDrain<'static>),
+    /// Leader flashing its LEDs
+    #[cfg(sync_link = "optical")]
+    OpticalLeader,
+    /// Follower listening on an EXTI input, pulled low during a pulse
+    Follower(ExtiInput<'static>),
+}
+
+/// Async task running this ornament's end of the sync link.
+///
+/// A leader sends every frame signalled on [`SYNC_TICKS`], on the wire
+/// itself or by asking the LED task to flash it. A follower decodes frames
+/// and sends the LED task a [`SyncPoint`] for each.
+///
+/// # Arguments
+///
+/// * `link` - Sync link (takes ownership)
+#[embassy_executor::task]
+pub async fn sync_task(link: SyncLink) {
+    activity::tracked(Task::Sync, run_sync(link)).await
+}
+
+/// Body of [`sync_task`].
+async fn run_sync(link: SyncLink) {
+    #[cfg(feature = "debug-mode")]
+    defmt::info!("Sync task started");
+
+    match link {
+        #[cfg(sync_link = "wire")]
+        SyncLink::WireLeader(mut line) => loop {
+            let frame = SYNC_TICKS.wait().await;
+            for width in pulses(frame) {
+                line.set_low();
+                Timer::after_millis(width).await;
+                line.set_high();
+                Timer::after_millis(GAP_MS).await;
+            }
+        },
+        #[cfg(sync_link = "optical")]
+        SyncLink::OpticalLeader => loop {
+            let frame = SYNC_TICKS.wait().await;
+            LED_COMMANDS.send(LedCommand::SyncFlash(frame)).await;
+        },
+        SyncLink::Follower(mut input) => {
+            let mut decoder = FrameDecoder::new();
+            let mut lock = PhaseLock::new(Duration::from_millis(LedController::STEP_MS.into()));
+
+            loop {
+                input.wait_for_low().await;
+                let start = Instant::now().as_micros();
+                input.wait_for_high().await;
+                let end = Instant::now().as_micros();
+
+                let Some((frame, frame_start)) = decoder.pulse(start, end) else {
+                    continue;
+                };
+
+                #[cfg(feature = "debug-mode")]
+                defmt::info!("Sync frame: {}", frame);
+
+                let next_step = Instant::from_micros(lock.lock(frame_start));
+                let point = SyncPoint {
+                    frame,
+                    next_step,
+                    period: lock.period(),
+                };
+                LED_COMMANDS.send(LedCommand::Sync(point)).await;
+            }
+        }
+    }
+}
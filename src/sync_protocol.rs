@@ -0,0 +1,275 @@
+//! Sync link framing and phase lock.
+//!
+//! The protocol half of pattern synchronization (see [`crate::sync`]): how
+//! the leader's pattern and step are sent as pulses on the sync link, and
+//! how a follower decodes them and locks its step timing to the leader's.
+//!
+//! # Framing
+//!
+//! A frame is a start pulse followed by [`DATA_BITS`] data pulses, each
+//! separated by a [`GAP_MS`] gap:
+//!
+//! | Pulse | Width                |
+//! |-------|----------------------|
+//! | Start | [`START_PULSE_MS`]   |
+//! | 0     | [`ZERO_PULSE_MS`]    |
+//! | 1     | [`ONE_PULSE_MS`]     |
+//!
+//! The data bits, most significant first, are the pattern (3 bits), the
+//! pattern step (8 bits) and an even parity bit. A frame lasts at most
+//! 82 ms. Pulses of any other width (such as the leader's pattern seen by
+//! an optical follower), overlong gaps and parity errors discard the frame.
+//! The start pulse's leading edge marks the leader's step time.
+//!
+//! # Phase Lock
+//!
+//! Each frame re-anchors the follower's next step to one step period after
+//! the frame's start. The period itself is measured from the frame spacing,
+//! which corrects for the leader's and follower's clocks running at
+//! slightly different rates, so a follower that misses frames keeps in
+//! step with the leader for a while.
+//!
+//! [`FrameDecoder`] and [`PhaseLock`] are pure state machines fed with
+//! microsecond timestamps, so the host tests in
+//! `host-tests/tests/sync_protocol.rs` simulate a leader and several
+//! followers with drifting clocks.
+
+use embassy_time::Duration;
+
+use crate::pattern::Pattern;
+
+/// Width of the start pulse, in milliseconds.
+pub const START_PULSE_MS: u64 = 8;
+
+/// Width of a 0 data pulse, in milliseconds.
+pub const ZERO_PULSE_MS: u64 = 2;
+
+/// Width of a 1 data pulse, in milliseconds.
+pub const ONE_PULSE_MS: u64 = 4;
+
+/// Gap after every pulse, in milliseconds.
+pub const GAP_MS: u64 = 2;
+
+/// Number of data bits in a frame.
+pub const DATA_BITS: u32 = 12;
+
+/// Shortest pulse accepted, in microseconds; shorter ones are glitches.
+const MIN_PULSE_US: u64 = 1_000;
+
+/// Boundary between 0 and 1 data pulses, in microseconds.
+const ZERO_ONE_US: u64 = 3_000;
+
+/// Boundary between 1 data pulses and start pulses, in microseconds.
+const ONE_START_US: u64 = 6_000;
+
+/// Longest pulse accepted, in microseconds.
+const MAX_PULSE_US: u64 = 12_000;
+
+/// Longest gap accepted between pulses of a frame, in microseconds.
+const MAX_GAP_US: u64 = 4_000;
+
+/// Largest accepted difference between the leader's step period and the
+/// follower's nominal one, in parts per million.
+pub const MAX_PERIOD_ERROR_PPM: u64 = 20_000;
+
+/// Most missed frames bridged when measuring the step period.
+const MAX_MISSED_FRAMES: u64 = 8;
+
+/// Weight of each new period measurement, as a right shift (1/8).
+const PERIOD_FILTER_SHIFT: u32 = 3;
+
+/// Pattern and step broadcast by the leader.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub struct SyncFrame {
+    /// Pattern the leader is showing
+    pub pattern: Pattern,
+    /// Step of the pattern the leader has just shown
+    pub step: u8,
+}
+
+impl SyncFrame {
+    /// Returns the frame's data bits, the first bit to send most
+    /// significant.
+    pub fn to_bits(self) -> u16 {
+        let pattern = match self.pattern {
+            Pattern::Alternate => 0,
+            Pattern::Swap => 1,
+            Pattern::Together => 2,
+            Pattern::Twinkle => 3,
+            Pattern::Message => 4,
+            Pattern::Show => 5,
+        };
+        let data = (pattern << 8) | u16::from(self.step);
+        (data << 1) | (data.count_ones() as u16 & 1)
+    }
+
+    /// Decodes a frame's data bits, or returns None if parity fails or
+    /// the pattern is unknown.
+    pub fn from_bits(bits: u16) -> Option<Self> {
+        if !bits.count_ones().is_multiple_of(2) {
+            return None;
+        }
+        let pattern = match (bits >> 9) & 0b111 {
+            0 => Pattern::Alternate,
+            1 => Pattern::Swap,
+            2 => Pattern::Together,
+            3 => Pattern::Twinkle,
+            4 => Pattern::Message,
+            5 => Pattern::Show,
+            _ => return None,
+        };
+        Some(Self {
+            pattern,
+            step: (bits >> 1) as u8,
+        })
+    }
+}
+
+/// Returns the pulse widths of a frame in milliseconds, in sending order.
+///
+/// Each pulse is followed by a [`GAP_MS`] gap.
+pub fn pulses(frame: SyncFrame) -> impl Iterator<Item = u64> {
+    let bits = frame.to_bits();
+    let data = (0..DATA_BITS).rev().map(move |bit| {
+        if bits & (1 << bit) != 0 {
+            ONE_PULSE_MS
+        } else {
+            ZERO_PULSE_MS
+        }
+    });
+    core::iter::once(START_PULSE_MS).chain(data)
+}
+
+/// Decodes frames from the pulses seen on the sync input.
+pub struct FrameDecoder {
+    /// Start time of the frame being received, if any, in µs
+    frame_start: Option<u64>,
+    /// End time of the last pulse, in µs
+    last_end: u64,
+    /// Data bits received so far
+    bits: u16,
+    /// Number of data bits received so far
+    count: u32,
+}
+
+impl FrameDecoder {
+    /// Creates a decoder waiting for a start pulse.
+    pub const fn new() -> Self {
+        Self {
+            frame_start: None,
+            last_end: 0,
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    /// Feeds one pulse to the decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `start_us` - Time the input went low, in microseconds
+    /// * `end_us` - Time the input went high again, in microseconds
+    ///
+    /// # Returns
+    ///
+    /// The frame completed by this pulse and the time its start pulse
+    /// began, if any
+    pub fn pulse(&mut self, start_us: u64, end_us: u64) -> Option<(SyncFrame, u64)> {
+        let width = end_us.saturating_sub(start_us);
+        let gap = start_us.saturating_sub(self.last_end);
+        self.last_end = end_us;
+
+        let bit = match width {
+            MIN_PULSE_US..ZERO_ONE_US => 0,
+            ZERO_ONE_US..ONE_START_US => 1,
+            ONE_START_US..=MAX_PULSE_US => {
+                self.frame_start = Some(start_us);
+                self.bits = 0;
+                self.count = 0;
+                return None;
+            }
+            _ => {
+                self.frame_start = None;
+                return None;
+            }
+        };
+
+        let frame_start = self.frame_start.filter(|_| gap <= MAX_GAP_US);
+        let Some(frame_start) = frame_start else {
+            self.frame_start = None;
+            return None;
+        };
+
+        self.bits = (self.bits << 1) | bit;
+        self.count += 1;
+        if self.count < DATA_BITS {
+            return None;
+        }
+
+        self.frame_start = None;
+        SyncFrame::from_bits(self.bits).map(|frame| (frame, frame_start))
+    }
+}
+
+/// Locks the follower's step timing to the leader's frames.
+pub struct PhaseLock {
+    /// Nominal step period, in µs
+    nominal_us: u64,
+    /// Measured step period of the leader, in µs
+    period_us: u64,
+    /// Start time of the last frame, in µs
+    last_frame: Option<u64>,
+}
+
+impl PhaseLock {
+    /// Creates a phase lock assuming the leader steps at the nominal rate.
+    ///
+    /// # Arguments
+    ///
+    /// * `nominal` - Nominal step period
+    pub const fn new(nominal: Duration) -> Self {
+        Self {
+            nominal_us: nominal.as_micros(),
+            period_us: nominal.as_micros(),
+            last_frame: None,
+        }
+    }
+
+    /// Returns the leader's step period as measured in local time.
+    pub fn period(&self) -> Duration {
+        Duration::from_micros(self.period_us)
+    }
+
+    /// Feeds the start time of a received frame.
+    ///
+    /// The time since the last frame, divided by the whole number of steps
+    /// it spans, refines the measured period. Spacings that are not close to
+    /// a whole number of steps (such as after the leader changed pattern,
+    /// restarting its step timing) only re-anchor the phase.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_start_us` - Time the frame's start pulse began, in µs
+    ///
+    /// # Returns
+    ///
+    /// The time of the follower's next step, in µs
+    pub fn lock(&mut self, frame_start_us: u64) -> u64 {
+        if let Some(last) = self.last_frame {
+            let interval = frame_start_us - last;
+            let steps = (interval + self.period_us / 2) / self.period_us;
+            if (1..=MAX_MISSED_FRAMES + 1).contains(&steps) {
+                let measured = interval / steps;
+                let tolerance = self.nominal_us * MAX_PERIOD_ERROR_PPM / 1_000_000;
+                if measured.abs_diff(self.nominal_us) <= tolerance {
+                    let error = measured as i64 - self.period_us as i64;
+                    self.period_us =
+                        (self.period_us as i64 + (error >> PERIOD_FILTER_SHIFT)) as u64;
+                }
+            }
+        }
+
+        self.last_frame = Some(frame_start_us);
+        frame_start_us + self.period_us
+    }
+}
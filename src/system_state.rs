@@ -0,0 +1,83 @@
+//! Shared system state for communication between tasks.
+//!
+//! Each task owns its hardware outright (the power task owns the load
+//! switches, the LED task owns the LEDs), so tasks communicate through
+//! a single statically allocated [`SystemState`] published on an
+//! `embassy_sync` [`Watch`]. Producers update the fields they own with
+//! [`update`], and any task can take a receiver to react to changes or read
+//! the latest snapshot with `SYSTEM_STATE.try_get()`.
+//!
+//! Receivers are only notified when a field actually changes, so periodic
+//! republishing of unchanged values does not wake anyone.
+
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, watch::Watch};
+
+use crate::pattern::Pattern;
+use crate::power_state::PowerState;
+
+/// Maximum number of tasks that can hold a [`SYSTEM_STATE`] receiver.
+const MAX_RECEIVERS: usize = 2;
+
+/// Fault conditions reported by the firmware.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Fault {
+    /// Writing persistent state to EEPROM failed
+    Storage,
+}
+
+/// Snapshot of system-wide state shared between tasks.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct SystemState {
+    /// Battery currently powering the ornament
+    pub power: PowerState,
+    /// Estimated charge remaining per battery slot, in percent
+    pub battery_percent: [u8; 2],
+    /// True while the low battery warning overlay should be shown
+    pub low_battery_warning: bool,
+    /// Most recent unresolved fault, if any
+    pub fault: Option<Fault>,
+    /// Pattern selected on the LEDs
+    pub pattern: Pattern,
+    /// True while the pattern is playing: not switched off, paused, or
+    /// waiting for darkness
+    pub leds_on: bool,
+}
+
+impl SystemState {
+    /// State at boot: main battery active, both cells assumed full, first
+    /// pattern playing.
+    const INITIAL: Self = Self {
+        power: PowerState::MainPower,
+        battery_percent: [100, 100],
+        low_battery_warning: false,
+        fault: None,
+        pattern: Pattern::Alternate,
+        leds_on: true,
+    };
+}
+
+/// Latest system state, always populated.
+pub static SYSTEM_STATE: Watch<CriticalSectionRawMutex, SystemState, MAX_RECEIVERS> =
+    Watch::new_with(SystemState::INITIAL);
+
+/// Updates the shared system state.
+///
+/// Receivers are notified only if the closure changed the state.
+///
+/// # Arguments
+///
+/// * `f` - Closure applying the update to the current state
+pub fn update(f: impl Fn(&mut SystemState)) {
+    update_with(&f);
+}
+
+/// Applies an update to the shared system state, shared by every caller of
+/// [`update`] rather than instantiated for each closure.
+fn update_with(f: &dyn Fn(&mut SystemState)) {
+    SYSTEM_STATE.sender().send_if_modified(|state| {
+        let state = state.get_or_insert(SystemState::INITIAL);
+        let before = *state;
+        f(state);
+        *state != before
+    });
+}
@@ -0,0 +1,136 @@
+//! Random twinkle pattern generator.
+//!
+//! Each LED switches on and off on its own random schedule: an on-time and
+//! an off-time are drawn from [`TwinkleConfig`] bounds every time it
+//! changes state. Durations are counted in pattern steps, so the same
+//! generator runs on the 1 s steps of the LED strings and the 50 ms steps
+//! of the RGB engine.
+//!
+//! # Duty Limit
+//!
+//! Every on period lasts at most `max_on` steps and is followed by at least
+//! `min_off` steps off, so no LED is lit for more than
+//! `max_on / (max_on + min_off)` of the time. [`TwinkleConfig::new`] refuses
+//! bounds above [`MAX_DUTY_PERCENT`], which keeps the twinkle within the
+//! battery budget of the fixed patterns.
+
+use crate::led_backend::Frame;
+use crate::prng::Xorshift32;
+
+/// Highest worst-case duty of a twinkling LED, in percent (that of the
+/// Together pattern).
+pub const MAX_DUTY_PERCENT: u32 = 50;
+
+/// Bounds of the random on- and off-times, in pattern steps.
+#[derive(Clone, Copy)]
+pub struct TwinkleConfig {
+    /// Shortest on-time
+    min_on: u16,
+    /// Longest on-time
+    max_on: u16,
+    /// Shortest off-time
+    min_off: u16,
+    /// Longest off-time
+    max_off: u16,
+}
+
+impl TwinkleConfig {
+    /// Creates twinkle bounds, checking them at compile time when used in
+    /// a constant.
+    ///
+    /// # Arguments
+    ///
+    /// * `on` - Shortest and longest on-time, in steps
+    /// * `off` - Shortest and longest off-time, in steps
+    ///
+    /// # Panics
+    ///
+    /// If a bound is zero, a range is reversed or the worst-case duty is
+    /// above [`MAX_DUTY_PERCENT`]
+    pub const fn new(on: (u16, u16), off: (u16, u16)) -> Self {
+        let config = Self {
+            min_on: on.0,
+            max_on: on.1,
+            min_off: off.0,
+            max_off: off.1,
+        };
+        assert!(on.0 > 0 && on.0 <= on.1, "invalid twinkle on-time bounds");
+        assert!(
+            off.0 > 0 && off.0 <= off.1,
+            "invalid twinkle off-time bounds"
+        );
+        assert!(
+            config.max_duty_percent() <= MAX_DUTY_PERCENT,
+            "twinkle duty above MAX_DUTY_PERCENT"
+        );
+        config
+    }
+
+    /// Returns the worst-case fraction of time an LED is lit, in percent
+    /// (rounded up).
+    pub const fn max_duty_percent(&self) -> u32 {
+        let on = self.max_on as u32;
+        let period = on + self.min_off as u32;
+        (on * 100).div_ceil(period)
+    }
+}
+
+/// Twinkle state of `N` LEDs.
+pub struct Twinkle<const N: usize> {
+    /// Duration bounds
+    config: TwinkleConfig,
+    /// LEDs currently lit
+    lit: Frame,
+    /// Steps left before each LED changes state
+    remaining: [u16; N],
+}
+
+impl<const N: usize> Twinkle<N> {
+    /// Creates a twinkle generator with every LED off.
+    ///
+    /// Call [`Self::restart`] before the first [`Self::step`] so the LEDs
+    /// start out of step.
+    pub const fn new(config: TwinkleConfig) -> Self {
+        Self {
+            config,
+            lit: Frame::OFF,
+            remaining: [0; N],
+        }
+    }
+
+    /// Turns every LED off and gives each a random initial off-time of up
+    /// to `max_off` steps.
+    pub fn restart(&mut self, rng: &mut Xorshift32) {
+        self.lit = Frame::OFF;
+        for remaining in &mut self.remaining {
+            *remaining = rng.range(0, u32::from(self.config.max_off)) as u16;
+        }
+    }
+
+    /// Advances one step.
+    ///
+    /// # Returns
+    ///
+    /// The LEDs lit during this step
+    pub fn step(&mut self, rng: &mut Xorshift32) -> Frame {
+        let config = self.config;
+
+        for (n, remaining) in self.remaining.iter_mut().enumerate() {
+            if *remaining > 0 {
+                *remaining -= 1;
+                continue;
+            }
+
+            self.lit = Frame(self.lit.0 ^ Frame::single(n).0);
+            let (min, max) = if self.lit.is_on(n) {
+                (config.min_on, config.max_on)
+            } else {
+                (config.min_off, config.max_off)
+            };
+            // This step counts towards the new state's duration
+            *remaining = rng.range(u32::from(min), u32::from(max)) as u16 - 1;
+        }
+
+        self.lit
+    }
+}
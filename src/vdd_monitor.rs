@@ -0,0 +1,75 @@
+//! Supply voltage measurement using the internal voltage reference.
+//!
+//! VDD is not wired to an ADC pin, but the internal reference (VREFINT) is
+//! factory-calibrated at VDDA = 3.0V. Converting VREFINT against the supply
+//! and scaling by the calibration value gives VDD:
+//!
+//! ```text
+//! VDD = 3000mV * VREFINT_CAL / VREFINT_DATA
+//! ```
+//!
+//! The measured voltage is the active battery less the OR-ing Schottky
+//! diode drop, so it is only a relative indication of cell health.
+//!
+//! The ADC is powered only for the duration of a measurement and is
+//! disabled again before the MCU returns to STOP mode. The system clock is
+//! boosted for the measurement (see [`crate::clock`]).
+
+use embassy_stm32::{
+    Peri,
+    adc::{Adc, SampleTime, vals::Ckmode},
+    peripherals::ADC1,
+};
+
+use crate::clock;
+use crate::hardware::Irqs;
+
+/// Address of the factory VREFINT calibration value (RM0377 / DS10668).
+const VREFINT_CAL_ADDR: *const u16 = 0x1FF8_0078 as *const u16;
+
+/// VDDA at which VREFINT_CAL was measured, in millivolts.
+const VREFINT_CAL_VDDA_MV: u32 = 3000;
+
+/// Measures VDD through the ADC internal reference channel.
+pub struct VddMonitor {
+    /// ADC peripheral, enabled only while measuring
+    adc: Peri<'static, ADC1>,
+}
+
+impl VddMonitor {
+    /// Creates a new VddMonitor.
+    ///
+    /// # Arguments
+    ///
+    /// * `adc` - ADC1 peripheral
+    pub fn new(adc: Peri<'static, ADC1>) -> Self {
+        Self { adc }
+    }
+
+    /// Measures the current supply voltage.
+    ///
+    /// Powers up and calibrates the ADC with the system clock boosted,
+    /// converts VREFINT once, then powers the ADC back down.
+    ///
+    /// # Returns
+    ///
+    /// VDD in millivolts
+    pub async fn measure_mv(&mut self) -> u16 {
+        let _boost = clock::boost();
+        let mut adc = Adc::new(self.adc.reborrow(), Irqs);
+        if clock::is_boosted() {
+            // PCLK2 is halved while boosted; keep the ADC clock unchanged
+            adc.set_ckmode(Ckmode::PCLK);
+        }
+        // VREFINT needs at least 10µs of sampling
+        adc.set_sample_time(SampleTime::CYCLES12_5);
+        let mut vref = adc.enable_vref();
+        let vrefint_data = u32::from(adc.read(&mut vref).await);
+
+        // SAFETY: VREFINT_CAL is a read-only factory-programmed location
+        // in system memory that is always mapped.
+        let vrefint_cal = u32::from(unsafe { core::ptr::read_volatile(VREFINT_CAL_ADDR) });
+
+        (VREFINT_CAL_VDDA_MV * vrefint_cal / vrefint_data.max(1)) as u16
+    }
+}
@@ -0,0 +1,112 @@
+//! WS2812/SK6812 addressable LED driver over SPI.
+//!
+//! The single-wire WS2812 protocol is generated with the SPI1 peripheral.
+//! Each frame is encoded into a buffer first (see [`crate::ws2812_encoder`])
+//! and sent by DMA, so the bit timing does not depend on the CPU keeping
+//! up.
+//!
+//! # Power Gating
+//!
+//! The LEDs' supply is switched by an active-low load switch. It is turned
+//! off whenever a frame is entirely black, and turned back on (with a short
+//! power-up delay) before the next lit frame. The LEDs lose their state
+//! while unpowered, which is why every frame is sent in full.
+//!
+//! The supply is not switched off between lit frames. A WS2812 runs its own
+//! PWM from the color it latched and only lights while powered, so cutting
+//! the supply after each frame would leave the LEDs dark for nearly the
+//! whole pattern step, and every step would then wait [`POWER_UP_US`]
+//! before sending. The dark stretches of the sparkle and twinkle patterns,
+//! overlays, blackouts and switching the LEDs off are all-black frames, so
+//! the supply is off for those.
+
+use embassy_stm32::Peri;
+use embassy_stm32::gpio::Output;
+use embassy_stm32::mode::Async;
+use embassy_stm32::peripherals::SPI1;
+use embassy_stm32::spi::{self, MosiPin, Spi, TxDma};
+use embassy_stm32::time::Hertz;
+use embassy_time::Timer;
+
+use crate::rgb::Rgb;
+use crate::ws2812_encoder::{BYTES_PER_LED, encode};
+
+/// SPI clock, giving one WS2812 bit per three SPI bits.
+const SPI_FREQUENCY: Hertz = Hertz(2_000_000);
+
+/// Maximum number of LEDs in the chain.
+pub const MAX_LEDS: usize = 16;
+
+/// Time the LEDs need after their supply is switched on, in microseconds.
+const POWER_UP_US: u64 = 500;
+
+/// Driver for a chain of WS2812/SK6812 LEDs with a gated supply.
+pub struct Ws2812 {
+    /// SPI1 in transmit-only mode, MOSI driving the chain's data input
+    spi: Spi<'static, Async>,
+    /// Active-low enable of the LED supply load switch
+    power_n: Output<'static>,
+    /// True while the LED supply is on
+    powered: bool,
+    /// Encoded frame being sent
+    buffer: [u8; MAX_LEDS * BYTES_PER_LED],
+}
+
+impl Ws2812 {
+    /// Creates a new WS2812 driver with the LED supply off.
+    ///
+    /// # Arguments
+    ///
+    /// * `spi` - SPI1 peripheral
+    /// * `data` - SPI1 MOSI pin connected to the first LED's data input
+    /// * `dma` - DMA channel for SPI1 TX
+    /// * `power_n` - Active-low LED supply enable, initially high
+    pub fn new(
+        spi: Peri<'static, SPI1>,
+        data: Peri<'static, impl MosiPin<SPI1>>,
+        dma: Peri<'static, impl TxDma<SPI1>>,
+        power_n: Output<'static>,
+    ) -> Self {
+        let mut config = spi::Config::default();
+        config.frequency = SPI_FREQUENCY;
+
+        Self {
+            spi: Spi::new_txonly_nosck(spi, data, dma, config),
+            power_n,
+            powered: false,
+            buffer: [0; MAX_LEDS * BYTES_PER_LED],
+        }
+    }
+
+    /// Switches the LED supply off.
+    pub fn power_off(&mut self) {
+        self.power_n.set_high();
+        self.powered = false;
+    }
+
+    /// Shows a frame on the LEDs.
+    ///
+    /// An all-black frame switches the LED supply off instead of being sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `colors` - Color of each LED, at most [`MAX_LEDS`]
+    pub async fn show(&mut self, colors: &[Rgb]) {
+        if colors.iter().all(|led| led.is_black()) {
+            self.power_off();
+            return;
+        }
+
+        if !self.powered {
+            self.power_n.set_low();
+            self.powered = true;
+            Timer::after_micros(POWER_UP_US).await;
+        }
+
+        let len = encode(colors, &mut self.buffer);
+        if self.spi.write(&self.buffer[..len]).await.is_err() {
+            #[cfg(feature = "debug-mode")]
+            defmt::warn!("WS2812 frame transfer failed");
+        }
+    }
+}
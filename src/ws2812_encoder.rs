@@ -0,0 +1,77 @@
+//! WS2812/SK6812 bit stream encoder.
+//!
+//! Each WS2812 data bit becomes three SPI bits (`100` for a 0, `110` for
+//! a 1), so a short high pulse is a 0 and a long high pulse is a 1. The
+//! [`Ws2812`](crate::ws2812::Ws2812) driver sends the encoded buffer from
+//! SPI1 by DMA.
+//!
+//! # Timing
+//!
+//! With MSI at 4.194 MHz and the SPI clock divided by 2, one SPI bit lasts
+//! 0.48µs and one LED bit 1.43µs:
+//!
+//! | Bit | High   | Low    | WS2812B spec (high)  |
+//! |-----|--------|--------|----------------------|
+//! | 0   | 0.48µs | 0.95µs | 0.40µs ± 0.15µs      |
+//! | 1   | 0.95µs | 0.48µs | 0.80µs ± 0.15µs      |
+//!
+//! Every encoded bit ends low and MOSI holds its last level, so the line
+//! idles low between frames. The gap between frames is far longer than the
+//! 280µs reset time, so no reset bytes are sent.
+//!
+//! # Wire Format
+//!
+//! Colors are sent in GRB order, most significant bit first, to the first
+//! LED in the chain first. See [`encode`]. The encoder is pure, so encoded
+//! buffers are checked against golden ones on the host
+//! (`host-tests/tests/ws2812_encoder.rs`).
+
+use crate::rgb::Rgb;
+
+/// SPI bit pattern of a WS2812 0 bit.
+const ZERO_BITS: u32 = 0b100;
+
+/// SPI bit pattern of a WS2812 1 bit.
+const ONE_BITS: u32 = 0b110;
+
+/// Encoded size of one LED (24 data bits × 3 SPI bits), in bytes.
+pub const BYTES_PER_LED: usize = 9;
+
+/// Encodes colors into the SPI bit stream.
+///
+/// # Arguments
+///
+/// * `colors` - Color of each LED, first LED in the chain first
+/// * `out` - Buffer of at least `colors.len() * BYTES_PER_LED` bytes
+///
+/// # Returns
+///
+/// Number of bytes written to `out`
+pub fn encode(colors: &[Rgb], out: &mut [u8]) -> usize {
+    let mut len = 0;
+
+    for color in colors {
+        let mut bits: u32 = 0;
+        let mut pending = 0;
+
+        for byte in [color.g, color.r, color.b] {
+            for bit in (0..8).rev() {
+                let code = if byte & (1 << bit) != 0 {
+                    ONE_BITS
+                } else {
+                    ZERO_BITS
+                };
+                bits = (bits << 3) | code;
+                pending += 3;
+
+                if pending >= 8 {
+                    pending -= 8;
+                    out[len] = (bits >> pending) as u8;
+                    len += 1;
+                }
+            }
+        }
+    }
+
+    len
+}